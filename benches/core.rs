@@ -0,0 +1,140 @@
+//! Times the core operations under `Parameters::DEFAULT`, the keys being generated outside of the
+//! measured closures.
+//!
+//! `cargo bench --bench core` prints the average time of each operation. The allocating variants
+//! of LWE addition and keyswitching, which create a new ciphertext per step as the crate used to,
+//! are timed next to the in-place ones.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use ttfhe::poly::ResiduePoly;
+use ttfhe::prelude::*;
+use ttfhe::utils::decompose_rounded;
+
+/// Average duration of `f` over enough runs to last about `budget`.
+fn time(budget: Duration, mut f: impl FnMut()) -> Duration {
+    f();
+    let mut runs = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < budget {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+/// Keyswitch subtracting one scaled key ciphertext at a time, each scaling and subtraction
+/// allocating a new ciphertext.
+fn keyswitch_allocating(ct: &LweCiphertext, ksk: &KeySwitchingKey) -> LweCiphertext {
+    let mut res = LweCiphertext::trivial_encrypt(ct.body, ksk.keys[0].mask.len());
+    for (i, a) in ct.mask.iter().enumerate() {
+        let digits = decompose_rounded(*a, ksk.base_log, ksk.levels);
+        for (j, digit) in digits.into_iter().enumerate() {
+            res = res.sub(&ksk.keys[i * ksk.levels.0 + j].multiply_constant(digit));
+        }
+    }
+    res
+}
+
+fn main() {
+    let budget = Duration::from_secs(1);
+    let params = Parameters::DEFAULT;
+    let lwe_sk = lwe_keygen_for(&params, &mut rand::thread_rng());
+    let glwe_sk = keygen_for(&params, &mut rand::thread_rng());
+    let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+    let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+    let key = EvaluationKey {
+        bsk: &bsk,
+        ksk: &ksk,
+        params: &params,
+    };
+
+    let report = |name: &str, duration: Duration| println!("{name:<28} {duration:>12.2?}");
+
+    let lwe = LweCiphertext::encrypt(encode(3), &lwe_sk);
+    let other = LweCiphertext::encrypt(encode(4), &lwe_sk);
+    report(
+        "lwe encrypt",
+        time(budget, || {
+            black_box(LweCiphertext::encrypt(encode(3), &lwe_sk));
+        }),
+    );
+    report(
+        "lwe decrypt",
+        time(budget, || {
+            black_box(black_box(lwe.clone()).decrypt(&lwe_sk));
+        }),
+    );
+    report(
+        "lwe add (allocating)",
+        time(budget, || {
+            black_box(black_box(lwe.clone()).add(black_box(other.clone())));
+        }),
+    );
+    let mut acc = lwe.clone();
+    report(
+        "lwe add (in place)",
+        time(budget, || {
+            acc += black_box(&other);
+        }),
+    );
+
+    let extracted = GlweCiphertext::encrypt(encode(5), &glwe_sk).sample_extract();
+    report(
+        "keyswitch (allocating)",
+        time(budget, || {
+            black_box(keyswitch_allocating(black_box(&extracted), &ksk));
+        }),
+    );
+    report(
+        "keyswitch (in place)",
+        time(budget, || {
+            black_box(black_box(&extracted).keyswitch(&ksk));
+        }),
+    );
+    report(
+        "ksk generation",
+        time(budget, || {
+            black_box(compute_ksk(&glwe_sk.recode(), &lwe_sk));
+        }),
+    );
+
+    let glwe = GlweCiphertext::encrypt(encode(5), &glwe_sk);
+    report(
+        "glwe encrypt",
+        time(budget, || {
+            black_box(GlweCiphertext::encrypt(encode(5), &glwe_sk));
+        }),
+    );
+    report(
+        "glwe decrypt",
+        time(budget, || {
+            black_box(black_box(&glwe).decrypt(&glwe_sk));
+        }),
+    );
+
+    for n in [512, 1024] {
+        let lhs: ResiduePoly = ResiduePoly::random_with(n, &mut rand::thread_rng());
+        let rhs: ResiduePoly = ResiduePoly::random_with(n, &mut rand::thread_rng());
+        report(
+            &format!("poly mul N = {n}"),
+            time(budget, || {
+                black_box(black_box(&lhs).mul(black_box(&rhs)));
+            }),
+        );
+    }
+
+    let ggsw = GgswCiphertext::encrypt(1, &glwe_sk);
+    report(
+        "external product",
+        time(budget, || {
+            black_box(ggsw.external_product(black_box(&glwe)));
+        }),
+    );
+    report(
+        "bootstrap",
+        time(budget, || {
+            black_box(key.bootstrap(black_box(&lwe), |m| m));
+        }),
+    );
+}
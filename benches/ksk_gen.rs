@@ -0,0 +1,43 @@
+//! Times the generation of a keyswitching key under `Parameters::DEFAULT`, serially and with
+//! `compute_ksk`.
+//!
+//! `cargo bench --bench ksk_gen --features parallel` compares the serial generation with the
+//! parallel one; without the feature, both are serial.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use ttfhe::prelude::*;
+
+/// Average duration of `f` over enough runs to last about `budget`.
+fn time(budget: Duration, mut f: impl FnMut()) -> Duration {
+    f();
+    let mut runs = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < budget {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    let budget = Duration::from_secs(2);
+    let params = Parameters::DEFAULT;
+    let glwe_sk = keygen_for(&params, &mut rand::thread_rng()).recode();
+    let lwe_sk = lwe_keygen_for(&params, &mut rand::thread_rng());
+
+    let serial = time(budget, || {
+        black_box(ttfhe::lwe::compute_ksk_with(
+            &glwe_sk,
+            &lwe_sk,
+            &mut rand::thread_rng(),
+        ));
+    });
+    let parallel = time(budget, || {
+        black_box(compute_ksk(&glwe_sk, &lwe_sk));
+    });
+    println!(
+        "serial {serial:.2?}, compute_ksk {parallel:.2?}, speedup {:.1}x",
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
@@ -0,0 +1,46 @@
+//! Times the schoolbook and NTT products of random polynomials of the usual sizes.
+//!
+//! `cargo bench --bench poly_mul` prints the average time of each product and the speedup, from
+//! which `poly::NTT_THRESHOLD` is chosen.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use ttfhe::poly::ResiduePoly;
+
+/// Average duration of `f` over enough runs to last about `budget`.
+fn time(budget: Duration, mut f: impl FnMut()) -> Duration {
+    f();
+    let mut runs = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < budget {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    let budget = Duration::from_millis(500);
+    println!(
+        "{:>6} {:>14} {:>14} {:>8}",
+        "N", "schoolbook", "ntt", "speedup"
+    );
+    for log_n in 5..=12 {
+        let n = 1 << log_n;
+        let lhs: ResiduePoly = ResiduePoly::random_with(n, &mut rand::thread_rng());
+        let rhs: ResiduePoly = ResiduePoly::random_with(n, &mut rand::thread_rng());
+
+        let schoolbook = time(budget, || {
+            let mut res = ResiduePoly::zero(n);
+            res.add_mul_assign_schoolbook(black_box(&lhs), black_box(&rhs));
+            black_box(res);
+        });
+        let ntt = time(budget, || {
+            black_box(black_box(&lhs).mul_ntt(black_box(&rhs)));
+        });
+        println!(
+            "{n:>6} {schoolbook:>14.2?} {ntt:>14.2?} {:>7.1}x",
+            schoolbook.as_secs_f64() / ntt.as_secs_f64()
+        );
+    }
+}
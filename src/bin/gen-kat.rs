@@ -0,0 +1,14 @@
+//! Regenerates the known-answer test fixtures. Run with `cargo run --bin gen-kat`.
+
+use std::path::Path;
+use ttfhe::kat::{KnownAnswers, FIXTURES_PATH, GLWE_KEY_SEED, KSK_SEED, LWE_KEY_SEED};
+
+fn main() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURES_PATH);
+    let kat = KnownAnswers::generate(LWE_KEY_SEED, GLWE_KEY_SEED, KSK_SEED);
+
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, serde_json::to_string_pretty(&kat).unwrap() + "\n").unwrap();
+
+    println!("wrote {} vectors to {}", kat.vectors.len(), path.display());
+}
@@ -0,0 +1,49 @@
+//! Lists parameter sets meeting a security and correctness target, cheapest first.
+//! Run with `cargo run --release --bin param-search -- [message_bits] [log2_failure] [security_bits]`.
+
+use ttfhe::params::{search, SearchConstraints};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |i: usize, default: f64| -> f64 {
+        args.get(i).map_or(default, |a| {
+            a.parse()
+                .unwrap_or_else(|_| panic!("invalid argument {a:?}"))
+        })
+    };
+
+    let constraints = SearchConstraints {
+        message_bits: arg(0, 4.0) as usize,
+        max_failure_probability: f64::powf(2.0, arg(1, -40.0)),
+        min_security_bits: arg(2, 128.0),
+    };
+
+    let sets = match search(constraints) {
+        Ok(sets) => sets,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{:>5} {:>2} {:>5} {:>9} {:>10} {:>7} {:>7} {:>8} {:>12} {:>10}",
+        "n", "k", "N", "lg(σ_lwe)", "lg(σ_glwe)", "ks", "pbs", "security", "lg(failure)", "cost"
+    );
+    for set in sets {
+        let p = set.params;
+        println!(
+            "{:>5} {:>2} {:>5} {:>9.1} {:>10.1} {:>7} {:>7} {:>8.1} {:>12.1} {:>10.3e}",
+            p.lwe_dim.0,
+            p.glwe_k.0,
+            p.poly_size.0,
+            p.lwe_sigma.log2(),
+            p.glwe_sigma.log2(),
+            format!("{}x{}", p.ks_base_log.0, p.ks_levels.0),
+            format!("{}x{}", p.pbs_base_log.0, p.pbs_levels.0),
+            set.security_bits,
+            set.failure_probability.log2(),
+            set.cost
+        );
+    }
+}
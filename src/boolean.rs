@@ -0,0 +1,200 @@
+//! Boolean gates on encrypted bits, with keys split between a client and a server.
+//!
+//! A bit is encrypted as the message `0` or `1`. A binary gate adds its inputs, whose sum in
+//! `{0, 1, 2}` determines the output, and bootstraps the sum with the truth table of the gate as
+//! LUT, which also refreshes the noise. `not` is linear and needs no bootstrap.
+
+use crate::error::TfheError;
+use crate::ggsw::{compute_bsk, BootstrappingKey, EvaluationKey};
+use crate::glwe::{keygen_for, SecretKey};
+use crate::key_provider::KeyProvider;
+use crate::lwe::{compute_ksk, lwe_keygen_for, KeySwitchingKey, LweCiphertext, LweSecretKey};
+use crate::params::{Parameters, PbsOrder};
+use crate::utils::{decode_bits, encode_bits};
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Secret key of the client, which encrypts and decrypts bits.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ClientKey {
+    /// Key of the ciphertexts: the LWE key, or the recoded GLWE key under
+    /// `PbsOrder::KeyswitchBootstrap`.
+    pub sk: LweSecretKey,
+    /// GLWE key of the blind rotations, from which the server key is generated.
+    pub glwe_sk: SecretKey,
+}
+
+/// Evaluation keys of the server, which evaluates gates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerKey {
+    pub bsk: BootstrappingKey,
+    pub ksk: KeySwitchingKey,
+    pub params: Parameters,
+}
+
+/// Generates the keys of a client and of its server for `params`.
+/// Panics if `params` are invalid.
+pub fn gen_keys(params: &Parameters) -> (ClientKey, ServerKey) {
+    let glwe_sk = keygen_for(params, &mut thread_rng());
+    let sk = match params.pbs_order {
+        PbsOrder::BootstrapKeyswitch => lwe_keygen_for(params, &mut thread_rng()),
+        PbsOrder::KeyswitchBootstrap => glwe_sk.recode(),
+    };
+    let client_key = ClientKey { sk, glwe_sk };
+    let server_key = gen_server_key(&client_key).expect("in-memory keys are always available");
+    (client_key, server_key)
+}
+
+/// Generates the server key of the client whose keys are held by `provider`, for the parameters
+/// of its GLWE key. Under `PbsOrder::KeyswitchBootstrap` the LWE key of the blind rotations is
+/// internal to the server key: a fresh one is drawn, and the key of the ciphertexts is not
+/// fetched.
+/// Errors if the provider fails or if its keys are not for the same parameters.
+pub fn gen_server_key(provider: &dyn KeyProvider) -> Result<ServerKey, TfheError> {
+    let glwe_sk = provider.glwe_key()?;
+    let params = glwe_sk.params;
+    let lwe_sk = match params.pbs_order {
+        PbsOrder::BootstrapKeyswitch => provider.lwe_key()?,
+        PbsOrder::KeyswitchBootstrap => Cow::Owned(lwe_keygen_for(&params, &mut thread_rng())),
+    };
+    if lwe_sk.params != params {
+        return Err(TfheError::MalformedKey(
+            "LWE and GLWE keys of different parameters".to_string(),
+        ));
+    }
+
+    Ok(ServerKey {
+        bsk: compute_bsk(&lwe_sk, &glwe_sk)?,
+        ksk: compute_ksk(&glwe_sk.recode(), &lwe_sk),
+        params,
+    })
+}
+
+impl ClientKey {
+    pub fn encrypt(&self, b: bool) -> LweCiphertext {
+        LweCiphertext::encrypt(
+            encode_bits(b as u8, self.sk.params.message_bits as u32),
+            &self.sk,
+        )
+    }
+
+    /// Panics if `ct` does not encrypt a bit.
+    pub fn decrypt(&self, ct: &LweCiphertext) -> bool {
+        match decode_bits(
+            ct.clone().decrypt(&self.sk),
+            self.sk.params.message_bits as u32,
+        ) {
+            0 => false,
+            1 => true,
+            m => panic!("{m} is not an encrypted bit"),
+        }
+    }
+}
+
+impl ServerKey {
+    pub fn evaluation_key(&self) -> EvaluationKey<'_> {
+        EvaluationKey {
+            bsk: &self.bsk,
+            ksk: &self.ksk,
+            params: &self.params,
+        }
+    }
+
+    /// `!a`, without bootstrap.
+    pub fn not(&self, a: &LweCiphertext) -> LweCiphertext {
+        LweCiphertext::trivial_encrypt(
+            encode_bits(1, self.params.message_bits as u32),
+            a.mask.len(),
+        )
+        .sub(a)
+    }
+
+    /// `a & b`, with one bootstrap.
+    pub fn and(&self, a: &LweCiphertext, b: &LweCiphertext) -> LweCiphertext {
+        self.gate(a, b, |sum| (sum == 2) as u8)
+    }
+
+    /// `!(a & b)`, with one bootstrap.
+    pub fn nand(&self, a: &LweCiphertext, b: &LweCiphertext) -> LweCiphertext {
+        self.gate(a, b, |sum| (sum < 2) as u8)
+    }
+
+    /// `a | b`, with one bootstrap.
+    pub fn or(&self, a: &LweCiphertext, b: &LweCiphertext) -> LweCiphertext {
+        self.gate(a, b, |sum| (sum > 0) as u8)
+    }
+
+    /// `a ^ b`, with one bootstrap.
+    pub fn xor(&self, a: &LweCiphertext, b: &LweCiphertext) -> LweCiphertext {
+        self.gate(a, b, |sum| sum % 2)
+    }
+
+    /// Bootstraps the sum of `a` and `b` with the truth table `f` of the sum.
+    fn gate(&self, a: &LweCiphertext, b: &LweCiphertext, f: impl Fn(u8) -> u8) -> LweCiphertext {
+        self.evaluation_key()
+            .bootstrap(&a.clone().add(b.clone()), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boolean::gen_keys;
+    use crate::params::presets::TEST;
+    use crate::params::{Parameters, PbsOrder};
+
+    #[test]
+    fn test_gates() {
+        let (client_key, server_key) = gen_keys(&TEST);
+
+        for a in [false, true] {
+            let ct_a = client_key.encrypt(a);
+            assert_eq!(client_key.decrypt(&server_key.not(&ct_a)), !a);
+            for b in [false, true] {
+                let ct_b = client_key.encrypt(b);
+                let decrypt = |ct| client_key.decrypt(&ct);
+                assert_eq!(decrypt(server_key.and(&ct_a, &ct_b)), a & b, "{a} & {b}");
+                assert_eq!(
+                    decrypt(server_key.nand(&ct_a, &ct_b)),
+                    !(a & b),
+                    "{a} nand {b}"
+                );
+                assert_eq!(decrypt(server_key.or(&ct_a, &ct_b)), a | b, "{a} | {b}");
+                assert_eq!(decrypt(server_key.xor(&ct_a, &ct_b)), a ^ b, "{a} ^ {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_gates_message_bits() {
+        // Sums of two bits stay below `2^(message_bits - 1)` with 3 bits.
+        let params = Parameters {
+            message_bits: 3,
+            ..TEST
+        };
+        let (client_key, server_key) = gen_keys(&params);
+
+        let (a, b) = (client_key.encrypt(true), client_key.encrypt(false));
+        assert!(client_key.decrypt(&server_key.or(&a, &b)));
+        assert!(!client_key.decrypt(&server_key.and(&a, &b)));
+        assert!(client_key.decrypt(&server_key.xor(&a, &b)));
+        assert!(!client_key.decrypt(&server_key.not(&a)));
+    }
+
+    #[test]
+    fn test_keyswitch_bootstrap_order() {
+        let params = Parameters {
+            pbs_order: PbsOrder::KeyswitchBootstrap,
+            ..TEST
+        };
+        let (client_key, server_key) = gen_keys(&params);
+
+        // A half adder chained into a NAND.
+        let (a, b) = (client_key.encrypt(true), client_key.encrypt(true));
+        let sum = server_key.xor(&a, &b);
+        let carry = server_key.and(&a, &b);
+        assert!(!client_key.decrypt(&sum));
+        assert!(client_key.decrypt(&carry));
+        assert!(client_key.decrypt(&server_key.nand(&sum, &server_key.not(&carry))));
+    }
+}
@@ -0,0 +1,180 @@
+//! Evaluation of finite automata over encrypted symbols, the state staying encrypted.
+//!
+//! A step looks the next state up from `state * alphabet_size + symbol` with one bootstrap, so the
+//! number of states times the size of the alphabet is limited to `P/2`.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::LweCiphertext;
+use crate::P;
+
+/// Deterministic finite automaton.
+#[derive(Clone, Debug)]
+pub struct Dfa {
+    /// Next state of every state, for every symbol.
+    transitions: Vec<Vec<u8>>,
+    accepting: Vec<u8>,
+}
+
+impl Dfa {
+    /// Automaton whose `s`-th row of `transitions` gives the next state from state `s` for every
+    /// symbol of the alphabet.
+    /// Errors if the rows do not all have the size of the alphabet, if a state is out of range, or
+    /// if the number of states times the size of the alphabet exceeds `P/2`.
+    pub fn new(transitions: Vec<Vec<u8>>, accepting: &[u8]) -> Result<Self, String> {
+        let num_states = transitions.len();
+        let alphabet_size = transitions.first().map_or(0, |row| row.len());
+        if num_states == 0 || alphabet_size == 0 {
+            return Err("the automaton has no states or no symbols".to_string());
+        }
+        if num_states * alphabet_size > P / 2 {
+            return Err(format!(
+                "{num_states} states and {alphabet_size} symbols exceed the {} inputs of a bootstrap",
+                P / 2
+            ));
+        }
+        for (s, row) in transitions.iter().enumerate() {
+            if row.len() != alphabet_size {
+                return Err(format!(
+                    "state {s} has {} transitions for {alphabet_size} symbols",
+                    row.len()
+                ));
+            }
+        }
+        if let Some(s) = transitions
+            .iter()
+            .flatten()
+            .chain(accepting)
+            .find(|&&s| s as usize >= num_states)
+        {
+            return Err(format!("state {s} is out of the {num_states} states"));
+        }
+
+        Ok(Dfa {
+            transitions,
+            accepting: accepting.to_vec(),
+        })
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn alphabet_size(&self) -> usize {
+        self.transitions[0].len()
+    }
+
+    /// Next state in the clear.
+    pub fn step(&self, state: u8, symbol: u8) -> u8 {
+        self.transitions[state as usize][symbol as usize]
+    }
+
+    /// Runs the automaton from the encrypted `state` over the encrypted `symbols`, with one
+    /// bootstrap per symbol. Symbols out of the alphabet give meaningless states, state `0` past the
+    /// last state.
+    pub fn run(
+        &self,
+        state: &LweCiphertext,
+        symbols: &[LweCiphertext],
+        key: &EvaluationKey,
+    ) -> LweCiphertext {
+        let alphabet_size = self.alphabet_size();
+        let table_size = self.num_states() * alphabet_size;
+        symbols.iter().fold(state.clone(), |state, symbol| {
+            let mut index = state;
+            index.multiply_constant_assign(alphabet_size as u64);
+            // The LUT covers the `P/2` inputs of a bootstrap, beyond the table when it is smaller.
+            key.bootstrap(&index.add(symbol.clone()), |m| {
+                if (m as usize) < table_size {
+                    self.step(m / alphabet_size as u8, m % alphabet_size as u8)
+                } else {
+                    0
+                }
+            })
+        })
+    }
+
+    /// Returns an encryption of `1` if the encrypted `state` is accepting, and of `0` otherwise.
+    pub fn accepts(&self, state: &LweCiphertext, key: &EvaluationKey) -> LweCiphertext {
+        key.bootstrap(state, |s| self.accepting.contains(&s) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dfa::Dfa;
+    use crate::ggsw::TestKeys;
+    use crate::lwe::LweCiphertext;
+    use crate::params::presets::TEST;
+    use crate::utils::{decode, encode};
+    use rand::{thread_rng, Rng};
+
+    /// Recognizes the binary strings containing `101`.
+    fn pattern_matcher() -> Dfa {
+        Dfa::new(vec![vec![0, 1], vec![2, 1], vec![0, 3], vec![3, 3]], &[3]).unwrap()
+    }
+
+    #[test]
+    fn test_run() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let dfa = pattern_matcher();
+        let random: Vec<u8> = (0..6).map(|_| thread_rng().gen_range(0..2)).collect();
+        for input in [vec![0, 1, 0, 1], vec![1, 1, 0, 0, 1, 1], random] {
+            let symbols: Vec<LweCiphertext> = input
+                .iter()
+                .map(|&s| LweCiphertext::encrypt(encode(s), &keys.lwe_sk))
+                .collect();
+            let start = LweCiphertext::encrypt(encode(0), &keys.lwe_sk);
+
+            let state = dfa.run(&start, &symbols, &key);
+            let expected = input.iter().fold(0, |s, &symbol| dfa.step(s, symbol));
+            assert_eq!(
+                decode(state.clone().decrypt(&keys.lwe_sk)),
+                expected,
+                "{input:?}"
+            );
+            let accepted = dfa.accepts(&state, &key);
+            assert_eq!(
+                decode(accepted.decrypt(&keys.lwe_sk)),
+                (expected == 3) as u8,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_partial_table() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        // Counts the `1`s modulo 3, on 6 of the 8 inputs of a bootstrap.
+        let dfa = Dfa::new(vec![vec![0, 1], vec![1, 2], vec![2, 0]], &[0]).unwrap();
+        let input = [1, 1, 0, 1, 1];
+        let symbols: Vec<LweCiphertext> = input
+            .iter()
+            .map(|&s| LweCiphertext::encrypt(encode(s), &keys.lwe_sk))
+            .collect();
+        let start = LweCiphertext::encrypt(encode(0), &keys.lwe_sk);
+        let state = dfa.run(&start, &symbols, &key);
+        assert_eq!(decode(state.decrypt(&keys.lwe_sk)), 1);
+
+        // Past the last state, the index falls out of the table.
+        let past = LweCiphertext::encrypt(encode(3), &keys.lwe_sk);
+        let state = dfa.run(&past, &symbols[..1], &key);
+        assert_eq!(decode(state.decrypt(&keys.lwe_sk)), 0);
+    }
+
+    #[test]
+    fn test_new_rejected() {
+        assert!(Dfa::new(vec![], &[]).is_err());
+        let err = Dfa::new(vec![vec![0, 1, 2]; 3], &[]).err().unwrap();
+        assert!(err.contains("exceed"), "{err}");
+        let err = Dfa::new(vec![vec![0, 1], vec![0]], &[]).err().unwrap();
+        assert!(err.contains("state 1 has 1 transitions"), "{err}");
+        let err = Dfa::new(vec![vec![0, 2], vec![0, 1]], &[]).err().unwrap();
+        assert!(err.contains("state 2"), "{err}");
+        assert!(Dfa::new(vec![vec![0, 1], vec![0, 1]], &[2]).is_err());
+        assert_eq!(pattern_matcher().num_states(), 4);
+    }
+}
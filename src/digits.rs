@@ -0,0 +1,172 @@
+//! Extraction of the digits of messages that use the whole plaintext space, of `P = 2^message_bits`
+//! messages for the `message_bits` of the parameters.
+//!
+//! A bootstrap evaluates functions of messages below `P/2` only, since the negacyclic rotation
+//! negates the outputs of the messages above. Sums of bootstrapped values, such as a digit with
+//! its accumulated carries, may still reach `P`. Their bits are extracted from the least
+//! significant one: multiplying the ciphertext by `2^(message_bits - 1 - i)` moves bit `i` of the
+//! message to the MSB, whose value is the sign that the negacyclic rotation of a constant
+//! polynomial reads. Once extracted, the bit is subtracted from the ciphertext, so that the next
+//! one is the lowest remaining bit. Digits are then assembled from their bits, with one bootstrap
+//! per extracted bit in total.
+//!
+//! Multiplying the ciphertext multiplies its noise, by up to `P/2` for the lowest bit, but the
+//! sign is read with a margin of `q/4` instead of `q/(2P)`.
+
+use crate::ggsw::EvaluationKey;
+use crate::glwe::GlweCiphertext;
+use crate::lwe::LweCiphertext;
+use crate::poly::ResiduePoly;
+
+/// Splits the message `m` in `[0, 2^message_bits)` of `ct`, for the `message_bits` of the
+/// parameters of `key`, into `num_digits` digits of `digit_bits` bits, from the least significant
+/// one, returned as encryptions under the key of `ct` with the same encoding.
+/// Bits of `m` above `digit_bits * num_digits` are dropped: the digits are those of
+/// `m mod 2^(digit_bits * num_digits)`.
+/// Errors if there are no digits, or if they have more bits than the messages.
+pub fn extract_digits(
+    ct: &LweCiphertext,
+    digit_bits: usize,
+    num_digits: usize,
+    key: &EvaluationKey,
+) -> Result<Vec<LweCiphertext>, String> {
+    let message_bits = key.params.message_bits;
+    let num_bits = digit_bits * num_digits;
+    if num_bits == 0 || num_bits > message_bits {
+        return Err(format!(
+            "{num_digits} digits of {digit_bits} bits do not split a {message_bits}-bit message"
+        ));
+    }
+
+    let bits = extract_bits(ct, num_bits, key);
+    Ok(bits
+        .chunks(digit_bits)
+        .map(|digit| {
+            let dim = digit[0].mask.len();
+            digit
+                .iter()
+                .enumerate()
+                .fold(LweCiphertext::zero(dim), |acc, (b, bit)| {
+                    acc.add(bit.multiply_constant(1 << b))
+                })
+        })
+        .collect())
+}
+
+/// Encryptions of the `num_bits` least significant bits of the message of `ct`, with one
+/// bootstrap each.
+fn extract_bits(ct: &LweCiphertext, num_bits: usize, key: &EvaluationKey) -> Vec<LweCiphertext> {
+    let message_bits = key.params.message_bits;
+    // Half of the encoding of 1: the rotation gives `-half` for a positive phase and `half` for a
+    // negative one, which become `0` and `1` once `half` is added.
+    let half = 1u64 << (63 - message_bits);
+    let lut = GlweCiphertext {
+        body: ResiduePoly {
+            coefs: vec![half.wrapping_neg(); key.params.poly_size.0],
+        },
+        ..GlweCiphertext::zero(key.params.glwe_k, key.params.poly_size)
+    };
+
+    let mut rest = ct.clone();
+    (0..num_bits)
+        .map(|i| {
+            // Centers the phase in its half of the torus.
+            let shifted = rest
+                .multiply_constant(1 << (message_bits - 1 - i))
+                .add_plaintext(1 << 62);
+
+            let mut bit = key.bootstrap_lut_poly(&shifted, &lut);
+            bit.body = bit.body.wrapping_add(half);
+
+            rest = rest.clone().sub(&bit.multiply_constant(1 << i));
+            bit
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::digits::extract_digits;
+    use crate::ggsw::TestKeys;
+    use crate::lwe::LweCiphertext;
+    use crate::params::presets::{TEST, TOY};
+    use crate::params::{DecompositionLevelCount, Parameters};
+    use crate::utils::{decode, decode_bits, encode};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_extract_digits() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        for m in 0..16 {
+            let ct = LweCiphertext::encrypt(encode(m), &keys.lwe_sk);
+            let digits: Vec<u8> = extract_digits(&ct, 2, 2, &key)
+                .unwrap()
+                .into_iter()
+                .map(|digit| decode(digit.decrypt(&keys.lwe_sk)))
+                .collect();
+            assert_eq!(digits, [m % 4, m / 4], "{m}");
+        }
+
+        let ct = LweCiphertext::encrypt(encode(13), &keys.lwe_sk);
+        let bits: Vec<u8> = extract_digits(&ct, 1, 3, &key)
+            .unwrap()
+            .into_iter()
+            .map(|bit| decode(bit.decrypt(&keys.lwe_sk)))
+            .collect();
+        assert_eq!(bits, [1, 0, 1]);
+
+        assert!(extract_digits(&ct, 2, 3, &key).is_err());
+        assert!(extract_digits(&ct, 0, 2, &key).is_err());
+    }
+
+    #[test]
+    fn test_extract_nibbles() {
+        // With the decompositions of `TOY`, the noise of a bootstrap, about 2^53, does not leave
+        // room for the sum of the bits of a nibble below 2^55, half a box of an 8-bit message.
+        let params = Parameters {
+            message_bits: 8,
+            ks_levels: DecompositionLevelCount(5),
+            pbs_levels: DecompositionLevelCount(3),
+            ..TOY
+        };
+        let keys = TestKeys::new(&params);
+        let key = keys.evaluation_key();
+
+        // 8 bootstraps per message: the extremes of the nibbles and random messages, rather than
+        // all 256.
+        let random = (0..16).map(|_| thread_rng().gen::<u8>());
+        for m in [0, 1, 15, 16, 0x5a, 0xa5, 0xf0, 255]
+            .into_iter()
+            .chain(random)
+        {
+            let ct = LweCiphertext::encrypt((m as u64) << 56, &keys.lwe_sk);
+            let nibbles: Vec<u8> = extract_digits(&ct, 4, 2, &key)
+                .unwrap()
+                .into_iter()
+                .map(|nibble| decode_bits(nibble.decrypt(&keys.lwe_sk), 8))
+                .collect();
+            assert_eq!(nibbles, [m % 16, m / 16], "{m}");
+        }
+        assert!(extract_digits(&LweCiphertext::zero(TOY.lwe_dim.0), 4, 3, &key).is_err());
+    }
+
+    #[test]
+    fn test_normalize_carries() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        // A 2-bit block accumulating the sum of three bootstrapped 2-bit digits.
+        let values: Vec<u8> = (0..3).map(|_| thread_rng().gen_range(0..4)).collect();
+        let block = values
+            .iter()
+            .map(|&v| key.bootstrap(&LweCiphertext::encrypt(encode(v), &keys.lwe_sk), |m| m))
+            .fold(LweCiphertext::zero(TEST.lwe_dim.0), |acc, ct| acc.add(ct));
+
+        let sum: u8 = values.iter().sum();
+        let normalized = extract_digits(&block, 2, 2, &key).unwrap();
+        assert_eq!(decode(normalized[0].clone().decrypt(&keys.lwe_sk)), sum % 4);
+        assert_eq!(decode(normalized[1].clone().decrypt(&keys.lwe_sk)), sum / 4);
+    }
+}
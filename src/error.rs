@@ -0,0 +1,54 @@
+//! Errors of the fallible operations of the crate.
+
+use std::fmt;
+
+/// Error of a fallible encoding, decryption or key operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TfheError {
+    /// The message `msg` is not below the plaintext modulus `max`.
+    MessageOutOfRange { msg: u64, max: u64 },
+    /// Bytes or values that do not describe a valid plaintext or ciphertext.
+    InvalidEncoding(String),
+    /// A key of dimension `expected` was given an input of dimension `found`.
+    KeyMismatchDimension { expected: usize, found: usize },
+    /// A key of polynomial size `expected` was given an input of polynomial size `found`.
+    KeyMismatchPolynomialSize { expected: usize, found: usize },
+    /// A key whose parts do not fit together, e.g. truncated.
+    MalformedKey(String),
+    /// A `key_provider::KeyProvider` failed to provide a key.
+    KeyUnavailable(String),
+    /// The noise of a decrypted phase, of absolute value `noise`, is not below `bound`: the
+    /// message may have been corrupted.
+    NoiseOverflow { noise: u64, bound: u64 },
+}
+
+impl fmt::Display for TfheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TfheError::MessageOutOfRange { msg, max } => {
+                write!(f, "message {msg} is out of the plaintext space [0, {max})")
+            }
+            TfheError::InvalidEncoding(reason) => write!(f, "invalid encoding: {reason}"),
+            TfheError::KeyMismatchDimension { expected, found } => {
+                write!(
+                    f,
+                    "key of dimension {expected} for an input of dimension {found}"
+                )
+            }
+            TfheError::KeyMismatchPolynomialSize { expected, found } => write!(
+                f,
+                "key of polynomial size {expected} for an input of polynomial size {found}"
+            ),
+            TfheError::MalformedKey(reason) => write!(f, "malformed key: {reason}"),
+            TfheError::KeyUnavailable(reason) => write!(f, "key unavailable: {reason}"),
+            TfheError::NoiseOverflow { noise, bound } => write!(
+                f,
+                "noise of 2^{:.1} overflows the bound of 2^{:.1}",
+                (*noise as f64).log2(),
+                (*bound as f64).log2()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TfheError {}
@@ -0,0 +1,155 @@
+//! Bodies of the fuzz targets of `fuzz/`. They are also run on random inputs by the test suite.
+
+use crate::ggsw::decomposition_8_2;
+use crate::lwe::{compute_ksk, decomposition_4_4, lwe_keygen, KeySwitchingKey, LweCiphertext};
+use crate::params::{
+    DecompositionBaseLog as BaseLog, DecompositionLevelCount as Levels, Parameters,
+};
+use crate::serialization::Serializable;
+use crate::utils::{decompose_rounded, recompose};
+use crate::{glwe::keygen, k, LWE_DIM, N};
+use std::sync::OnceLock;
+
+/// Accepted inputs must serialize back to the exact same bytes.
+pub fn lwe_from_bytes(data: &[u8]) {
+    if let Ok(ct) = LweCiphertext::from_bytes_for(data, &Parameters::DEFAULT) {
+        assert_eq!(ct.to_bytes(), data);
+    }
+}
+
+pub fn ksk_from_bytes(data: &[u8]) {
+    if let Ok(ksk) = KeySwitchingKey::from_bytes_for(data, &Parameters::DEFAULT) {
+        assert_eq!(ksk.to_bytes(), data);
+    }
+}
+
+/// Keyswitches an arbitrary ciphertext of dimension `kN`, whose coefficients are read from `data`.
+pub fn keyswitch(data: &[u8]) {
+    static KSK: OnceLock<KeySwitchingKey> = OnceLock::new();
+    let ksk = KSK.get_or_init(|| compute_ksk(&keygen().recode(), &lwe_keygen()));
+
+    let words: Vec<u64> = data
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    if words.is_empty() {
+        return;
+    }
+
+    let ct = LweCiphertext {
+        mask: (1..=k * N).map(|i| words[i % words.len()]).collect(),
+        body: words[0],
+    };
+    let res = ct.keyswitch(ksk);
+    assert_eq!(res.mask.len(), LWE_DIM);
+}
+
+/// Decomposes the first 8 bytes of `data` and checks the digits recompose to the closest
+/// representable value. The next two bytes, if any, select the base and the number of levels of a
+/// third decomposition.
+pub fn decomposition(data: &[u8]) {
+    let Some(bytes) = data.get(..8) else {
+        return;
+    };
+    let val = u64::from_le_bytes(bytes.try_into().unwrap());
+
+    let (low, high) = decomposition_8_2(val);
+    let digits_8_2 = [low as i64 as u64, high as i64 as u64];
+    let digits_4_4 = decomposition_4_4(val);
+    assert_eq!(
+        decompose_rounded(val, BaseLog(8), Levels(2)),
+        digits_8_2,
+        "{val:#x}"
+    );
+    assert_eq!(
+        decompose_rounded(val, BaseLog(4), Levels(4)),
+        digits_4_4,
+        "{val:#x}"
+    );
+
+    let mut cases = vec![(digits_8_2.to_vec(), 8), (digits_4_4.to_vec(), 4)];
+    if let Some(&[b, l]) = data.get(8..10) {
+        let base_log = 1 + b as usize % 32;
+        let levels = 1 + l as usize % (63 / base_log);
+        cases.push((
+            decompose_rounded(val, BaseLog(base_log), Levels(levels)),
+            base_log,
+        ));
+    }
+
+    for (digits, base_log) in &cases {
+        let (digits, base_log) = (&digits[..], *base_log);
+        // Signed digits lie in [-B/2, B/2).
+        let half_base = 1i64 << (base_log - 1);
+        for &d in digits {
+            assert!(
+                (-half_base..half_base).contains(&(d as i64)),
+                "{val:#x}: {digits:?}"
+            );
+        }
+
+        // The LSBs are rounded away: the error is at most half of the last level.
+        let error = recompose(digits, BaseLog(base_log)).wrapping_sub(val) as i64;
+        let max_error = 1u64 << (63 - base_log * digits.len());
+        assert!(error.unsigned_abs() <= max_error, "{val:#x}: {digits:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzzing;
+    use crate::lwe::{KeySwitchingKey, LweCiphertext};
+    use crate::params::{DecompositionBaseLog as BaseLog, DecompositionLevelCount as Levels};
+    use crate::serialization::Serializable;
+    use rand::{thread_rng, Rng};
+
+    /// Runs `target` on random inputs, then on mutations of `seed`.
+    fn smoke(target: fn(&[u8]), seed: &[u8], iterations: usize) {
+        let mut rng = thread_rng();
+        for _ in 0..iterations {
+            let len = rng.gen_range(0..64);
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            target(&data);
+
+            let mut mutated = seed.to_vec();
+            if !mutated.is_empty() {
+                let i = rng.gen_range(0..mutated.len());
+                mutated[i] = rng.gen();
+                mutated.truncate(rng.gen_range(i..=mutated.len()));
+            }
+            target(&mutated);
+        }
+    }
+
+    #[test]
+    fn test_lwe_from_bytes() {
+        smoke(
+            fuzzing::lwe_from_bytes,
+            &LweCiphertext::default().to_bytes(),
+            1000,
+        );
+    }
+
+    #[test]
+    fn test_ksk_from_bytes() {
+        let ksk = KeySwitchingKey {
+            base_log: BaseLog(4),
+            levels: Levels(4),
+            keys: vec![LweCiphertext::default(); 16],
+        };
+        smoke(fuzzing::ksk_from_bytes, &ksk.to_bytes(), 100);
+    }
+
+    #[test]
+    fn test_keyswitch() {
+        smoke(fuzzing::keyswitch, &[0xff; 64], 5);
+    }
+
+    #[test]
+    fn test_decomposition() {
+        smoke(fuzzing::decomposition, &0x00f8u64.to_le_bytes(), 10_000);
+        // 16 MSBs equal to 0x00f8: the second nibble receives a carry and used to become the digit 16.
+        fuzzing::decomposition(&(0x00f8u64 << 48).to_le_bytes());
+        fuzzing::decomposition(&u64::MAX.to_le_bytes());
+    }
+}
@@ -1,81 +1,341 @@
-use crate::utils::round_value;
-use crate::LWE_DIM;
-use crate::{glwe::GlweCiphertext, k, poly::ResiduePoly, ELL, N};
-use crate::{glwe::SecretKey, lwe::LweSecretKey};
+use crate::error::TfheError;
+use crate::glwe::SecretKey;
+use crate::lut::Lut;
+use crate::lwe::{KeyDistribution, LweSecretKey};
+use crate::lwe::{KeySwitchingKey, LweCiphertext};
+use crate::params::{
+    BlindRotationAlgorithm, DecompositionBaseLog, DecompositionLevelCount, Parameters, PbsOrder,
+};
+use crate::size::SizeInfo;
+use crate::utils::{decompose_rounded, par_map, round_value, short_digest};
+use crate::{glwe::GlweCiphertext, poly::ResiduePoly};
+use rand::{thread_rng, CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
 
-pub type BootstrappingKey = Vec<GgswCiphertext>;
+/// GGSW encryptions of a LWE secret key under a GLWE key, see `compute_bsk`. Dereferences to its
+/// ciphertexts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BootstrappingKey {
+    pub(crate) ggsws: Vec<GgswCiphertext>,
+    /// Distribution of the encrypted key, which sets the number of ciphertexts per coefficient of
+    /// `Ginx` keys.
+    pub distribution: KeyDistribution,
+}
+
+impl Deref for BootstrappingKey {
+    type Target = [GgswCiphertext];
+
+    fn deref(&self) -> &[GgswCiphertext] {
+        &self.ggsws
+    }
+}
+
+impl SizeInfo for BootstrappingKey {
+    fn heap_bytes(&self) -> usize {
+        self.ggsws.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // ciphertexts and distribution
+        self.ggsws.serialized_bytes() + 4
+    }
+
+    fn element_count(&self) -> usize {
+        self.ggsws.element_count()
+    }
+}
+
+/// Keys bootstrapping LWE ciphertexts back under their own key.
+pub struct EvaluationKey<'a> {
+    pub bsk: &'a BootstrappingKey,
+    /// Keyswitching key from the recoded GLWE key back to the LWE key.
+    pub ksk: &'a KeySwitchingKey,
+    pub params: &'a Parameters,
+}
 
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct GgswCiphertext {
-    z_m_gt: Vec<GlweCiphertext>,
+    pub(crate) z_m_gt: Vec<GlweCiphertext>,
+    /// lg(B) of the gadget decomposition.
+    pub(crate) base_log: usize,
+    pub(crate) levels: usize,
+    /// Message of a trivial encryption, for which external products reduce to scalings.
+    #[serde(skip)]
+    trivial_msg: Option<u8>,
+}
+
+/// Prints the decomposition, the dimensions and a digest of the coefficients instead of the
+/// coefficients.
+impl fmt::Debug for GgswCiphertext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefs: Vec<u64> = self
+            .z_m_gt
+            .iter()
+            .flat_map(|row| row.mask.iter().chain([&row.body]))
+            .flat_map(|poly| poly.coefs.iter().copied())
+            .collect();
+        let first = self.z_m_gt.first();
+        f.debug_struct("GgswCiphertext")
+            .field("base_log", &self.base_log)
+            .field("levels", &self.levels)
+            .field("k", &first.map_or(0, |row| row.mask.len()))
+            .field("poly_size", &first.map_or(0, |row| row.body.coefs.len()))
+            .field("digest", &format_args!("{}", short_digest(&coefs)))
+            .finish()
+    }
+}
+
+impl SizeInfo for GgswCiphertext {
+    fn heap_bytes(&self) -> usize {
+        self.z_m_gt.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // rows, base_log and levels
+        self.z_m_gt.serialized_bytes() + 8 + 8
+    }
+
+    fn element_count(&self) -> usize {
+        self.z_m_gt.element_count()
+    }
 }
 
 impl GgswCiphertext {
+    /// Encrypts `msg` under `sk`, with the decomposition of the parameters of `sk`.
     pub fn encrypt(msg: u8, sk: &SecretKey) -> Self {
+        Self::encrypt_with(msg, sk, &mut thread_rng())
+    }
+
+    /// Encrypts `msg` under `sk`, drawing the masks and the noises from `rng`.
+    pub fn encrypt_with<R: Rng + CryptoRng>(msg: u8, sk: &SecretKey, rng: &mut R) -> Self {
+        let mut poly = ResiduePoly::zero(sk.params.poly_size.0);
+        poly.coefs[0] = msg as u64;
+        Self::encrypt_poly_with(&poly, sk, rng)
+    }
+
+    /// Encrypts the polynomial `msg`, whose coefficients are small integers, `-1` being `u64::MAX`.
+    /// The external product by the result multiplies the message of a GLWE ciphertext by `msg`,
+    /// and its noise by the sum of the absolute values of the coefficients of `msg`.
+    /// Panics if `msg` does not have the degree of `sk`.
+    pub fn encrypt_poly(msg: &ResiduePoly, sk: &SecretKey) -> Self {
+        Self::encrypt_poly_with(msg, sk, &mut thread_rng())
+    }
+
+    /// Encrypts the polynomial `msg` under `sk`, drawing the masks and the noises from `rng`.
+    pub fn encrypt_poly_with<R: Rng + CryptoRng>(
+        msg: &ResiduePoly,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> Self {
+        let glwe_k = sk.polys.len();
+        let (base_log, levels) = (sk.params.pbs_base_log.0, sk.params.pbs_levels.0);
+        assert_eq!(
+            msg.coefs.len(),
+            sk.params.poly_size.0,
+            "message and key dimensions differ"
+        );
+
         // initialize Z
-        let mut z_m_gt: Vec<GlweCiphertext> = (0..(k + 1) * ELL)
-            .map(|_| GlweCiphertext::encrypt(0, sk))
+        let mut z_m_gt: Vec<GlweCiphertext> = (0..(glwe_k + 1) * levels)
+            .map(|_| GlweCiphertext::encrypt_with(0, sk, rng))
             .collect();
 
         // m * g, g being [q/B, ..., q/B^l]
-        let mut mg = [0u64; ELL];
-        mg[0] = (msg as u64) << 56;
-        mg[1] = (msg as u64) << 48;
+        let mg: Vec<ResiduePoly> = (0..levels)
+            .map(|l| ResiduePoly {
+                coefs: msg
+                    .coefs
+                    .iter()
+                    .map(|c| c.wrapping_shl((64 - base_log * (l + 1)) as u32))
+                    .collect(),
+            })
+            .collect();
 
         // add m * G^t to Z
         for i in 0..z_m_gt.len() {
-            if i < k * ELL {
-                for j in 0..z_m_gt[i].mask.len() {
-                    z_m_gt[i].mask[j].add_constant_assign(mg[i % ELL]);
-                }
+            if i < glwe_k * levels {
+                z_m_gt[i].mask[i / levels].add_assign(&mg[i % levels]);
+            } else {
+                z_m_gt[i].body.add_assign(&mg[i % levels]);
+            }
+        }
+
+        GgswCiphertext {
+            z_m_gt,
+            base_log,
+            levels,
+            trivial_msg: None,
+        }
+    }
+
+    /// Trivially encrypts `msg` with the dimensions and the decomposition of `params`.
+    pub fn trivial_encrypt(msg: u8, params: &Parameters) -> Self {
+        let levels = params.pbs_levels.0;
+        let mut z_m_gt = vec![
+            GlweCiphertext::zero(params.glwe_k, params.poly_size);
+            (params.glwe_k.0 + 1) * levels
+        ];
+        for (i, row) in z_m_gt.iter_mut().enumerate() {
+            let mg = (msg as u64) << (64 - params.pbs_base_log.0 * (i % levels + 1));
+            if i < params.glwe_k.0 * levels {
+                row.mask[i / levels].add_constant_assign(mg);
             } else {
-                z_m_gt[i].body.add_constant_assign(mg[i % ELL]);
+                row.body.add_constant_assign(mg);
             }
         }
 
-        GgswCiphertext { z_m_gt }
+        GgswCiphertext {
+            z_m_gt,
+            base_log: params.pbs_base_log.0,
+            levels,
+            trivial_msg: Some(msg),
+        }
+    }
+
+    /// Whether `self` was built by `trivial_encrypt`.
+    pub fn is_trivial(&self) -> bool {
+        self.trivial_msg.is_some()
     }
 
     // The last `GlweCiphertext` of `z_m_gt` is an encryption of msg * q/B^l
     pub fn decrypt(self, sk: &SecretKey) -> u8 {
-        ((((&self.z_m_gt[self.z_m_gt.len() - 1].decrypt(sk) >> 47) + 1) >> 1) % 16) as u8
+        let shift = 64 - self.base_log * self.levels;
+        ((((self.z_m_gt[self.z_m_gt.len() - 1].decrypt(sk) >> (shift - 1)) + 1) >> 1) % 16) as u8
     }
 
     /// Performs a product (GGSW x GLWE) -> GLWE.
+    /// The product by a trivial encryption of `m` is `m * ct`, without decomposition.
     pub fn external_product(&self, ct: &GlweCiphertext) -> GlweCiphertext {
-        let g_inverse_ct = apply_g_inverse(ct);
+        let mut res = ct.clone();
+        self.external_product_assign(&mut res, &mut ExternalProductScratch::default());
+        res
+    }
 
-        let mut res = GlweCiphertext::default();
-        for i in 0..(k + 1) * ELL {
-            for j in 0..k {
-                res.mask[j].add_assign(&g_inverse_ct[i].mul(&self.z_m_gt[i].mask[j]));
+    /// Returns `ct1` if `self` encrypts `1` and `ct0` if it encrypts `0`, see `cmux`.
+    pub fn cmux(&self, ct0: &GlweCiphertext, ct1: &GlweCiphertext) -> GlweCiphertext {
+        cmux(self, ct0, ct1)
+    }
+
+    /// GGSW encryption of the product of the messages of `self` and `rhs`, with the decomposition
+    /// of `rhs`. For bits, this is their AND, usable as a CMUX selector.
+    /// Every row of the result is the external product of `self` and the row of `rhs`. It carries
+    /// the noise of the row times the message of `self`, plus the noise of an external product by
+    /// `self`, which amplifies the noise of `self` by the decomposition. Products are thus chained
+    /// with fresh ciphertexts on the left, `fresh.mul(&acc)`, for the noise to grow additively.
+    /// The rounding of the decomposition does not shrink with the level of the row, so
+    /// `base_log * levels` must leave room for the noise of the rows: with the keys of
+    /// `params::presets::TEST`, 2 levels of 8 bits fail after one product, while with 4 levels the
+    /// noise of a CMUX stays near 2^51 over several products.
+    pub fn mul(&self, rhs: &Self) -> Self {
+        GgswCiphertext {
+            z_m_gt: rhs
+                .z_m_gt
+                .iter()
+                .map(|row| self.external_product(row))
+                .collect(),
+            base_log: rhs.base_log,
+            levels: rhs.levels,
+            trivial_msg: self
+                .trivial_msg
+                .zip(rhs.trivial_msg)
+                .map(|(m1, m2)| m1.wrapping_mul(m2)),
+        }
+    }
+
+    /// Replaces `acc` by the external product of `self` and `acc`, with the buffers of `scratch`.
+    /// The result is the one of `external_product`.
+    pub fn external_product_assign(
+        &self,
+        acc: &mut GlweCiphertext,
+        scratch: &mut ExternalProductScratch,
+    ) {
+        if let Some(msg) = self.trivial_msg {
+            for poly in acc.mask.iter_mut().chain([&mut acc.body]) {
+                for c in poly.coefs.iter_mut() {
+                    *c = c.wrapping_mul(msg as u64);
+                }
             }
-            res.body
-                .add_assign(&g_inverse_ct[i].mul(&self.z_m_gt[i].body));
+            return;
         }
-        res
+
+        // The decomposed ciphertext is read from `scratch.glwe` while `acc` is overwritten.
+        std::mem::swap(acc, &mut scratch.glwe);
+        set_zero(acc, scratch.glwe.mask.len(), scratch.glwe.body.coefs.len());
+        self.add_product(&scratch.glwe, acc, &mut scratch.g_inverse);
+    }
+
+    /// Adds the external product of `self` and `ct` to `acc`.
+    fn add_product(
+        &self,
+        ct: &GlweCiphertext,
+        acc: &mut GlweCiphertext,
+        g_inverse_ct: &mut Vec<ResiduePoly>,
+    ) {
+        measured!(ExternalProduct, {
+            assert_eq!(
+                (ct.mask.len() + 1) * self.levels,
+                self.z_m_gt.len(),
+                "GGSW and GLWE dimensions differ"
+            );
+            apply_g_inverse(ct, self.base_log, self.levels, g_inverse_ct);
+
+            for i in 0..g_inverse_ct.len() {
+                for j in 0..acc.mask.len() {
+                    acc.mask[j].add_mul_assign(&g_inverse_ct[i], &self.z_m_gt[i].mask[j]);
+                }
+                acc.body
+                    .add_mul_assign(&g_inverse_ct[i], &self.z_m_gt[i].body);
+            }
+        })
     }
 }
 
-/// Decomposition of a GLWE ciphertext.
-fn apply_g_inverse(ct: &GlweCiphertext) -> Vec<ResiduePoly> {
-    let mut res: [ResiduePoly; (k + 1) * ELL] = Default::default();
+/// Buffers of `external_product_assign` and `cmux_assign`, reused from one call to the next.
+/// They adapt to the dimensions of the ciphertexts, and start empty.
+#[derive(Default)]
+pub struct ExternalProductScratch {
+    g_inverse: Vec<ResiduePoly>,
+    glwe: GlweCiphertext,
+}
 
-    for i in 0..N {
-        // mask decomposition
-        for j in 0..k {
-            let (nu_2, nu_1) = decomposition_8_2(ct.mask[j].coefs[i]);
-            res[j * ELL].coefs[i] = nu_1 as u64;
-            res[j * ELL + 1].coefs[i] = nu_2 as u64;
-        }
+/// Makes `ct` the zero ciphertext of dimensions `glwe_k` and `poly_size`, reusing its buffers.
+fn set_zero(ct: &mut GlweCiphertext, glwe_k: usize, poly_size: usize) {
+    ct.mask.resize_with(glwe_k, ResiduePoly::new);
+    for poly in ct.mask.iter_mut().chain([&mut ct.body]) {
+        poly.coefs.clear();
+        poly.coefs.resize(poly_size, 0);
+    }
+}
+
+/// Decomposition of a GLWE ciphertext, from the most significant level of the first mask
+/// polynomial to the least significant level of the body.
+/// The decomposition is written into `res`, whose buffers are reused.
+fn apply_g_inverse(
+    ct: &GlweCiphertext,
+    base_log: usize,
+    levels: usize,
+    res: &mut Vec<ResiduePoly>,
+) {
+    let poly_size = ct.body.coefs.len();
+    res.resize_with((ct.mask.len() + 1) * levels, ResiduePoly::new);
+    for poly in res.iter_mut() {
+        poly.coefs.resize(poly_size, 0);
+    }
 
-        // body decomposition
-        let (nu_2, nu_1) = decomposition_8_2(ct.body.coefs[i]);
-        res[(k + 1) * ELL - 2].coefs[i] = nu_1 as u64;
-        res[(k + 1) * ELL - 1].coefs[i] = nu_2 as u64;
+    for (j, poly) in ct.mask.iter().chain([&ct.body]).enumerate() {
+        for i in 0..poly_size {
+            let digits = decompose_rounded(
+                poly.coefs[i],
+                DecompositionBaseLog(base_log),
+                DecompositionLevelCount(levels),
+            );
+            for l in 0..levels {
+                res[j * levels + l].coefs[i] = digits[levels - 1 - l];
+            }
+        }
     }
-    res.to_vec()
 }
 
 /// Approximate decomposition with lg(B) = 8 and ell = 2.
@@ -90,32 +350,278 @@ pub fn decomposition_8_2(val: u64) -> (i8, i8) {
 }
 
 /// Ciphertext multiplexer. If `ctb` is an encryption of `1`, return `ct2`. Else, return `ct1`.
+/// A trivial selector picks its input directly.
 pub fn cmux(ctb: &GgswCiphertext, ct1: &GlweCiphertext, ct2: &GlweCiphertext) -> GlweCiphertext {
-    let mut res = ct2.sub(ct1);
-    res = ctb.external_product(&res);
-    res = res.add(ct1);
+    let mut res = ct1.clone();
+    cmux_assign(ctb, &mut res, ct2, &mut ExternalProductScratch::default());
     res
 }
 
-/// Encrypts the bits of `s` under `sk`
-pub fn compute_bsk(s: &LweSecretKey, sk: &SecretKey) -> BootstrappingKey {
-    let bsk: Vec<GgswCiphertext> = (0..LWE_DIM)
-        .map(|i| GgswCiphertext::encrypt(s[i].try_into().unwrap(), sk))
+/// In-place multiplexer: replaces `acc` by `other` if `ctb` is an encryption of `1`, with the
+/// buffers of `scratch`. The result is the one of `cmux(ctb, acc, other)`.
+pub fn cmux_assign(
+    ctb: &GgswCiphertext,
+    acc: &mut GlweCiphertext,
+    other: &GlweCiphertext,
+    scratch: &mut ExternalProductScratch,
+) {
+    match ctb.trivial_msg {
+        Some(0) => return,
+        Some(1) => {
+            acc.clone_from(other);
+            return;
+        }
+        _ => {}
+    }
+
+    // acc + ctb * (other - acc)
+    let diff = &mut scratch.glwe;
+    set_zero(diff, acc.mask.len(), acc.body.coefs.len());
+    for (d, (o, a)) in diff.mask.iter_mut().chain([&mut diff.body]).zip(
+        other
+            .mask
+            .iter()
+            .chain([&other.body])
+            .zip(acc.mask.iter().chain([&acc.body])),
+    ) {
+        for (d, (o, a)) in d.coefs.iter_mut().zip(o.coefs.iter().zip(&a.coefs)) {
+            *d = o.wrapping_sub(*a);
+        }
+    }
+    ctb.add_product(diff, acc, &mut scratch.g_inverse);
+}
+
+impl EvaluationKey<'_> {
+    /// Bootstraps `ct`, whose message must be below `2^(message_bits - 1)`, into an encryption of
+    /// `f` of its message under the same key, in the order of `params.pbs_order`.
+    /// Panics if `ct` is not under the key of that order.
+    pub fn bootstrap(&self, ct: &LweCiphertext, f: impl Fn(u8) -> u8) -> LweCiphertext {
+        let lut = GlweCiphertext::trivial_encrypt_lut_for(self.params, f);
+        self.bootstrap_lut_poly(ct, &lut)
+    }
+
+    /// Bootstraps `ct` by blind rotating the LUT polynomial `lut`, e.g. made by
+    /// `GlweCiphertext::trivial_encrypt_lut_for`, and extracting its constant coefficient.
+    /// Panics if `ct` is not under the key of `params.pbs_order`.
+    pub fn bootstrap_lut_poly(&self, ct: &LweCiphertext, lut: &GlweCiphertext) -> LweCiphertext {
+        let log2_modulus = (2 * self.params.poly_size.0).ilog2();
+
+        match self.params.pbs_order {
+            PbsOrder::BootstrapKeyswitch => {
+                self.check_dimension(ct, self.params.lwe_dim.0);
+                self.blind_rotate(lut, ct.modswitch_to(log2_modulus))
+                    .sample_extract()
+                    .keyswitch(self.ksk)
+            }
+            PbsOrder::KeyswitchBootstrap => {
+                self.check_dimension(ct, self.params.glwe_k.0 * self.params.poly_size.0);
+                let keyswitched = ct.keyswitch(self.ksk);
+                self.blind_rotate(lut, keyswitched.modswitch_to(log2_modulus))
+                    .sample_extract()
+            }
+        }
+    }
+
+    /// Blind rotates `lut` by `c` with the algorithm of `params`.
+    fn blind_rotate(&self, lut: &GlweCiphertext, c: LweCiphertext) -> GlweCiphertext {
+        match self.params.blind_rotation {
+            BlindRotationAlgorithm::Ginx => lut.blind_rotate(c, self.bsk),
+            BlindRotationAlgorithm::Ap { base_log } => lut.blind_rotate_ap(c, self.bsk, base_log),
+        }
+    }
+
+    fn check_dimension(&self, ct: &LweCiphertext, dim: usize) {
+        assert_eq!(
+            ct.mask.len(),
+            dim,
+            "ciphertext of dimension {} given to a {:?} bootstrap",
+            ct.mask.len(),
+            self.params.pbs_order
+        );
+    }
+
+    /// Bootstraps `ct` through `lut`.
+    pub fn bootstrap_lut(&self, ct: &LweCiphertext, lut: &Lut) -> LweCiphertext {
+        self.bootstrap(ct, |m| lut.eval(m))
+    }
+
+    /// Applies the chain `luts`, in order, to `ct` with a single bootstrap of their composition.
+    /// An empty chain refreshes `ct`. Errors if the output of a table cannot be an input of the next.
+    pub fn apply_composed(
+        &self,
+        ct: &LweCiphertext,
+        luts: &[Lut],
+    ) -> Result<LweCiphertext, String> {
+        let lut = luts
+            .iter()
+            .try_fold(Lut::identity(), |acc, lut| acc.compose(lut))?;
+        Ok(self.bootstrap_lut(ct, &lut))
+    }
+}
+
+/// Computes the bootstrapping key of `s` under `sk` for the blind rotation algorithm of their
+/// parameters.
+/// With `Ginx`, encrypts the bits of `s`: a ternary coefficient `s_i` is encrypted as the two bits
+/// `s_i = 1` and `s_i = -1`. With `Ap { base_log }`, encrypts the monomials `X^(d B^j s_i)` for
+/// every nonzero digit `d` in base `B = 2^base_log` and every digit position `j`, whatever the
+/// distribution of `s`.
+/// Errors with `TfheError::MalformedKey` if the keys are of different parameters or if a
+/// coefficient of `s` is out of its distribution.
+/// The encryptions are computed in parallel with the `parallel` feature.
+pub fn compute_bsk(s: &LweSecretKey, sk: &SecretKey) -> Result<BootstrappingKey, TfheError> {
+    let ggsws = par_map(&bsk_plaintexts(s, sk)?, |msg| {
+        GgswCiphertext::encrypt_poly(msg, sk)
+    });
+    Ok(BootstrappingKey {
+        ggsws,
+        distribution: s.distribution,
+    })
+}
+
+/// Computes the bootstrapping key of `s` under `sk`, drawing the randomness of the encryptions
+/// from `rng`.
+pub fn compute_bsk_with<R: Rng + CryptoRng>(
+    s: &LweSecretKey,
+    sk: &SecretKey,
+    rng: &mut R,
+) -> Result<BootstrappingKey, TfheError> {
+    let ggsws = bsk_plaintexts(s, sk)?
+        .iter()
+        .map(|msg| GgswCiphertext::encrypt_poly_with(msg, sk, rng))
         .collect();
+    Ok(BootstrappingKey {
+        ggsws,
+        distribution: s.distribution,
+    })
+}
+
+/// Plaintexts of the bootstrapping key of `s` under `sk`, see `compute_bsk`.
+fn bsk_plaintexts(s: &LweSecretKey, sk: &SecretKey) -> Result<Vec<ResiduePoly>, TfheError> {
+    if s.params != sk.params {
+        return Err(TfheError::MalformedKey(
+            "LWE and GLWE keys of different parameters".to_string(),
+        ));
+    }
+    let in_distribution = |c: u64| match s.distribution {
+        KeyDistribution::Binary => c <= 1,
+        KeyDistribution::Ternary => c <= 1 || c == u64::MAX,
+    };
+    if let Some(c) = s.coefs.iter().find(|&&c| !in_distribution(c)) {
+        return Err(TfheError::MalformedKey(format!(
+            "coefficient {c} of a {:?} key",
+            s.distribution
+        )));
+    }
+    let poly_size = s.params.poly_size.0;
+
+    if let BlindRotationAlgorithm::Ap { base_log } = s.params.blind_rotation {
+        let digits = s.params.blind_rotation.digits(s.params.poly_size);
+        let base_log = base_log.0;
+        let two_n = 2 * poly_size as u64;
+        return Ok(s
+            .coefs
+            .iter()
+            .flat_map(|&s_i| {
+                (0..digits).flat_map(move |j| {
+                    (1..1u64 << base_log).map(move |d| {
+                        monomial((d << (j * base_log)).wrapping_mul(s_i) % two_n, poly_size)
+                    })
+                })
+            })
+            .collect());
+    }
+
+    let bits: Vec<u64> = match s.distribution {
+        KeyDistribution::Binary => s.coefs.clone(),
+        KeyDistribution::Ternary => s
+            .coefs
+            .iter()
+            .flat_map(|&c| [(c == 1) as u64, (c == u64::MAX) as u64])
+            .collect(),
+    };
+
+    Ok(bits
+        .into_iter()
+        .map(|bit| {
+            let mut msg = ResiduePoly::zero(poly_size);
+            msg.coefs[0] = bit;
+            msg
+        })
+        .collect())
+}
+
+/// The monomial `X^exponent` of degree below `poly_size`, for `exponent` below `2 * poly_size`.
+fn monomial(exponent: u64, poly_size: usize) -> ResiduePoly {
+    let mut poly = ResiduePoly::zero(poly_size);
+    let exponent = exponent as usize;
+    if exponent < poly_size {
+        poly.coefs[exponent] = 1;
+    } else {
+        poly.coefs[exponent - poly_size] = u64::MAX;
+    }
+    poly
+}
 
-    bsk
+/// Fresh keys for the tests of the modules built on bootstraps.
+#[cfg(test)]
+pub(crate) struct TestKeys {
+    pub lwe_sk: LweSecretKey,
+    pub glwe_sk: SecretKey,
+    pub bsk: BootstrappingKey,
+    pub ksk: KeySwitchingKey,
+    pub params: Parameters,
+}
+
+#[cfg(test)]
+impl TestKeys {
+    /// Draws the LWE and GLWE keys for `params` and computes their evaluation keys.
+    pub(crate) fn new(params: &Parameters) -> Self {
+        let lwe_sk = crate::lwe::lwe_keygen_for(params, &mut thread_rng());
+        let glwe_sk = crate::glwe::keygen_for(params, &mut thread_rng());
+        let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+        let ksk = crate::lwe::compute_ksk(&glwe_sk.recode(), &lwe_sk);
+        Self {
+            lwe_sk,
+            glwe_sk,
+            bsk,
+            ksk,
+            params: *params,
+        }
+    }
+
+    pub(crate) fn evaluation_key(&self) -> EvaluationKey<'_> {
+        EvaluationKey {
+            bsk: &self.bsk,
+            ksk: &self.ksk,
+            params: &self.params,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ggsw::{cmux, GgswCiphertext};
-    use crate::glwe::{keygen, GlweCiphertext};
+    use crate::error::TfheError;
+    use crate::ggsw::{
+        apply_g_inverse, cmux, cmux_assign, compute_bsk, compute_bsk_with, BootstrappingKey,
+        EvaluationKey, ExternalProductScratch, GgswCiphertext, TestKeys,
+    };
+    use crate::glwe::{keygen_for, GlweCiphertext};
+    use crate::lwe::{
+        lwe_keygen_for, lwe_keygen_ternary_for, KeyDistribution, KeySwitchingKey, LweCiphertext,
+    };
+    use crate::params::presets::{TEST, TOY};
+    use crate::params::{
+        DecompositionBaseLog, DecompositionLevelCount, GlweDimension, Parameters, PbsOrder,
+        PolynomialSize,
+    };
+    use crate::poly::ResiduePoly;
     use crate::utils::{decode, encode};
-    use rand::{thread_rng, Rng};
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, Rng, SeedableRng};
 
     #[test]
     fn test_keygen_enc_dec() {
-        let sk = keygen();
+        let sk = keygen_for(&TEST, &mut thread_rng());
         for _ in 0..100 {
             let msg = thread_rng().gen_range(0..16);
             let ct = GgswCiphertext::encrypt(msg, &sk);
@@ -124,9 +630,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encrypt_poly() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let poly_size = TEST.poly_size.0;
+        let msgs: Vec<u8> = (0..poly_size)
+            .map(|_| thread_rng().gen_range(0..4))
+            .collect();
+        let ct = GlweCiphertext::encrypt_poly(
+            &ResiduePoly {
+                coefs: msgs.iter().map(|&m| encode(m)).collect(),
+            },
+            &sk,
+        );
+
+        // X^3 rotates the slots, the last three wrapping around negated.
+        let mut monomial = ResiduePoly::zero(poly_size);
+        monomial.coefs[3] = 1;
+        let rotated = GgswCiphertext::encrypt_poly(&monomial, &sk).external_product(&ct);
+        let decrypted: Vec<u8> = rotated.decrypt_poly(&sk).into_iter().map(decode).collect();
+        for i in 0..poly_size {
+            let expected = if i < 3 {
+                (16 - msgs[poly_size + i - 3]) % 16
+            } else {
+                msgs[i - 3]
+            };
+            assert_eq!(decrypted[i], expected, "slot {i}");
+        }
+
+        // 1 + X adds to every slot the previous one.
+        let mut one_plus_x = ResiduePoly::zero(poly_size);
+        one_plus_x.coefs[0] = 1;
+        one_plus_x.coefs[1] = 1;
+        let product = GgswCiphertext::encrypt_poly(&one_plus_x, &sk).external_product(&ct);
+        let decrypted: Vec<u8> = product.decrypt_poly(&sk).into_iter().map(decode).collect();
+        assert_eq!(decrypted[0], (16 + msgs[0] - msgs[poly_size - 1]) % 16);
+        for i in 1..poly_size {
+            assert_eq!(decrypted[i], msgs[i] + msgs[i - 1], "slot {i}");
+        }
+
+        // Constant polynomials are the scalar messages.
+        let mut constant = ResiduePoly::zero(poly_size);
+        constant.coefs[0] = 5;
+        assert_eq!(GgswCiphertext::encrypt_poly(&constant, &sk).decrypt(&sk), 5);
+    }
+
     #[test]
     fn test_external_product() {
-        let sk = keygen();
+        let sk = keygen_for(&TEST, &mut thread_rng());
         for _ in 0..100 {
             let msg1 = thread_rng().gen_range(0..16);
             let msg2 = thread_rng().gen_range(0..16);
@@ -139,10 +690,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_external_product_bits() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        for b in 0..2 {
+            let ggsw = GgswCiphertext::encrypt(b, &sk);
+            for _ in 0..100 {
+                let m = thread_rng().gen_range(0..16);
+                let res = ggsw.external_product(&GlweCiphertext::encrypt(encode(m), &sk));
+                assert_eq!(decode(res.decrypt(&sk)), b * m);
+            }
+        }
+    }
+
     #[test]
     fn test_cmux() {
         for _ in 0..100 {
-            let sk = keygen();
+            let sk = keygen_for(&TEST, &mut thread_rng());
             let msg1 = thread_rng().gen_range(0..16);
             let msg2 = thread_rng().gen_range(0..16);
             let b = thread_rng().gen_range(0..2);
@@ -158,16 +722,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cmux_tree() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let leaves: Vec<GlweCiphertext> = (0..16)
+            .map(|m| GlweCiphertext::encrypt(encode(15 - m), &sk))
+            .collect();
+
+        for index in [0, 6, 9, 15] {
+            let bits: Vec<GgswCiphertext> = (0..4)
+                .map(|i| GgswCiphertext::encrypt((index >> i) & 1, &sk))
+                .collect();
+
+            // 15 CMuxes, the least significant bit selecting among the leaves.
+            let mut level = leaves.clone();
+            for bit in &bits {
+                level = level
+                    .chunks(2)
+                    .map(|pair| bit.cmux(&pair[0], &pair[1]))
+                    .collect();
+            }
+            assert_eq!(decode(level[0].decrypt(&sk)), 15 - index, "leaf {index}");
+        }
+    }
+
     #[test]
     fn test_cmux_trivial() {
         for _ in 0..100 {
-            let sk = keygen();
+            let sk = keygen_for(&TEST, &mut thread_rng());
             let msg1 = thread_rng().gen_range(0..16);
             let msg2 = thread_rng().gen_range(0..16);
             let b = thread_rng().gen_range(0..2);
 
-            let ct1 = GlweCiphertext::trivial_encrypt(encode(msg1));
-            let ct2 = GlweCiphertext::trivial_encrypt(encode(msg2));
+            let ct1 = GlweCiphertext::trivial_encrypt_for(encode(msg1), &TEST);
+            let ct2 = GlweCiphertext::trivial_encrypt_for(encode(msg2), &TEST);
             let ctb = GgswCiphertext::encrypt(b, &sk);
 
             let res = cmux(&ctb, &ct1, &ct2);
@@ -177,4 +765,279 @@ mod tests {
             assert_eq!(pt, (1 - b) * msg1 + b * msg2);
         }
     }
+
+    #[test]
+    fn test_mul() {
+        // Products of GGSW ciphertexts need a finer decomposition than a blind rotation.
+        let params = Parameters {
+            pbs_levels: DecompositionLevelCount(4),
+            ..TEST
+        };
+        let sk = keygen_for(&params, &mut thread_rng());
+        let ct1 = GlweCiphertext::encrypt(encode(3), &sk);
+        let ct2 = GlweCiphertext::encrypt(encode(12), &sk);
+        let select = |ctb: &GgswCiphertext| decode(cmux(ctb, &ct1, &ct2).decrypt(&sk));
+
+        for (b1, b2) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let product = GgswCiphertext::encrypt(b1, &sk).mul(&GgswCiphertext::encrypt(b2, &sk));
+            assert_eq!(select(&product), if b1 & b2 == 1 { 12 } else { 3 });
+        }
+
+        for _ in 0..4 {
+            let bits: Vec<u8> = (0..4).map(|_| thread_rng().gen_range(0..2)).collect();
+            let product = bits[1..]
+                .iter()
+                .fold(GgswCiphertext::encrypt(bits[0], &sk), |acc, &b| {
+                    GgswCiphertext::encrypt(b, &sk).mul(&acc)
+                });
+            let expected = if bits.iter().all(|&b| b == 1) { 12 } else { 3 };
+            assert_eq!(select(&product), expected, "{bits:?}");
+        }
+
+        let trivial = GgswCiphertext::trivial_encrypt(1, &params);
+        assert!(trivial.mul(&trivial).is_trivial());
+        assert_eq!(select(&trivial.mul(&GgswCiphertext::encrypt(1, &sk))), 12);
+    }
+
+    /// External product allocating every intermediate polynomial.
+    fn reference_external_product(ggsw: &GgswCiphertext, ct: &GlweCiphertext) -> GlweCiphertext {
+        let mut g_inverse_ct = vec![];
+        apply_g_inverse(ct, ggsw.base_log, ggsw.levels, &mut g_inverse_ct);
+        let mut res = GlweCiphertext::zero(
+            GlweDimension(ct.mask.len()),
+            PolynomialSize(ct.body.coefs.len()),
+        );
+        for (digits, row) in g_inverse_ct.iter().zip(&ggsw.z_m_gt) {
+            for j in 0..res.mask.len() {
+                res.mask[j] = res.mask[j].add(&digits.mul(&row.mask[j]));
+            }
+            res.body = res.body.add(&digits.mul(&row.body));
+        }
+        res
+    }
+
+    fn assert_same(ct1: &GlweCiphertext, ct2: &GlweCiphertext) {
+        assert_eq!(ct1.mask.len(), ct2.mask.len());
+        for (p1, p2) in ct1
+            .mask
+            .iter()
+            .chain([&ct1.body])
+            .zip(ct2.mask.iter().chain([&ct2.body]))
+        {
+            assert_eq!(p1.coefs, p2.coefs);
+        }
+    }
+
+    #[test]
+    fn test_assign_matches_pure() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        // One scratch for every call, whose buffers hold the previous results.
+        let mut scratch = ExternalProductScratch::default();
+        for _ in 0..10 {
+            let b = thread_rng().gen_range(0..2);
+            let ctb = GgswCiphertext::encrypt(b, &sk);
+            let ct1 = GlweCiphertext::encrypt(encode(thread_rng().gen_range(0..16)), &sk);
+            let ct2 = GlweCiphertext::encrypt(encode(thread_rng().gen_range(0..16)), &sk);
+
+            let expected = reference_external_product(&ctb, &ct1);
+            let mut acc = ct1.clone();
+            ctb.external_product_assign(&mut acc, &mut scratch);
+            assert_same(&acc, &expected);
+            assert_same(&ctb.external_product(&ct1), &expected);
+
+            let expected = reference_external_product(&ctb, &ct2.sub(&ct1)).add(&ct1);
+            let mut acc = ct1.clone();
+            cmux_assign(&ctb, &mut acc, &ct2, &mut scratch);
+            assert_same(&acc, &expected);
+            assert_same(&cmux(&ctb, &ct1, &ct2), &expected);
+
+            let trivial = GgswCiphertext::trivial_encrypt(b, &TEST);
+            let mut acc = ct1.clone();
+            cmux_assign(&trivial, &mut acc, &ct2, &mut scratch);
+            assert_same(&acc, if b == 0 { &ct1 } else { &ct2 });
+        }
+    }
+
+    #[test]
+    fn test_trivial_selector() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        for _ in 0..20 {
+            let msg1 = thread_rng().gen_range(0..16);
+            let msg2 = thread_rng().gen_range(0..16);
+            let b = thread_rng().gen_range(0..2);
+
+            let ct1 = GlweCiphertext::encrypt(encode(msg1), &sk);
+            let ct2 = GlweCiphertext::encrypt(encode(msg2), &sk);
+            let ctb = GgswCiphertext::trivial_encrypt(b, &TEST);
+            assert!(ctb.is_trivial());
+            assert!(!GgswCiphertext::encrypt(b, &sk).is_trivial());
+            assert_eq!(ctb.clone().decrypt(&sk), b);
+
+            let selected = cmux(&ctb, &ct1, &ct2);
+            let expected = if b == 0 { &ct1 } else { &ct2 };
+            assert_eq!(selected.body.coefs, expected.body.coefs);
+            assert_eq!(selected.mask[0].coefs, expected.mask[0].coefs);
+
+            // The scaling agrees with the decomposition it skips.
+            let m = thread_rng().gen_range(0..16);
+            let ggsw = GgswCiphertext::trivial_encrypt(m, &TEST);
+            let fast = ggsw.external_product(&ct2);
+            let slow = GgswCiphertext {
+                trivial_msg: None,
+                ..ggsw
+            }
+            .external_product(&ct2);
+            assert_eq!(decode(fast.decrypt(&sk)), m * msg2 % 16);
+            assert_eq!(decode(slow.decrypt(&sk)), m * msg2 % 16);
+        }
+    }
+
+    #[test]
+    fn test_debug() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let ggsw = GgswCiphertext::encrypt(1, &sk);
+        let glwe = GlweCiphertext::encrypt(encode(3), &sk);
+
+        let debug = format!("{ggsw:?}");
+        assert!(
+            debug.contains(&format!("levels: {}", TEST.pbs_levels.0)),
+            "{debug}"
+        );
+        assert!(
+            debug.contains(&format!("poly_size: {}", TEST.poly_size.0)),
+            "{debug}"
+        );
+        assert!(debug.len() < 128, "{debug}");
+
+        let debug = format!("{glwe:?}");
+        assert!(debug.contains(&format!(
+            "k: {}, poly_size: {}",
+            TEST.glwe_k.0, TEST.poly_size.0
+        )));
+        assert!(!debug.contains(&glwe.body.coefs[0].to_string()), "{debug}");
+
+        // Lists print one summary per element.
+        let bsk = vec![ggsw.clone(), ggsw];
+        assert_eq!(format!("{bsk:?}").matches("GgswCiphertext").count(), 2);
+    }
+
+    #[test]
+    fn test_bsk_rejects_malformed_keys() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let mut lwe_sk = lwe_keygen_ternary_for(&TEST, &mut thread_rng());
+        lwe_sk.distribution = KeyDistribution::Binary;
+        lwe_sk.coefs[0] = u64::MAX;
+        assert_eq!(
+            compute_bsk(&lwe_sk, &glwe_sk).err(),
+            Some(TfheError::MalformedKey(format!(
+                "coefficient {} of a Binary key",
+                u64::MAX
+            )))
+        );
+
+        let params = Parameters {
+            pbs_order: PbsOrder::KeyswitchBootstrap,
+            ..TEST
+        };
+        let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+        assert!(matches!(
+            compute_bsk(&lwe_sk, &glwe_sk),
+            Err(TfheError::MalformedKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_bootstrap_orders() {
+        let mut results = vec![];
+        for pbs_order in [PbsOrder::BootstrapKeyswitch, PbsOrder::KeyswitchBootstrap] {
+            let params = Parameters { pbs_order, ..TEST };
+            let keys = TestKeys::new(&params);
+            let key = keys.evaluation_key();
+            // Ciphertexts stay under the key of the order through bootstraps.
+            let sk = match pbs_order {
+                PbsOrder::BootstrapKeyswitch => keys.lwe_sk.clone(),
+                PbsOrder::KeyswitchBootstrap => keys.glwe_sk.recode(),
+            };
+
+            let outputs: Vec<u8> = (0..8)
+                .map(|m| {
+                    let ct = LweCiphertext::encrypt(encode(m), &sk);
+                    let doubled = key.bootstrap(&ct, |m| 2 * m % 8);
+                    decode(key.bootstrap(&doubled, |m| m + 8).decrypt(&sk))
+                })
+                .collect();
+            results.push(outputs);
+        }
+
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], [8, 10, 12, 14, 8, 10, 12, 14]);
+    }
+
+    #[test]
+    fn test_bootstrap_refreshes_noise() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+        // Three quarters of the half width of a decoding box: decoding survives one such error,
+        // not two.
+        let error = 3u64 << 57;
+
+        for msg in 0..8 {
+            let mut noisy = LweCiphertext::encrypt(encode(msg), &keys.lwe_sk);
+            noisy.body = noisy.body.wrapping_add(error);
+            assert_eq!(decode(noisy.clone().decrypt(&keys.lwe_sk)), msg);
+
+            let mut refreshed = key.bootstrap(&noisy, |m| m);
+            assert_eq!(decode(refreshed.clone().decrypt(&keys.lwe_sk)), msg);
+
+            noisy.body = noisy.body.wrapping_add(error);
+            refreshed.body = refreshed.body.wrapping_add(error);
+            assert_ne!(decode(noisy.decrypt(&keys.lwe_sk)), msg);
+            assert_eq!(decode(refreshed.decrypt(&keys.lwe_sk)), msg);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "dimension 128 given to a KeyswitchBootstrap bootstrap")]
+    fn test_bootstrap_wrong_key() {
+        let params = Parameters {
+            pbs_order: PbsOrder::KeyswitchBootstrap,
+            ..TOY
+        };
+        let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+        let key = EvaluationKey {
+            bsk: &BootstrappingKey {
+                ggsws: vec![],
+                distribution: KeyDistribution::Binary,
+            },
+            ksk: &KeySwitchingKey {
+                base_log: DecompositionBaseLog(4),
+                levels: DecompositionLevelCount(4),
+                keys: vec![],
+            },
+            params: &params,
+        };
+        key.bootstrap(&LweCiphertext::encrypt(0, &lwe_sk), |m| m);
+    }
+
+    #[test]
+    fn test_seeded_bsk() {
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let bsk = |seed: u64| {
+            compute_bsk_with(&lwe_sk, &glwe_sk, &mut StdRng::seed_from_u64(seed)).unwrap()
+        };
+
+        let first = bsk(7);
+        assert_eq!(
+            bincode::serialize(&first).unwrap(),
+            bincode::serialize(&bsk(7)).unwrap()
+        );
+        assert_ne!(
+            first[0].z_m_gt[0].mask[0].coefs,
+            bsk(8)[0].z_m_gt[0].mask[0].coefs
+        );
+
+        let ggsw = GgswCiphertext::encrypt_with(1, &glwe_sk, &mut StdRng::seed_from_u64(7));
+        assert_eq!(ggsw.decrypt(&glwe_sk), 1);
+    }
 }
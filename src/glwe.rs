@@ -1,107 +1,439 @@
-use crate::ggsw::{cmux, BootstrappingKey};
-use crate::lwe::{LweCiphertext, LweSecretKey};
-use crate::utils::encode;
-use crate::P;
-use crate::{k, poly::ResiduePoly, LWE_DIM, N};
-use rand_distr::{Distribution, Normal};
+use crate::error::TfheError;
+use crate::ggsw::{cmux_assign, BootstrappingKey, ExternalProductScratch};
+use crate::lwe::{KeyDistribution, LweCiphertext, LweSecretKey, NoiseLevel};
+use crate::params::{
+    BlindRotationAlgorithm, DecompositionBaseLog, GlweDimension, Parameters, PolynomialSize,
+};
+use crate::torus::Torus;
+use crate::utils::{
+    encode_bits, fingerprint, mask_rng, nonce_rng, sample_gaussian, short_digest, wipe, NONCE_LEN,
+    SEED_LEN,
+};
+use crate::{k, poly::ResiduePoly, N};
+use rand::{thread_rng, CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Clone, Serialize, Deserialize)]
-pub struct GlweCiphertext {
-    pub mask: Vec<ResiduePoly>,
+#[serde(bound = "")]
+pub struct GlweCiphertext<T: Torus = u64> {
+    pub mask: Vec<ResiduePoly<T>>,
+    pub body: ResiduePoly<T>,
+}
+
+/// GLWE ciphertext whose mask is replaced by the seed of the stream it is drawn from, see
+/// `utils::mask_rng`. Only the seed and the body are stored, instead of `k + 1` polynomials.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeededGlweCiphertext {
+    pub seed: [u8; SEED_LEN],
+    pub glwe_k: GlweDimension,
     pub body: ResiduePoly,
 }
 
 /// Set of `k` polynomials in {0, 1}\[X\]/(X^N + 1).
-#[derive(Clone)]
-pub struct SecretKey {
-    pub polys: Vec<ResiduePoly>,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SecretKey<T: Torus = u64> {
+    pub polys: Vec<ResiduePoly<T>>,
+    /// Parameters the key was generated for.
+    pub params: Parameters,
 }
 
-impl GlweCiphertext {
-    pub fn encrypt(mu: u64, sk: &SecretKey) -> GlweCiphertext {
-        let sigma = f64::powf(2.0, 39.0);
-        let normal = Normal::new(0.0, sigma).unwrap();
+impl<T: Torus> GlweCiphertext<T> {
+    pub fn encrypt(mu: T, sk: &SecretKey<T>) -> Self {
+        Self::encrypt_with(mu, sk, &mut thread_rng())
+    }
 
-        let e = normal.sample(&mut rand::thread_rng()).round() as i64;
-        let mu_star = mu.wrapping_add_signed(e);
+    /// Encrypts `mu` under `sk`, drawing the mask and the noise from `rng`.
+    /// The dimensions and the noise level are the ones of the parameters of `sk`.
+    pub fn encrypt_with<R: Rng + CryptoRng>(mu: T, sk: &SecretKey<T>, rng: &mut R) -> Self {
+        let e = sample_gaussian(T::scale_std_dev(sk.params.glwe_sigma), rng);
+        let mu_star = mu.wrapping_add(T::from_i64(e));
 
-        let mask: Vec<ResiduePoly> = (0..k).map(|_| ResiduePoly::get_random()).collect();
+        let poly_size = sk.params.poly_size.0;
+        let mask: Vec<ResiduePoly<T>> = (0..sk.polys.len())
+            .map(|_| ResiduePoly::random_with(poly_size, rng))
+            .collect();
 
-        let mut body = ResiduePoly::default();
-        for i in 0..k {
+        let mut body = ResiduePoly::zero(poly_size);
+        for i in 0..sk.polys.len() {
             body.add_assign(&mask[i].mul(&sk.polys[i]));
         }
 
-        body.add_constant_assign(mu_star as u64);
+        body.add_constant_assign(mu_star);
+
+        Self { mask, body }
+    }
+
+    pub fn encrypt_poly(mu: &ResiduePoly<T>, sk: &SecretKey<T>) -> Self {
+        Self::encrypt_poly_with(mu, sk, &mut thread_rng())
+    }
+
+    /// Encrypts the polynomial `mu` under `sk`, drawing the mask and the noise of every
+    /// coefficient from `rng`.
+    pub fn encrypt_poly_with<R: Rng + CryptoRng>(
+        mu: &ResiduePoly<T>,
+        sk: &SecretKey<T>,
+        rng: &mut R,
+    ) -> Self {
+        let poly_size = sk.params.poly_size.0;
+        assert_eq!(
+            mu.coefs.len(),
+            poly_size,
+            "message and key dimensions differ"
+        );
+
+        let mask: Vec<ResiduePoly<T>> = (0..sk.polys.len())
+            .map(|_| ResiduePoly::random_with(poly_size, rng))
+            .collect();
+
+        let mut body = ResiduePoly::zero(poly_size);
+        for i in 0..sk.polys.len() {
+            body.add_assign(&mask[i].mul(&sk.polys[i]));
+        }
+
+        for (coef, m) in body.coefs.iter_mut().zip(&mu.coefs) {
+            let e = sample_gaussian(T::scale_std_dev(sk.params.glwe_sigma), rng);
+            *coef = coef.wrapping_add(m.wrapping_add(T::from_i64(e)));
+        }
+
+        Self { mask, body }
+    }
+
+    /// Like `decrypt`, but returns an error instead of panicking if `self` and `sk` have different
+    /// dimensions or polynomial sizes, as when they come from different parameter sets.
+    pub fn try_decrypt(&self, sk: &SecretKey<T>) -> Result<T, TfheError> {
+        if self.mask.len() != sk.polys.len() {
+            return Err(TfheError::KeyMismatchDimension {
+                expected: sk.polys.len(),
+                found: self.mask.len(),
+            });
+        }
+        if self.body.coefs.len() != sk.params.poly_size.0 {
+            return Err(TfheError::KeyMismatchPolynomialSize {
+                expected: sk.params.poly_size.0,
+                found: self.body.coefs.len(),
+            });
+        }
+        Ok(self.decrypt(sk))
+    }
 
-        GlweCiphertext { mask, body }
+    pub fn decrypt(&self, sk: &SecretKey<T>) -> T {
+        self.decrypt_poly(sk)[0]
     }
 
-    pub fn decrypt(&self, sk: &SecretKey) -> u64 {
-        let mut body = ResiduePoly::default();
-        for i in 0..k {
+    /// Decrypts every coefficient of the plaintext polynomial.
+    pub fn decrypt_poly(&self, sk: &SecretKey<T>) -> Vec<T> {
+        assert!(
+            self.mask.len() == sk.polys.len() && self.body.coefs.len() == sk.params.poly_size.0,
+            "ciphertext and key dimensions differ"
+        );
+
+        let mut body = ResiduePoly::zero(sk.params.poly_size.0);
+        for i in 0..sk.polys.len() {
             body.add_assign(&self.mask[i].mul(&sk.polys[i]));
         }
 
         let mu_star = self.body.sub(&body);
-        mu_star.coefs[0]
+        mu_star.coefs
+    }
+
+    /// Whether `self` is a trivial encryption, with a zero mask.
+    pub fn is_trivial(&self) -> bool {
+        self.mask
+            .iter()
+            .all(|poly| poly.coefs.iter().all(|&c| c == T::default()))
     }
 
+    /// Adding a trivial ciphertext only touches the body.
     pub fn add(&self, rhs: &Self) -> Self {
-        let mut res = GlweCiphertext::default();
-        for i in 0..k {
-            res.mask[i] = self.mask[i].add(&rhs.mask[i]);
+        if rhs.is_trivial() && rhs.mask.len() == self.mask.len() {
+            return Self {
+                mask: self.mask.clone(),
+                body: self.body.add(&rhs.body),
+            };
+        }
+
+        Self {
+            mask: self
+                .mask
+                .iter()
+                .zip(&rhs.mask)
+                .map(|(a, b)| a.add(b))
+                .collect(),
+            body: self.body.add(&rhs.body),
         }
-        res.body = self.body.add(&rhs.body);
-        res
     }
 
     pub fn sub(&self, rhs: &Self) -> Self {
-        let mut res = GlweCiphertext::default();
-        for i in 0..k {
-            res.mask[i] = self.mask[i].sub(&rhs.mask[i]);
+        Self {
+            mask: self
+                .mask
+                .iter()
+                .zip(&rhs.mask)
+                .map(|(a, b)| a.sub(b))
+                .collect(),
+            body: self.body.sub(&rhs.body),
         }
-        res.body = self.body.sub(&rhs.body);
-        res
     }
 
-    /// Converts a GLWE ciphertext into a LWE ciphertext of dimension `N`.
-    // TODO: generalize for k > 1
-    pub fn sample_extract(&self) -> LweCiphertext {
-        let mut mask = [0u64; N];
-        mask[0] = self.mask[0].coefs[0];
-        for i in 1..N {
-            mask[i] = self.mask[0].coefs[N - i].wrapping_neg();
+    fn map_coefs(&mut self, f: impl Fn(T) -> T) {
+        for c in self.mask.iter_mut().chain([&mut self.body]) {
+            for coef in c.coefs.iter_mut() {
+                *coef = f(*coef);
+            }
         }
+    }
 
-        let body = self.body.coefs[0];
+    fn zip_coefs(&mut self, rhs: &Self, f: impl Fn(T, T) -> T) {
+        assert!(
+            self.mask.len() == rhs.mask.len() && self.body.coefs.len() == rhs.body.coefs.len(),
+            "ciphertext dimensions differ"
+        );
+        for (c, d) in self
+            .mask
+            .iter_mut()
+            .chain([&mut self.body])
+            .zip(rhs.mask.iter().chain([&rhs.body]))
+        {
+            for (coef, other) in c.coefs.iter_mut().zip(&d.coefs) {
+                *coef = f(*coef, *other);
+            }
+        }
+    }
+
+    /// Converts a GLWE ciphertext into a LWE ciphertext of dimension `kN`.
+    pub fn sample_extract(&self) -> LweCiphertext<T> {
+        self.sample_extract_at(0)
+    }
 
-        LweCiphertext {
-            mask: mask.to_vec(),
-            body,
+    /// Converts a GLWE ciphertext into a LWE ciphertext of dimension `kN` encrypting the
+    /// `index`-th coefficient of its plaintext polynomial.
+    pub fn sample_extract_at(&self, index: usize) -> LweCiphertext<T> {
+        let poly_size = self.body.coefs.len();
+        assert!(
+            index < poly_size,
+            "no coefficient {index} in degree {poly_size}"
+        );
+
+        let mut mask = Vec::<T>::with_capacity(self.mask.len() * poly_size);
+        for poly in &self.mask {
+            for i in 0..poly_size {
+                mask.push(if i <= index {
+                    poly.coefs[index - i]
+                } else {
+                    poly.coefs[poly_size + index - i].wrapping_neg()
+                });
+            }
         }
+
+        let body = self.body.coefs[index];
+
+        LweCiphertext { mask, body }
     }
 
     /// Trivially encrypts `mu`.
-    pub fn trivial_encrypt(mu: u64) -> Self {
-        let mut res = Self::default();
+    pub fn trivial_encrypt(mu: T) -> Self {
+        Self::trivial_encrypt_for(mu, &Parameters::DEFAULT)
+    }
+
+    /// Trivially encrypts `mu` with the dimensions of `params`.
+    pub fn trivial_encrypt_for(mu: T, params: &Parameters) -> Self {
+        let mut res = Self::zero(params.glwe_k, params.poly_size);
         res.body.coefs[0] = mu;
         res
     }
 
+    /// Trivially encrypts the polynomial `mu` with `glwe_k` mask polynomials.
+    pub fn trivial_encrypt_poly(mu: ResiduePoly<T>, glwe_k: GlweDimension) -> Self {
+        Self {
+            mask: vec![ResiduePoly::zero(mu.coefs.len()); glwe_k.0],
+            body: mu,
+        }
+    }
+
+    /// Trivial encryption of `0` with `glwe_k` mask polynomials of size `poly_size`.
+    pub fn zero(glwe_k: GlweDimension, poly_size: PolynomialSize) -> Self {
+        Self {
+            mask: vec![ResiduePoly::zero(poly_size.0); glwe_k.0],
+            body: ResiduePoly::zero(poly_size.0),
+        }
+    }
+
+    /// Writes `self * X^exponent` into `out`, reusing its buffers.
+    pub fn rotate_into(&self, exponent: u64, out: &mut Self) {
+        out.mask.resize_with(self.mask.len(), ResiduePoly::new);
+        for (poly, rotated) in self.mask.iter().zip(&mut out.mask) {
+            poly.multiply_by_monomial_into(exponent as usize, rotated);
+        }
+        self.body
+            .multiply_by_monomial_into(exponent as usize, &mut out.body);
+    }
+
+    /// Multiplies by the monomial `X^exponent` every component of `self`.
+    pub fn rotate(&self, exponent: u64) -> Self {
+        Self {
+            mask: self
+                .mask
+                .iter()
+                .map(|poly| poly.multiply_by_monomial(exponent as usize))
+                .collect(),
+            body: self.body.multiply_by_monomial(exponent as usize),
+        }
+    }
+}
+
+impl GlweCiphertext {
+    /// Encrypts `mu` under `sk` with a mask and a noise derived from `nonce` and the coefficients
+    /// of `sk`, see `LweCiphertext::encrypt_deterministic`.
+    ///
+    /// **A nonce must never be reused for different messages.**
+    pub fn encrypt_deterministic(mu: u64, sk: &SecretKey, nonce: [u8; NONCE_LEN]) -> Self {
+        let mut rng = nonce_rng(
+            b"ttfhe/glwe-encrypt",
+            sk.polys.iter().flat_map(|p| &p.coefs),
+            &nonce,
+        );
+        Self::encrypt_with(mu, sk, &mut rng)
+    }
+
+    /// Encrypts `mu` under `sk` with a mask drawn from the stream of `seed`, see
+    /// `SeededGlweCiphertext`. The seed must never be reused under the same key.
+    pub fn encrypt_seeded(mu: u64, sk: &SecretKey, seed: [u8; SEED_LEN]) -> SeededGlweCiphertext {
+        Self::encrypt_seeded_with(mu, sk, seed, &mut thread_rng())
+    }
+
+    /// Encrypts `mu` under `sk` with a mask drawn from the stream of `seed` and a noise drawn from
+    /// `rng`.
+    pub fn encrypt_seeded_with<R: Rng + CryptoRng>(
+        mu: u64,
+        sk: &SecretKey,
+        seed: [u8; SEED_LEN],
+        rng: &mut R,
+    ) -> SeededGlweCiphertext {
+        let e = sample_gaussian(sk.params.glwe_sigma, rng);
+
+        let glwe_k = GlweDimension(sk.polys.len());
+        let mask = draw_mask(glwe_k, sk.params.poly_size, &seed);
+        let mut body = ResiduePoly::zero(sk.params.poly_size.0);
+        for (a, s) in mask.iter().zip(&sk.polys) {
+            body.add_assign(&a.mul(s));
+        }
+        body.add_constant_assign(mu.wrapping_add_signed(e));
+
+        SeededGlweCiphertext { seed, glwe_k, body }
+    }
+
     /// Performs the blind rotation of `self`.
+    /// `bsk` holds one GGSW ciphertext per key coefficient for binary keys, and two for ternary keys.
+    /// Panics if `bsk` is not a key of the dimension of `c`, see `try_blind_rotate`.
     // `self` is assumed to be a trivial encryption
     // `c` is a modswitched LWE ciphertext (modulus = 2N)
     pub fn blind_rotate(&self, c: LweCiphertext, bsk: &BootstrappingKey) -> Self {
-        let mut c_prime = self.clone();
+        self.try_blind_rotate(c, bsk)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        c_prime.rotate_trivial((2 * N as u64) - c.body);
-        for i in 0..LWE_DIM {
-            c_prime = cmux(&bsk[i], &c_prime, &c_prime.rotate(c.mask[i]));
-        }
+    /// Like `blind_rotate`, but returns an error instead of panicking if `bsk` does not hold the
+    /// number of GGSW ciphertexts of its key distribution for the dimension of `c`.
+    pub fn try_blind_rotate(
+        &self,
+        c: LweCiphertext,
+        bsk: &BootstrappingKey,
+    ) -> Result<Self, TfheError> {
+        check_bsk(&c, bsk)?;
+        Ok(self.blind_rotate_with(c, bsk, |_, _| {}))
+    }
+
+    /// Performs the blind rotation of `self`, calling `observer` with the index of every key
+    /// coefficient and the accumulator after its CMUX, or its two CMUXes for ternary keys.
+    #[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+    pub fn blind_rotate_observed(
+        &self,
+        c: LweCiphertext,
+        bsk: &BootstrappingKey,
+        observer: impl FnMut(usize, &GlweCiphertext),
+    ) -> Self {
+        check_bsk(&c, bsk).unwrap_or_else(|err| panic!("{err}"));
+        self.blind_rotate_with(c, bsk, observer)
+    }
+
+    // The no-op observer of `blind_rotate` is inlined away. `bsk` is checked by the callers.
+    fn blind_rotate_with(
+        &self,
+        c: LweCiphertext,
+        bsk: &BootstrappingKey,
+        mut observer: impl FnMut(usize, &GlweCiphertext),
+    ) -> Self {
+        measured!(BlindRotation, {
+            let mut c_prime = self.clone();
+            // The accumulator is updated in place: no ciphertext is allocated per CMUX.
+            let mut rotated = self.clone();
+            let mut scratch = ExternalProductScratch::default();
+
+            let two_n = 2 * self.body.coefs.len() as u64;
+            c_prime.rotate_trivial((two_n - c.body) % two_n);
+            match bsk.distribution {
+                KeyDistribution::Binary => {
+                    for i in 0..c.mask.len() {
+                        c_prime.rotate_into(c.mask[i], &mut rotated);
+                        cmux_assign(&bsk[i], &mut c_prime, &rotated, &mut scratch);
+                        observer(i, &c_prime);
+                    }
+                }
+                KeyDistribution::Ternary => {
+                    // Rotate by `a_i` if `s_i = 1`, and by `-a_i` if `s_i = -1`.
+                    for i in 0..c.mask.len() {
+                        c_prime.rotate_into(c.mask[i], &mut rotated);
+                        cmux_assign(&bsk[2 * i], &mut c_prime, &rotated, &mut scratch);
+                        let inverse = (two_n - c.mask[i]) % two_n;
+                        c_prime.rotate_into(inverse, &mut rotated);
+                        cmux_assign(&bsk[2 * i + 1], &mut c_prime, &rotated, &mut scratch);
+                        observer(i, &c_prime);
+                    }
+                }
+            }
+
+            c_prime
+        })
+    }
 
-        c_prime
+    /// Blind rotation with the AP algorithm: multiplies `self` by `X^(a_i s_i)` with an external
+    /// product for every nonzero digit of `a_i` in base `2^base_log`.
+    /// `bsk` is computed by `ggsw::compute_bsk` with `BlindRotationAlgorithm::Ap { base_log }`.
+    pub fn blind_rotate_ap(
+        &self,
+        c: LweCiphertext,
+        bsk: &BootstrappingKey,
+        base_log: DecompositionBaseLog,
+    ) -> Self {
+        measured!(BlindRotation, {
+            let poly_size = self.body.coefs.len();
+            let digits = BlindRotationAlgorithm::Ap { base_log }.digits(PolynomialSize(poly_size));
+            let base_log = base_log.0;
+            let keys_per_digit = (1 << base_log) - 1;
+            assert_eq!(
+                bsk.len(),
+                c.mask.len() * digits * keys_per_digit,
+                "ciphertext and bootstrapping key dimensions differ"
+            );
+            let mut c_prime = self.clone();
+            let mut scratch = ExternalProductScratch::default();
+
+            let two_n = 2 * poly_size as u64;
+            c_prime.rotate_trivial((two_n - c.body) % two_n);
+            for (i, &a_i) in c.mask.iter().enumerate() {
+                for j in 0..digits {
+                    let d = ((a_i >> (j * base_log)) & keys_per_digit as u64) as usize;
+                    if d != 0 {
+                        bsk[(i * digits + j) * keys_per_digit + d - 1]
+                            .external_product_assign(&mut c_prime, &mut scratch);
+                    }
+                }
+            }
+
+            c_prime
+        })
     }
 
     /// Multiplies by the monomial `X^exponent` the body of `self`.
@@ -110,92 +442,219 @@ impl GlweCiphertext {
         self.body = self.body.multiply_by_monomial(exponent as usize);
     }
 
-    /// Multiplies by the monomial `X^exponent` every component of `self`.
-    pub fn rotate(&self, exponent: u64) -> Self {
-        let mut res = Self::default();
-        for i in 0..k {
-            res.mask[i] = self.mask[i].multiply_by_monomial(exponent as usize);
-        }
-
-        res.body = self.body.multiply_by_monomial(exponent as usize);
+    /// Trivially encrypts the LUT polynomial.
+    pub fn trivial_encrypt_lut_poly() -> Self {
+        Self::trivial_encrypt_lut_poly_for(&Parameters::DEFAULT)
+    }
 
-        res
+    /// Trivially encrypts the LUT polynomial with the dimensions of `params`.
+    pub fn trivial_encrypt_lut_poly_for(params: &Parameters) -> Self {
+        Self::trivial_encrypt_lut_for(params, |m| m)
     }
 
-    /// Trivially encrypts the LUT polynomial.
-    pub fn trivial_encrypt_lut_poly() -> Self {
+    /// Trivially encrypts the LUT polynomial of `f` with the dimensions of `params`: blind
+    /// rotating it by an encryption of `m` in `[0, 2^(message_bits - 1))` yields an encryption of
+    /// `f(m)`, both encoded on `params.message_bits` bits.
+    pub fn trivial_encrypt_lut_for(params: &Parameters, f: impl Fn(u8) -> u8) -> Self {
         // TODO: use iterator
-        let mut lut_coefs = [0u64; N];
+        let poly_size = params.poly_size.0;
+        let plaintext_modulus = 1 << params.message_bits;
+        let mut lut_coefs = vec![0u64; poly_size];
 
-        for i in 0..N {
-            lut_coefs[(i.wrapping_sub(64)) % N] = encode(((P * i) / (2 * N)).try_into().unwrap());
+        for i in 0..poly_size {
+            let value = encode_bits(
+                f(((plaintext_modulus * i) / (2 * poly_size))
+                    .try_into()
+                    .unwrap()),
+                params.message_bits as u32,
+            );
+            // The first half box of `f(0)` wraps around to the end of the polynomial, where phases
+            // slightly below `0` read it negated.
+            lut_coefs[(i.wrapping_sub(poly_size / plaintext_modulus)) % poly_size] =
+                if i < poly_size / plaintext_modulus {
+                    value.wrapping_neg()
+                } else {
+                    value
+                };
         }
 
         Self {
-            body: ResiduePoly {
-                coefs: lut_coefs.to_vec(),
-            },
-            ..Default::default()
+            body: ResiduePoly { coefs: lut_coefs },
+            ..Self::zero(params.glwe_k, params.poly_size)
         }
     }
 }
 
-impl SecretKey {
+impl SeededGlweCiphertext {
+    /// Regenerates the mask from the seed.
+    pub fn expand(&self) -> GlweCiphertext {
+        GlweCiphertext {
+            mask: draw_mask(
+                self.glwe_k,
+                PolynomialSize(self.body.coefs.len()),
+                &self.seed,
+            ),
+            body: self.body.clone(),
+        }
+    }
+}
+
+/// Draws the `glwe_k` uniform polynomials of a mask from the stream of `seed`.
+fn draw_mask(
+    glwe_k: GlweDimension,
+    poly_size: PolynomialSize,
+    seed: &[u8; SEED_LEN],
+) -> Vec<ResiduePoly> {
+    let mut rng = mask_rng(seed);
+    (0..glwe_k.0)
+        .map(|_| ResiduePoly::random_with(poly_size.0, &mut rng))
+        .collect()
+}
+
+/// Wipes the coefficients, so that they do not linger in freed memory.
+impl<T: Torus> Drop for SecretKey<T> {
+    fn drop(&mut self) {
+        for poly in &mut self.polys {
+            wipe(&mut poly.coefs);
+        }
+    }
+}
+
+impl<T: Torus> SecretKey<T> {
     /// Converts a GLWE secret key into a LWE secret key.
-    // TODO: generalize for k > 1
-    pub fn recode(&self) -> LweSecretKey {
-        self.polys[0]
-            .coefs
-            .iter()
-            .copied()
-            .map(|coef| coef)
-            .collect()
+    pub fn recode(&self) -> LweSecretKey<T> {
+        LweSecretKey {
+            coefs: self.polys.iter().flat_map(|p| p.coefs.clone()).collect(),
+            params: self.params,
+            distribution: KeyDistribution::Binary,
+            noise: NoiseLevel::Glwe,
+        }
     }
 }
 
-impl Default for GlweCiphertext {
-    fn default() -> Self {
-        GlweCiphertext {
-            mask: vec![ResiduePoly::default(); k],
-            body: ResiduePoly::default(),
+impl SecretKey {
+    /// Copy of `self` on the torus of scalars `T`, for the same parameters, see
+    /// `ResiduePoly::to_torus`.
+    pub fn to_torus<T: Torus>(&self) -> SecretKey<T> {
+        SecretKey {
+            polys: self.polys.iter().map(ResiduePoly::to_torus).collect(),
+            params: self.params,
         }
     }
+
+    /// Digest identifying `self` without revealing it, e.g. in known-answer tests.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let coefs: Vec<u64> = self.polys.iter().flat_map(|p| p.coefs.clone()).collect();
+        fingerprint(b"ttfhe/glwe-key", &coefs)
+    }
+}
+
+impl_ciphertext_ops!(GlweCiphertext);
+
+impl<T: Torus> Default for GlweCiphertext<T> {
+    fn default() -> Self {
+        GlweCiphertext::zero(GlweDimension(k), PolynomialSize(N))
+    }
+}
+
+/// Prints the dimensions and a digest of the coefficients instead of the coefficients.
+impl<T: Torus> fmt::Debug for GlweCiphertext<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefs: Vec<u64> = self
+            .mask
+            .iter()
+            .chain([&self.body])
+            .flat_map(|poly| poly.coefs.iter().map(|c| c.to_u64()))
+            .collect();
+        f.debug_struct("GlweCiphertext")
+            .field("k", &self.mask.len())
+            .field("poly_size", &self.body.coefs.len())
+            .field("digest", &format_args!("{}", short_digest(&coefs)))
+            .finish()
+    }
 }
 
 pub fn keygen() -> SecretKey {
-    let polys: Vec<ResiduePoly> = (0..k).map(|_| ResiduePoly::get_random_bin()).collect();
+    keygen_with(&mut thread_rng())
+}
 
-    SecretKey { polys }
+/// Generates a GLWE secret key, drawing its coefficients from `rng`.
+pub fn keygen_with<R: Rng + CryptoRng>(rng: &mut R) -> SecretKey {
+    keygen_for(&Parameters::DEFAULT, rng)
+}
+
+/// Generates a GLWE secret key for `params`, drawing its coefficients from `rng`.
+/// Panics if `params` are invalid.
+pub fn keygen_for<R: Rng + CryptoRng>(params: &Parameters, rng: &mut R) -> SecretKey {
+    params.validate().expect("invalid parameters");
+
+    let polys: Vec<ResiduePoly> = (0..params.glwe_k.0)
+        .map(|_| ResiduePoly::random_bin_with(params.poly_size.0, rng))
+        .collect();
+
+    SecretKey {
+        polys,
+        params: *params,
+    }
+}
+
+/// Checks that `bsk` holds the GGSW ciphertexts of a `Ginx` key of the dimension of `c`: one per
+/// coefficient for binary keys and two for ternary keys.
+fn check_bsk(c: &LweCiphertext, bsk: &BootstrappingKey) -> Result<(), TfheError> {
+    let per_coef = match bsk.distribution {
+        KeyDistribution::Binary => 1,
+        KeyDistribution::Ternary => 2,
+    };
+    if !bsk.len().is_multiple_of(per_coef) {
+        return Err(TfheError::MalformedKey(format!(
+            "{} GGSW ciphertexts for a {:?} key",
+            bsk.len(),
+            bsk.distribution
+        )));
+    }
+    if bsk.len() / per_coef != c.mask.len() {
+        return Err(TfheError::KeyMismatchDimension {
+            expected: bsk.len() / per_coef,
+            found: c.mask.len(),
+        });
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ggsw::compute_bsk;
-    use crate::glwe::{keygen, GlweCiphertext};
-    use crate::lwe::{compute_ksk, lwe_keygen, LweCiphertext, LweSecretKey};
-    use crate::utils::{decode, decode_bootstrapped, encode};
+    use crate::error::TfheError;
+    use crate::ggsw::{compute_bsk, EvaluationKey};
+    use crate::glwe::{keygen_for, GlweCiphertext};
+    use crate::lwe::{compute_ksk, lwe_keygen_for, lwe_keygen_ternary_for, LweCiphertext};
+    use crate::params::presets::TEST;
+    use crate::params::{
+        BlindRotationAlgorithm, DecompositionBaseLog, GlweDimension, Parameters, PolynomialSize,
+    };
+    use crate::utils::{decode, decode_bootstrapped, encode, NONCE_LEN, SEED_LEN};
     use rand::{thread_rng, Rng};
 
     #[test]
-  //  #[ignore]
+    //  #[ignore]
     fn test_bootstrapping() {
-        let sk1 = lwe_keygen();
-        let sk2 = keygen();
-        let bsk = compute_bsk(&sk1, &sk2); // list of encryptions under `sk2` of the bits of `sk1`.
+        let sk1 = lwe_keygen_for(&TEST, &mut thread_rng());
+        let sk2 = keygen_for(&TEST, &mut thread_rng());
+        let bsk = compute_bsk(&sk1, &sk2).unwrap(); // list of encryptions under `sk2` of the bits of `sk1`.
         let ksk = compute_ksk(&sk2.recode(), &sk1); // list of encryptions under `sk1` of the bits of `sk2`.
 
-        let lut = GlweCiphertext::trivial_encrypt_lut_poly();
+        let lut = GlweCiphertext::trivial_encrypt_lut_poly_for(&TEST);
 
         for _ in 0..1 {
             let msg = thread_rng().gen_range(0..8);
 
-            let c = LweCiphertext::encrypt(encode(msg), &sk1).modswitch(); // "noisy" ciphertext that will be bootstrapped
+            let c = LweCiphertext::encrypt(encode(msg), &sk1)
+                .modswitch_to((2 * TEST.poly_size.0).ilog2()); // "noisy" ciphertext that will be bootstrapped
 
             let blind_rotated_lut = lut.blind_rotate(c, &bsk); // should return a GLWE encryption of X^{- \tilde{\mu}^*} * v(X) which should be equal to a polynomial with constant term \mu.
 
             let res = blind_rotated_lut
                 .sample_extract()
-                .keyswitch(&mut ksk.clone())
+                .keyswitch(&ksk)
                 .decrypt(&sk1);
 
             let pt = decode_bootstrapped(res);
@@ -203,18 +662,103 @@ mod tests {
         }
     }
 
-    /// This test fails from time to time.
-    /// It might be the case that B = 16, ell = 4 isn't suitable for keyswitching.
+    #[test]
+    fn test_bootstrapping_ternary() {
+        let sk1 = lwe_keygen_ternary_for(&TEST, &mut thread_rng());
+        let sk2 = keygen_for(&TEST, &mut thread_rng());
+        let bsk = compute_bsk(&sk1, &sk2).unwrap();
+        let ksk = compute_ksk(&sk2.recode(), &sk1);
+        assert_eq!(bsk.len(), 2 * TEST.lwe_dim.0);
+
+        // Both LUTs together output the 16 messages.
+        let identity = GlweCiphertext::trivial_encrypt_lut_for(&TEST, |m| m);
+        let mirror = GlweCiphertext::trivial_encrypt_lut_for(&TEST, |m| 15 - m);
+        for msg in 0..8 {
+            let c = LweCiphertext::encrypt(encode(msg), &sk1)
+                .modswitch_to((2 * TEST.poly_size.0).ilog2());
+            for (lut, expected) in [(&identity, msg), (&mirror, 15 - msg)] {
+                let res = lut
+                    .blind_rotate(c.clone(), &bsk)
+                    .sample_extract()
+                    .keyswitch(&ksk)
+                    .decrypt(&sk1);
+                assert_eq!(decode(res), expected);
+            }
+        }
+
+        // The distribution is read from the key, not inferred from its length.
+        let c = LweCiphertext::zero(2 * TEST.lwe_dim.0);
+        assert_eq!(
+            identity.try_blind_rotate(c, &bsk).err(),
+            Some(TfheError::KeyMismatchDimension {
+                expected: TEST.lwe_dim.0,
+                found: 2 * TEST.lwe_dim.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_bootstrapping_ap() {
+        let params = Parameters {
+            blind_rotation: BlindRotationAlgorithm::Ap {
+                base_log: DecompositionBaseLog(2),
+            },
+            ..TEST
+        };
+        let sk2 = keygen_for(&params, &mut thread_rng());
+        for sk1 in [
+            lwe_keygen_for(&params, &mut thread_rng()),
+            lwe_keygen_ternary_for(&params, &mut thread_rng()),
+        ] {
+            let bsk = compute_bsk(&sk1, &sk2).unwrap();
+            let ksk = compute_ksk(&sk2.recode(), &sk1);
+            let digits = (2 * params.poly_size.0).ilog2().div_ceil(2) as usize;
+            assert_eq!(bsk.len(), 3 * digits * params.lwe_dim.0);
+            let key = EvaluationKey {
+                bsk: &bsk,
+                ksk: &ksk,
+                params: &params,
+            };
+
+            for msg in 0..8 {
+                let ct = LweCiphertext::encrypt(encode(msg), &sk1);
+                let res = key.bootstrap(&ct, |m| 15 - m).decrypt(&sk1);
+                assert_eq!(decode(res), 15 - msg);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blind_rotate_zero_body() {
+        let sk1 = lwe_keygen_for(&TEST, &mut thread_rng());
+        let sk2 = keygen_for(&TEST, &mut thread_rng());
+        let bsk = compute_bsk(&sk1, &sk2).unwrap();
+
+        // A modswitched body of 0 rotates the LUT by X^{2N} = 1, which must not negate it.
+        let lut = GlweCiphertext::trivial_encrypt_lut_for(&TEST, |m| m + 1);
+        let c = LweCiphertext::zero(TEST.lwe_dim.0);
+        let res = lut.blind_rotate(c, &bsk).decrypt(&sk2);
+        assert_eq!(decode(res), 1);
+
+        // Phases slightly below 0, from negative noise, are in the box of 0 as well.
+        let mut c = LweCiphertext::zero(TEST.lwe_dim.0);
+        c.body = 2 * TEST.poly_size.0 as u64 - 1;
+        let res = lut.blind_rotate(c, &bsk).decrypt(&sk2);
+        assert_eq!(decode(res), 1);
+    }
+
+    /// This test fails from time to time under `Parameters::DEFAULT`, whose keyswitching noise is
+    /// close to the decoding bound (see `noise`).
     #[test]
     fn test_keyswitching() {
-        let sk1 = lwe_keygen();
-        let sk2 = keygen();
+        let sk1 = lwe_keygen_for(&TEST, &mut thread_rng());
+        let sk2 = keygen_for(&TEST, &mut thread_rng());
         let ksk = compute_ksk(&sk2.recode(), &sk1); // list of encryptions under `sk1` of the bits of `sk2`.
 
         for _ in 0..100 {
             let msg = thread_rng().gen_range(0..8);
             let ct = GlweCiphertext::encrypt(encode(msg), &sk2).sample_extract();
-            let ks = ct.keyswitch(&mut ksk.clone());
+            let ks = ct.keyswitch(&ksk);
             let res = ks.decrypt(&sk1);
             let pt = decode(res);
 
@@ -222,55 +766,263 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_keygen_enc_dec() {
-        let sk = keygen();
-        for _ in 0..100 {
-            let msg = thread_rng().gen_range(0..16);
-            let ct = GlweCiphertext::encrypt(encode(msg), &sk);
-            let pt = decode(ct.decrypt(&sk));
-            assert_eq!(pt, msg);
+    /// `TEST` with `k` mask polynomials, of a size shrinking as `k` grows.
+    fn with_glwe_k(k: usize) -> Parameters {
+        Parameters {
+            glwe_k: GlweDimension(k),
+            poly_size: PolynomialSize(TEST.poly_size.0 / k.next_power_of_two()),
+            ..TEST
         }
     }
 
-    #[test]
-    fn test_add() {
-        let sk = keygen();
-        for _ in 0..100 {
-            let msg1 = thread_rng().gen_range(0..16);
-            let msg2 = thread_rng().gen_range(0..16);
-            let ct1 = GlweCiphertext::encrypt(encode(msg1), &sk);
-            let ct2 = GlweCiphertext::encrypt(encode(msg2), &sk);
-            let res = ct1.add(&ct2);
-            let pt = decode(res.decrypt(&sk));
-            assert_eq!(pt, (msg1 + msg2) % 16);
-        }
+    /// Tests of the operations generic over the scalar, instantiated for every backend.
+    macro_rules! torus_tests {
+        ($name:ident, $t:ty) => {
+            mod $name {
+                use super::with_glwe_k;
+                use crate::glwe::{keygen_for, GlweCiphertext, SecretKey};
+                use crate::lwe::{compute_ksk, lwe_keygen_for, LweCiphertext, LweSecretKey};
+                use crate::params::{presets::TEST, Parameters};
+                use crate::poly::ResiduePoly;
+                use crate::torus::Torus;
+                use rand::{thread_rng, Rng};
+
+                fn keygen(params: &Parameters) -> SecretKey<$t> {
+                    keygen_for(params, &mut thread_rng()).to_torus()
+                }
+
+                fn encode(msg: u8) -> $t {
+                    <$t as Torus>::encode(msg)
+                }
+
+                fn encode_poly(msgs: &[u8], poly_size: usize) -> ResiduePoly<$t> {
+                    let mut coefs: Vec<$t> = msgs.iter().map(|&m| encode(m)).collect();
+                    coefs.resize(poly_size, <$t>::default());
+                    ResiduePoly { coefs }
+                }
+
+                fn decode_poly(coefs: Vec<$t>) -> Vec<u8> {
+                    coefs.into_iter().map(Torus::decode).collect()
+                }
+
+                #[test]
+                fn test_keygen_enc_dec() {
+                    for k in 1..=3 {
+                        let sk = keygen(&with_glwe_k(k));
+                        for _ in 0..100 {
+                            let msg = thread_rng().gen_range(0..16);
+                            let ct = GlweCiphertext::encrypt(encode(msg), &sk);
+                            assert_eq!(ct.mask.len(), k);
+                            let pt = ct.decrypt(&sk).decode();
+                            assert_eq!(pt, msg, "k = {k}");
+                        }
+                    }
+                }
+
+                #[test]
+                fn test_add() {
+                    for k in 1..=3 {
+                        let sk = keygen(&with_glwe_k(k));
+                        for _ in 0..100 {
+                            let msg1 = thread_rng().gen_range(0..16);
+                            let msg2 = thread_rng().gen_range(0..16);
+                            let ct1 = GlweCiphertext::encrypt(encode(msg1), &sk);
+                            let ct2 = GlweCiphertext::encrypt(encode(msg2), &sk);
+                            let res = ct1.add(&ct2);
+                            let pt = res.decrypt(&sk).decode();
+                            assert_eq!(pt, (msg1 + msg2) % 16, "k = {k}");
+                        }
+                    }
+                }
+
+                #[test]
+                fn test_operators() {
+                    let sk = keygen(&TEST);
+                    let ct1 = GlweCiphertext::encrypt(encode(3), &sk);
+                    let ct2 = GlweCiphertext::encrypt(encode(7), &sk);
+
+                    assert_eq!((-ct1.clone()).decrypt(&sk).decode(), 13);
+                    let affine = ct1.clone() * 3 - ct2.clone() * -2 + &ct1;
+                    assert_eq!(affine.decrypt(&sk).decode(), (4 * 3 + 2 * 7) % 16);
+
+                    let mut acc = ct2.clone();
+                    acc -= &ct1;
+                    acc += ct2;
+                    assert_eq!(acc.decrypt(&sk).decode(), 11);
+                }
+
+                #[test]
+                fn test_add_packed() {
+                    let sk = keygen(&TEST);
+                    let poly_size = TEST.poly_size.0;
+                    let random_msgs = || -> Vec<u8> {
+                        (0..poly_size)
+                            .map(|_| thread_rng().gen_range(0..16))
+                            .collect()
+                    };
+                    let (msgs1, msgs2) = (random_msgs(), random_msgs());
+
+                    let ct1 = GlweCiphertext::encrypt_poly(&encode_poly(&msgs1, poly_size), &sk);
+                    let ct2 = GlweCiphertext::encrypt_poly(&encode_poly(&msgs2, poly_size), &sk);
+                    let sums: Vec<u8> = msgs1
+                        .iter()
+                        .zip(&msgs2)
+                        .map(|(a, b)| (a + b) % 16)
+                        .collect();
+                    assert_eq!(decode_poly(ct1.add(&ct2).decrypt_poly(&sk)), sums);
+
+                    // Fewer messages than coefficients are padded with zeros.
+                    let ct = GlweCiphertext::encrypt_poly(&encode_poly(&[3, 1, 4], poly_size), &sk);
+                    let decrypted = decode_poly(ct.decrypt_poly(&sk));
+                    assert_eq!(decrypted[..3], [3, 1, 4]);
+                    assert!(decrypted[3..].iter().all(|&m| m == 0));
+                }
+
+                #[test]
+                fn test_add_trivial() {
+                    let sk = keygen(&TEST);
+                    let ct = GlweCiphertext::encrypt(encode(3), &sk);
+                    let trivial = GlweCiphertext::trivial_encrypt_for(encode(9), &TEST);
+                    assert!(trivial.is_trivial());
+                    assert!(!ct.is_trivial());
+
+                    let res = ct.add(&trivial);
+                    assert_eq!(res.mask[0].coefs, ct.mask[0].coefs);
+                    assert_eq!(res.decrypt(&sk).decode(), 12);
+                    assert!(trivial.add(&trivial).is_trivial());
+                    assert_eq!(trivial.add(&ct).decrypt(&sk).decode(), 12);
+                }
+
+                #[test]
+                fn test_trivial_encrypt_poly() {
+                    let sk = keygen(&TEST);
+                    let lwe_sk: LweSecretKey<$t> =
+                        lwe_keygen_for(&TEST, &mut thread_rng()).to_torus();
+                    let ksk = compute_ksk(&sk.recode(), &lwe_sk);
+                    let poly = ResiduePoly {
+                        coefs: (0..TEST.poly_size.0)
+                            .map(|i| encode(i as u8 % 16))
+                            .collect(),
+                    };
+                    let trivial = GlweCiphertext::trivial_encrypt_poly(poly.clone(), TEST.glwe_k);
+                    assert!(trivial.is_trivial());
+                    assert_eq!(trivial.decrypt_poly(&sk), poly.coefs);
+
+                    let ct = GlweCiphertext::encrypt_poly(&poly, &sk);
+                    let sum = ct.add(&trivial);
+                    let decrypted = decode_poly(sum.decrypt_poly(&sk));
+                    assert!(decrypted
+                        .iter()
+                        .enumerate()
+                        .all(|(i, &m)| m == ((2 * i) % 16) as u8));
+                    assert!(decode_poly(ct.sub(&trivial).decrypt_poly(&sk))
+                        .into_iter()
+                        .all(|m| m == 0));
+
+                    // Through a sample extraction and a keyswitch.
+                    let extracted = sum.sample_extract_at(3).keyswitch(&ksk);
+                    assert_eq!(extracted.decrypt(&lwe_sk).decode(), 6);
+                }
+
+                #[test]
+                fn test_sub() {
+                    let sk = keygen(&TEST);
+                    for _ in 0..100 {
+                        let msg1 = thread_rng().gen_range(0..16);
+                        let msg2 = thread_rng().gen_range(0..16);
+                        let ct1 = GlweCiphertext::encrypt(encode(msg1), &sk);
+                        let ct2 = GlweCiphertext::encrypt(encode(msg2), &sk);
+                        let res = ct1.sub(&ct2);
+                        let pt = res.decrypt(&sk).decode();
+                        assert_eq!(pt, (msg1.wrapping_sub(msg2)) % 16);
+                    }
+                }
+
+                #[test]
+                fn test_sample_extract() {
+                    let sk = keygen(&TEST);
+                    let msg = thread_rng().gen_range(0..16);
+                    let ct = GlweCiphertext::encrypt(encode(msg), &sk);
+
+                    let sample_extracted: LweCiphertext<$t> = ct.sample_extract();
+                    let recoded_sk: LweSecretKey<$t> = sk.recode();
+
+                    let pt = sample_extracted.decrypt(&recoded_sk).decode();
+                    assert_eq!(pt, msg)
+                }
+
+                #[test]
+                fn test_sample_extract_at() {
+                    for k in 1..=2 {
+                        let params = with_glwe_k(k);
+                        let sk = keygen(&params);
+                        let poly_size = params.poly_size.0;
+
+                        // Distinct messages at the extracted positions, random ones elsewhere.
+                        let indices = [0, 1, poly_size / 2, poly_size - 1];
+                        let mut msgs: Vec<u8> = (0..poly_size)
+                            .map(|_| thread_rng().gen_range(0..16))
+                            .collect();
+                        for (msg, &index) in (5..).zip(&indices) {
+                            msgs[index] = msg;
+                        }
+                        let mu = encode_poly(&msgs, poly_size);
+                        let ct = GlweCiphertext::encrypt_poly(&mu, &sk);
+
+                        for index in indices {
+                            let extracted = ct.sample_extract_at(index);
+                            assert_eq!(extracted.mask.len(), k * poly_size);
+                            assert_eq!(
+                                extracted.decrypt(&sk.recode()).decode(),
+                                msgs[index],
+                                "k = {k}, index {index}"
+                            );
+                        }
+                    }
+                }
+            }
+        };
     }
 
+    torus_tests!(u32_backend, u32);
+    torus_tests!(u64_backend, u64);
+
     #[test]
-    fn test_sub() {
-        let sk = keygen();
-        for _ in 0..100 {
-            let msg1 = thread_rng().gen_range(0..16);
-            let msg2 = thread_rng().gen_range(0..16);
-            let ct1 = GlweCiphertext::encrypt(encode(msg1), &sk);
-            let ct2 = GlweCiphertext::encrypt(encode(msg2), &sk);
-            let res = ct1.sub(&ct2);
-            let pt = decode(res.decrypt(&sk));
-            assert_eq!(pt, (msg1.wrapping_sub(msg2)) % 16);
-        }
+    fn test_encrypt_deterministic() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let nonce = [42u8; NONCE_LEN];
+
+        let ct = GlweCiphertext::encrypt_deterministic(encode(9), &sk, nonce);
+        let again = GlweCiphertext::encrypt_deterministic(encode(9), &sk, nonce);
+        assert_eq!(
+            bincode::serialize(&ct).unwrap(),
+            bincode::serialize(&again).unwrap()
+        );
+        assert_eq!(decode(ct.decrypt(&sk)), 9);
+
+        let other = GlweCiphertext::encrypt_deterministic(encode(9), &sk, [43u8; NONCE_LEN]);
+        assert_ne!(ct.mask[0].coefs, other.mask[0].coefs);
     }
 
     #[test]
-    fn test_sample_extract() {
-        let sk = keygen();
-        let msg = thread_rng().gen_range(0..16);
-        let ct = GlweCiphertext::encrypt(encode(msg), &sk);
+    fn test_encrypt_seeded() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let seed: [u8; SEED_LEN] = thread_rng().gen();
 
-        let sample_extracted: LweCiphertext = ct.sample_extract();
-        let recoded_sk: LweSecretKey = sk.recode();
+        let seeded = GlweCiphertext::encrypt_seeded(encode(9), &sk, seed);
+        let ct = seeded.expand();
+        assert_eq!(
+            bincode::serialize(&ct).unwrap(),
+            bincode::serialize(&seeded.expand()).unwrap()
+        );
+        assert_eq!(decode(ct.decrypt(&sk)), 9);
 
-        let pt = decode(sample_extracted.decrypt(&recoded_sk));
-        assert_eq!(pt, msg)
+        // Of the k + 1 polynomials, only the body is left, next to the seed and k.
+        let body_len = bincode::serialize(&ct.body).unwrap().len();
+        assert_eq!(
+            bincode::serialize(&seeded).unwrap().len(),
+            SEED_LEN + 8 + body_len
+        );
+        assert!((TEST.glwe_k.0 + 1) * body_len <= bincode::serialize(&ct).unwrap().len());
     }
 }
@@ -0,0 +1,113 @@
+//! Hamming distance between encrypted bit vectors, e.g. for private biometric matching.
+//!
+//! The bits of the XOR of the vectors are bootstrapped from the sums of their bits, then counted
+//! with a `tally::Counter`, whose base-4 digits count past the plaintext modulus.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::LweCiphertext;
+use crate::tally::{num_digits_for, Counter};
+
+/// Encrypted Hamming distance between the encrypted bit vectors `a` and `b`, with one bootstrap
+/// per bit for the XOR, and the bootstraps of the carries of the count.
+/// Errors if the vectors are empty or of different lengths.
+pub fn hamming_distance(
+    a: &[LweCiphertext],
+    b: &[LweCiphertext],
+    key: &EvaluationKey,
+) -> Result<Counter, String> {
+    if a.len() != b.len() {
+        return Err(format!(
+            "bit vectors of different lengths {} and {}",
+            a.len(),
+            b.len()
+        ));
+    }
+    if a.is_empty() {
+        return Err("empty bit vectors".to_string());
+    }
+
+    let dim = a[0].mask.len();
+    let mut distance = Counter::zero(dim, num_digits_for(a.len() as u64));
+    for (x, y) in a.iter().zip(b) {
+        let xor = key.bootstrap(&x.clone().add(y.clone()), |m| m % 2);
+        distance.add_at(0, &xor, 1, key);
+    }
+    distance.normalize(key);
+    Ok(distance)
+}
+
+/// Returns an encryption of `1` if the Hamming distance between `a` and `b` is at most `t`, and of
+/// `0` otherwise. Errors if the vectors are empty or of different lengths.
+pub fn matches_within(
+    a: &[LweCiphertext],
+    b: &[LweCiphertext],
+    t: u8,
+    key: &EvaluationKey,
+) -> Result<LweCiphertext, String> {
+    Ok(hamming_distance(a, b, key)?.at_most(t as u64, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::hamming::{hamming_distance, matches_within};
+    use crate::lwe::{LweCiphertext, LweSecretKey};
+    use crate::params::presets::TEST;
+    use crate::utils::{decode, encode};
+    use rand::{thread_rng, Rng};
+
+    fn encrypt_bits(bits: &[u8], sk: &LweSecretKey) -> Vec<LweCiphertext> {
+        bits.iter()
+            .map(|&bit| LweCiphertext::encrypt(encode(bit), sk))
+            .collect()
+    }
+
+    #[test]
+    fn test_hamming_distance() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let bits: Vec<u8> = (0..64).map(|_| thread_rng().gen_range(0..2)).collect();
+        let a = encrypt_bits(&bits, &keys.lwe_sk);
+        for flipped in [0, 1, 32, 64] {
+            let other: Vec<u8> = bits
+                .iter()
+                .enumerate()
+                .map(|(i, &bit)| bit ^ (i < flipped) as u8)
+                .collect();
+            let b = encrypt_bits(&other, &keys.lwe_sk);
+
+            let distance = hamming_distance(&a, &b, &key).unwrap();
+            assert_eq!(distance.decrypt(&keys.lwe_sk), flipped as u64);
+            for (t, expected) in [(flipped.saturating_sub(1), flipped == 0), (flipped, true)] {
+                let within = distance.at_most(t as u64, &key);
+                assert_eq!(
+                    decode(within.decrypt(&keys.lwe_sk)),
+                    expected as u8,
+                    "distance {flipped}, threshold {t}"
+                );
+            }
+        }
+
+        let b = encrypt_bits(&bits[..10], &keys.lwe_sk);
+        let err = hamming_distance(&a, &b, &key).err().unwrap();
+        assert!(err.contains("different lengths"), "{err}");
+    }
+
+    #[test]
+    fn test_matches_within() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let a = encrypt_bits(&[1, 0, 1, 1, 0, 0], &keys.lwe_sk);
+        let b = encrypt_bits(&[0, 0, 1, 0, 0, 1], &keys.lwe_sk);
+        for (t, expected) in [(2, 0), (3, 1), (200, 1)] {
+            let within = matches_within(&a, &b, t, &key).unwrap();
+            assert_eq!(
+                decode(within.decrypt(&keys.lwe_sk)),
+                expected,
+                "threshold {t}"
+            );
+        }
+    }
+}
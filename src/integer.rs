@@ -0,0 +1,143 @@
+//! Encrypted unsigned integers of any width, as lists of encrypted digits (radix decomposition).
+//!
+//! A block holds a base-4 digit, like the digits of `uint4::FheUint4`: the sum of two digits and
+//! of an incoming carry is at most `3 + 3 + 1 = 7`, below `P/2`, so that a bootstrap can split it
+//! into a digit and the carry of the next block. A base-16 block would leave no room for the carry
+//! in a 4-bit plaintext space, so an integer of `b` bits takes `b / 2` blocks.
+//!
+//! Additions propagate the carries from the least significant block, with 2 bootstraps per block
+//! but the last one, whose carry is dropped: sums wrap around modulo `4^num_blocks`.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::utils::{decode, encode};
+use serde::{Deserialize, Serialize};
+
+/// Base of the digits of the blocks.
+pub const BLOCK_BASE: u64 = 4;
+
+/// Encrypted integer in `[0, 4^num_blocks)`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RadixCiphertext {
+    /// Base-4 digits, the least significant first.
+    pub blocks: Vec<LweCiphertext>,
+}
+
+impl RadixCiphertext {
+    /// Encrypts `msg` as `num_blocks` base-4 digits under `sk`.
+    /// Panics if `num_blocks` is `0` or if `msg` does not fit in `num_blocks` digits.
+    pub fn encrypt(msg: u64, num_blocks: usize, sk: &LweSecretKey) -> Self {
+        assert!(num_blocks > 0, "an integer has at least one block");
+        assert!(
+            num_blocks >= 32 || msg < BLOCK_BASE.pow(num_blocks as u32),
+            "{msg} does not fit in {num_blocks} base-{BLOCK_BASE} digits"
+        );
+
+        let blocks = (0..num_blocks)
+            .map(|i| {
+                let digit = msg.checked_shr(2 * i as u32).unwrap_or(0) % BLOCK_BASE;
+                LweCiphertext::encrypt(encode(digit as u8), sk)
+            })
+            .collect();
+        RadixCiphertext { blocks }
+    }
+
+    /// Decrypts the integer, modulo `2^64` if it has more than 32 blocks.
+    pub fn decrypt(&self, sk: &LweSecretKey) -> u64 {
+        self.blocks.iter().rev().fold(0u64, |acc, block| {
+            acc.wrapping_mul(BLOCK_BASE)
+                .wrapping_add(decode(block.clone().decrypt(sk)) as u64)
+        })
+    }
+
+    /// `self + rhs` modulo `4^num_blocks`, with `2 * num_blocks - 1` bootstraps.
+    /// The blocks of the result are digits again, so that results can be added further.
+    /// Panics if `self` and `rhs` do not have the same number of blocks.
+    pub fn add(&self, rhs: &Self, key: &EvaluationKey) -> Self {
+        assert_eq!(
+            self.blocks.len(),
+            rhs.blocks.len(),
+            "integers of different numbers of blocks"
+        );
+
+        let base = BLOCK_BASE as u8;
+        // Integers without blocks, e.g. deserialized, add up to an integer without blocks.
+        let last = self.blocks.len().saturating_sub(1);
+        let mut carry: Option<LweCiphertext> = None;
+        let mut blocks = Vec::with_capacity(self.blocks.len());
+        for (i, (a, b)) in self.blocks.iter().zip(&rhs.blocks).enumerate() {
+            let mut sum = a.clone().add(b.clone());
+            if let Some(carry) = carry.take() {
+                sum = sum.add(carry);
+            }
+            if i < last {
+                carry = Some(key.bootstrap(&sum, |m| m / base));
+            }
+            blocks.push(key.bootstrap(&sum, |m| m % base));
+        }
+        RadixCiphertext { blocks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::integer::RadixCiphertext;
+    use crate::lwe::lwe_keygen_for;
+    use crate::params::presets::TEST;
+    use rand::{thread_rng, Rng};
+
+    /// Number of base-4 blocks of a 32-bit integer.
+    const BLOCKS_32: usize = 16;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        for msg in [0, 1, 0xdead_beef, u32::MAX as u64] {
+            let ct = RadixCiphertext::encrypt(msg, BLOCKS_32, &sk);
+            assert_eq!(ct.decrypt(&sk), msg);
+        }
+        let ct = RadixCiphertext::encrypt(u64::MAX, 32, &sk);
+        assert_eq!(ct.decrypt(&sk), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit")]
+    fn test_encrypt_too_large() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        RadixCiphertext::encrypt(1 << 32, BLOCKS_32, &sk);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one block")]
+    fn test_encrypt_no_blocks() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        RadixCiphertext::encrypt(0, 0, &sk);
+    }
+
+    #[test]
+    fn test_add() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        // The carry of the second pair crosses every block.
+        let pairs = [
+            (thread_rng().gen::<u32>(), thread_rng().gen::<u32>()),
+            (u32::MAX, 1),
+            (thread_rng().gen::<u32>(), thread_rng().gen::<u32>()),
+        ];
+        for (a, b) in pairs {
+            let ct_a = RadixCiphertext::encrypt(a as u64, BLOCKS_32, &keys.lwe_sk);
+            let ct_b = RadixCiphertext::encrypt(b as u64, BLOCKS_32, &keys.lwe_sk);
+            let sum = ct_a.add(&ct_b, &key);
+            assert_eq!(
+                sum.decrypt(&keys.lwe_sk),
+                a.wrapping_add(b) as u64,
+                "{a} + {b}"
+            );
+        }
+
+        let empty = RadixCiphertext { blocks: vec![] };
+        assert!(empty.add(&empty, &key).blocks.is_empty());
+    }
+}
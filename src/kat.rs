@@ -0,0 +1,312 @@
+//! Known-answer tests pinning the seeded key and ciphertext derivations, the serialization format
+//! and the results of the core operations.
+//!
+//! Every message is encrypted, so that a change of the rounding of `decode` shows up as a wrong
+//! recorded message, and the keyswitched ciphertext depends on the signs of the decomposition
+//! digits. The keys are too large to be recorded whole: their fingerprints are.
+//!
+//! The fixtures committed under `kat/` are regenerated with `cargo run --bin gen-kat`. This should
+//! only be done when a change of the derivations or of the wire format is intended.
+
+use crate::glwe::{keygen_with, GlweCiphertext};
+use crate::lwe::{compute_ksk_with, lwe_keygen_with, LweCiphertext};
+use crate::utils::{decode, encode, fingerprint, NONCE_LEN};
+use crate::N;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+/// Location of the fixtures, relative to the crate root.
+pub const FIXTURES_PATH: &str = "kat/vectors.json";
+
+/// Seeds of the keys the vectors are computed with.
+pub const LWE_KEY_SEED: u64 = 1;
+pub const GLWE_KEY_SEED: u64 = 2;
+pub const KSK_SEED: u64 = 3;
+
+/// Messages encrypted as LWE ciphertexts. The i-th one is encrypted with the seed `100 + i`.
+/// The first four were the only ones of the first fixtures, the others complete the plaintext space.
+const LWE_MESSAGES: [u8; 16] = [0, 1, 7, 15, 2, 3, 4, 5, 6, 8, 9, 10, 11, 12, 13, 14];
+
+/// Domain of the fingerprints of the keys.
+const KEY_DOMAIN: &[u8] = b"ttfhe/kat";
+
+/// Nonce of the deterministic encryptions.
+const NONCE: [u8; NONCE_LEN] = *b"ttfhe known-answer nonce";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KnownAnswers {
+    pub lwe_key_seed: u64,
+    pub glwe_key_seed: u64,
+    pub ksk_seed: u64,
+    /// Fingerprints of the coefficients of the keys, hex encoded.
+    pub lwe_key: String,
+    pub glwe_key: String,
+    pub ksk: String,
+    pub vectors: Vec<Vector>,
+}
+
+/// Expected result of one operation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vector {
+    pub name: String,
+    /// bincode serialization of the resulting ciphertext, hex encoded.
+    pub bytes: String,
+    /// Decryption of the resulting ciphertext, before decoding.
+    pub phase: u64,
+    /// Decoded message.
+    pub message: u8,
+}
+
+impl KnownAnswers {
+    /// Computes all vectors from the key seeds.
+    pub fn generate(lwe_key_seed: u64, glwe_key_seed: u64, ksk_seed: u64) -> Self {
+        let lwe_sk = lwe_keygen_with(&mut ChaCha20Rng::seed_from_u64(lwe_key_seed));
+        let glwe_sk = keygen_with(&mut ChaCha20Rng::seed_from_u64(glwe_key_seed));
+        let ksk = compute_ksk_with(
+            &glwe_sk.recode(),
+            &lwe_sk,
+            &mut ChaCha20Rng::seed_from_u64(ksk_seed),
+        );
+
+        let mut vectors = vec![];
+        let mut push = |name: String, bytes: Vec<u8>, phase: u64| {
+            vectors.push(Vector {
+                name,
+                bytes: to_hex(&bytes),
+                phase,
+                message: decode(phase),
+            })
+        };
+
+        let cts: Vec<LweCiphertext> = LWE_MESSAGES
+            .iter()
+            .enumerate()
+            .map(|(i, &msg)| {
+                let mut rng = ChaCha20Rng::seed_from_u64(100 + i as u64);
+                LweCiphertext::encrypt_with(encode(msg), &lwe_sk, &mut rng)
+            })
+            .collect();
+
+        for (msg, ct) in LWE_MESSAGES.iter().zip(&cts) {
+            push(
+                format!("lwe_encrypt/{msg}"),
+                serialize(ct),
+                ct.clone().decrypt(&lwe_sk),
+            );
+        }
+
+        for (i, j) in [(1, 2), (2, 3)] {
+            let sum = cts[i].clone().add(cts[j].clone());
+            push(
+                format!("lwe_add/{}+{}", LWE_MESSAGES[i], LWE_MESSAGES[j]),
+                serialize(&sum),
+                sum.decrypt(&lwe_sk),
+            );
+        }
+
+        for (i, j) in [(3, 2), (1, 2)] {
+            let diff = cts[i].clone().sub(&cts[j]);
+            push(
+                format!("lwe_sub/{}-{}", LWE_MESSAGES[i], LWE_MESSAGES[j]),
+                serialize(&diff),
+                diff.decrypt(&lwe_sk),
+            );
+        }
+
+        for (i, c) in [(5, 5), (4, 7)] {
+            let mut product = cts[i].clone();
+            product.multiply_constant_assign(c);
+            push(
+                format!("lwe_multiply_constant/{}*{c}", LWE_MESSAGES[i]),
+                serialize(&product),
+                product.decrypt(&lwe_sk),
+            );
+        }
+
+        // The switched ciphertext is scaled back to modulus 2^64 to be decrypted.
+        let mut switched = cts[2].modswitch();
+        let bytes = serialize(&switched);
+        switched.multiply_constant_assign(1 << (64 - (2 * N).ilog2()));
+        push(
+            format!("lwe_modswitch/{}", LWE_MESSAGES[2]),
+            bytes,
+            switched.decrypt(&lwe_sk),
+        );
+
+        let mut rng = ChaCha20Rng::seed_from_u64(200);
+        let glwe_ct = GlweCiphertext::encrypt_with(encode(9), &glwe_sk, &mut rng);
+        push(
+            "glwe_encrypt/9".to_string(),
+            serialize(&glwe_ct),
+            glwe_ct.decrypt(&glwe_sk),
+        );
+
+        let mut rng = ChaCha20Rng::seed_from_u64(201);
+        let keyswitched = GlweCiphertext::encrypt_with(encode(5), &glwe_sk, &mut rng)
+            .sample_extract()
+            .keyswitch(&ksk);
+        push(
+            "keyswitch/5".to_string(),
+            serialize(&keyswitched),
+            keyswitched.decrypt(&lwe_sk),
+        );
+
+        let ct = LweCiphertext::encrypt_deterministic(encode(3), &lwe_sk, NONCE);
+        push(
+            "lwe_encrypt_deterministic/3".to_string(),
+            serialize(&ct),
+            ct.clone().decrypt(&lwe_sk),
+        );
+
+        let glwe_ct = GlweCiphertext::encrypt_deterministic(encode(9), &glwe_sk, NONCE);
+        push(
+            "glwe_encrypt_deterministic/9".to_string(),
+            serialize(&glwe_ct),
+            glwe_ct.decrypt(&glwe_sk),
+        );
+
+        let glwe_coefs: Vec<u64> = glwe_sk.polys.iter().flat_map(|p| p.coefs.clone()).collect();
+        let ksk_coefs: Vec<u64> = ksk
+            .keys
+            .iter()
+            .flat_map(|key| key.mask.iter().chain([&key.body]).copied())
+            .collect();
+
+        KnownAnswers {
+            lwe_key_seed,
+            glwe_key_seed,
+            ksk_seed,
+            lwe_key: to_hex(&fingerprint(KEY_DOMAIN, &lwe_sk.coefs)),
+            glwe_key: to_hex(&fingerprint(KEY_DOMAIN, &glwe_coefs)),
+            ksk: to_hex(&fingerprint(KEY_DOMAIN, &ksk_coefs)),
+            vectors,
+        }
+    }
+}
+
+fn serialize<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).unwrap()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes the output of `to_hex`. Errors on an odd length or on characters other than hex
+/// digits.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("hex string of odd length {}", hex.len()));
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            let digits = std::str::from_utf8(pair)
+                .ok()
+                .filter(|digits| digits.bytes().all(|c| c.is_ascii_hexdigit()))
+                .ok_or_else(|| format!("invalid hex digits at offset {}", 2 * i))?;
+            Ok(u8::from_str_radix(digits, 16).unwrap())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glwe::{keygen_with, GlweCiphertext};
+    use crate::kat::{from_hex, to_hex, KnownAnswers};
+    use crate::lwe::{lwe_keygen_with, LweCiphertext};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    fn fixtures() -> KnownAnswers {
+        serde_json::from_str(include_str!("../kat/vectors.json")).unwrap()
+    }
+
+    #[test]
+    fn test_known_answers() {
+        let expected = fixtures();
+        let actual = KnownAnswers::generate(
+            expected.lwe_key_seed,
+            expected.glwe_key_seed,
+            expected.ksk_seed,
+        );
+
+        assert_eq!(expected.lwe_key, actual.lwe_key, "LWE key differs");
+        assert_eq!(expected.glwe_key, actual.glwe_key, "GLWE key differs");
+        assert_eq!(expected.ksk, actual.ksk, "keyswitching key differs");
+        assert_eq!(expected.vectors.len(), actual.vectors.len());
+        for (expected, actual) in expected.vectors.iter().zip(&actual.vectors) {
+            assert_eq!(expected.name, actual.name);
+            assert_eq!(
+                expected.bytes, actual.bytes,
+                "{}: bytes differ",
+                expected.name
+            );
+            assert_eq!(
+                expected.phase, actual.phase,
+                "{}: phase differs",
+                expected.name
+            );
+            assert_eq!(expected.message, actual.message, "{}", expected.name);
+        }
+    }
+
+    #[test]
+    fn test_recorded_messages() {
+        for vector in fixtures().vectors {
+            let (op, operands) = vector.name.split_once('/').unwrap();
+            let parse = |m: &str| m.parse::<u8>().unwrap();
+            let msg = if let Some((a, c)) = operands.split_once('*') {
+                parse(a).wrapping_mul(parse(c))
+            } else if let Some((a, b)) = operands.split_once('-') {
+                parse(a).wrapping_sub(parse(b))
+            } else {
+                operands.split('+').map(parse).sum::<u8>()
+            } % 16;
+            assert_eq!(vector.message, msg, "{op}");
+        }
+    }
+
+    #[test]
+    fn test_recorded_bytes_deserialize() {
+        let fixtures = fixtures();
+        let lwe_sk = lwe_keygen_with(&mut ChaCha20Rng::seed_from_u64(fixtures.lwe_key_seed));
+        let glwe_sk = keygen_with(&mut ChaCha20Rng::seed_from_u64(fixtures.glwe_key_seed));
+
+        for vector in fixtures.vectors {
+            let bytes = from_hex(&vector.bytes).unwrap();
+            let phase = match vector.name.split_once('/').unwrap().0 {
+                "glwe_encrypt" | "glwe_encrypt_deterministic" => {
+                    bincode::deserialize::<GlweCiphertext>(&bytes)
+                        .unwrap()
+                        .decrypt(&glwe_sk)
+                }
+                // Switched ciphertexts are only checked to deserialize.
+                "lwe_modswitch" => {
+                    bincode::deserialize::<LweCiphertext>(&bytes).unwrap();
+                    continue;
+                }
+                _ => bincode::deserialize::<LweCiphertext>(&bytes)
+                    .unwrap()
+                    .decrypt(&lwe_sk),
+            };
+            assert_eq!(phase, vector.phase, "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(from_hex(&to_hex(&bytes)), Ok(bytes));
+        assert_eq!(from_hex("00ABff"), Ok(vec![0x00, 0xab, 0xff]));
+
+        let err = from_hex("abc").unwrap_err();
+        assert!(err.contains("odd length 3"), "{err}");
+        for invalid in ["0g", "+f", "-1", " 1", "\u{e9}"] {
+            let err = from_hex(&format!("00{invalid}")).unwrap_err();
+            assert!(err.contains("at offset 2"), "{invalid}: {err}");
+        }
+    }
+}
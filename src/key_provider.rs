@@ -0,0 +1,193 @@
+//! Access to secret keys that do not live in the memory of the process, e.g. held by a hardware
+//! security module or wrapped by a key management service.
+//!
+//! The functions that need a secret key but not its lifetime, decryption with a `Decryptor`,
+//! `stats::measure_noise` and `boolean::gen_server_key`, take a `&dyn KeyProvider` and fetch the
+//! key at every call. A provider may load, unwrap or compute the key on demand and return it owned,
+//! or return a reference to a key it holds. The in-memory client keys are providers of their own
+//! keys.
+
+use crate::boolean;
+use crate::error::TfheError;
+use crate::glwe::SecretKey;
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::shortint;
+use crate::utils::decode;
+use std::borrow::Cow;
+
+/// Source of the secret keys of a client.
+/// Failures to provide a key, e.g. `TfheError::KeyUnavailable`, propagate to the callers.
+pub trait KeyProvider {
+    /// Key of the ciphertexts, under which they are encrypted and decrypted.
+    fn lwe_key(&self) -> Result<Cow<'_, LweSecretKey>, TfheError>;
+
+    /// GLWE key of the blind rotations.
+    fn glwe_key(&self) -> Result<Cow<'_, SecretKey>, TfheError>;
+}
+
+impl KeyProvider for boolean::ClientKey {
+    fn lwe_key(&self) -> Result<Cow<'_, LweSecretKey>, TfheError> {
+        Ok(Cow::Borrowed(&self.sk))
+    }
+
+    fn glwe_key(&self) -> Result<Cow<'_, SecretKey>, TfheError> {
+        Ok(Cow::Borrowed(&self.glwe_sk))
+    }
+}
+
+impl KeyProvider for shortint::ClientKey {
+    fn lwe_key(&self) -> Result<Cow<'_, LweSecretKey>, TfheError> {
+        self.key.lwe_key()
+    }
+
+    fn glwe_key(&self) -> Result<Cow<'_, SecretKey>, TfheError> {
+        self.key.glwe_key()
+    }
+}
+
+/// Decrypts ciphertexts under the LWE key of a provider.
+#[derive(Clone, Copy)]
+pub struct Decryptor<'a> {
+    provider: &'a dyn KeyProvider,
+}
+
+impl<'a> Decryptor<'a> {
+    pub fn new(provider: &'a dyn KeyProvider) -> Self {
+        Decryptor { provider }
+    }
+
+    /// Decrypts and decodes `ct`, fetching the key once.
+    /// Errors if the provider fails or if `ct` is not of the dimension of the key.
+    pub fn decrypt(&self, ct: &LweCiphertext) -> Result<u8, TfheError> {
+        let sk = self.provider.lwe_key()?;
+        Ok(decode(ct.clone().try_decrypt(&sk)?))
+    }
+
+    /// Decrypts and decodes every ciphertext of `cts`, fetching the key once for all of them.
+    pub fn decrypt_many(&self, cts: &[LweCiphertext]) -> Result<Vec<u8>, TfheError> {
+        let sk = self.provider.lwe_key()?;
+        cts.iter()
+            .map(|ct| Ok(decode(ct.clone().try_decrypt(&sk)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boolean::{gen_keys, gen_server_key, ClientKey};
+    use crate::error::TfheError;
+    use crate::glwe::SecretKey;
+    use crate::key_provider::{Decryptor, KeyProvider};
+    use crate::lwe::{LweCiphertext, LweSecretKey};
+    use crate::params::presets::TEST;
+    use crate::params::{Parameters, PbsOrder};
+    use crate::utils::encode;
+    use std::borrow::Cow;
+    use std::cell::Cell;
+
+    /// Provider of owned copies of the keys of a client key, counting the fetches.
+    struct CountingProvider {
+        client_key: ClientKey,
+        lwe_fetches: Cell<usize>,
+        glwe_fetches: Cell<usize>,
+    }
+
+    impl KeyProvider for CountingProvider {
+        fn lwe_key(&self) -> Result<Cow<'_, LweSecretKey>, TfheError> {
+            self.lwe_fetches.set(self.lwe_fetches.get() + 1);
+            Ok(Cow::Owned(self.client_key.sk.clone()))
+        }
+
+        fn glwe_key(&self) -> Result<Cow<'_, SecretKey>, TfheError> {
+            self.glwe_fetches.set(self.glwe_fetches.get() + 1);
+            Ok(Cow::Owned(self.client_key.glwe_sk.clone()))
+        }
+    }
+
+    /// Provider whose backend is down.
+    struct FailingProvider;
+
+    impl KeyProvider for FailingProvider {
+        fn lwe_key(&self) -> Result<Cow<'_, LweSecretKey>, TfheError> {
+            Err(TfheError::KeyUnavailable("connection refused".to_string()))
+        }
+
+        fn glwe_key(&self) -> Result<Cow<'_, SecretKey>, TfheError> {
+            Err(TfheError::KeyUnavailable("connection refused".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_counting_provider() {
+        let (client_key, _) = gen_keys(&TEST);
+        let provider = CountingProvider {
+            client_key,
+            lwe_fetches: Cell::new(0),
+            glwe_fetches: Cell::new(0),
+        };
+
+        let cts: Vec<_> = (0..4)
+            .map(|msg| LweCiphertext::encrypt(encode(msg), &provider.client_key.sk))
+            .collect();
+        let decryptor = Decryptor::new(&provider);
+        assert_eq!(decryptor.decrypt(&cts[3]), Ok(3));
+        assert_eq!(decryptor.decrypt_many(&cts), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(provider.lwe_fetches.get(), 2);
+        assert_eq!(provider.glwe_fetches.get(), 0);
+
+        let server_key = gen_server_key(&provider).unwrap();
+        assert_eq!(
+            (provider.lwe_fetches.get(), provider.glwe_fetches.get()),
+            (3, 1)
+        );
+        let ct = server_key.and(
+            &provider.client_key.encrypt(true),
+            &provider.client_key.encrypt(true),
+        );
+        assert!(provider.client_key.decrypt(&ct));
+    }
+
+    #[test]
+    fn test_keyswitch_bootstrap_order() {
+        let params = Parameters {
+            pbs_order: PbsOrder::KeyswitchBootstrap,
+            ..TEST
+        };
+        let (client_key, _) = gen_keys(&params);
+
+        // The LWE key of the blind rotations is drawn afresh: the key of the ciphertexts is not
+        // fetched.
+        let provider = CountingProvider {
+            client_key,
+            lwe_fetches: Cell::new(0),
+            glwe_fetches: Cell::new(0),
+        };
+        let server_key = gen_server_key(&provider).unwrap();
+        assert_eq!(
+            (provider.lwe_fetches.get(), provider.glwe_fetches.get()),
+            (0, 1)
+        );
+        let ct = server_key.xor(
+            &provider.client_key.encrypt(true),
+            &provider.client_key.encrypt(false),
+        );
+        assert_eq!(Decryptor::new(&provider.client_key).decrypt(&ct), Ok(1));
+    }
+
+    #[test]
+    fn test_failing_provider() {
+        let (client_key, _) = gen_keys(&TEST);
+        let ct = client_key.encrypt(true);
+
+        let err = TfheError::KeyUnavailable("connection refused".to_string());
+        assert_eq!(
+            Decryptor::new(&FailingProvider).decrypt(&ct),
+            Err(err.clone())
+        );
+        assert_eq!(
+            Decryptor::new(&FailingProvider).decrypt_many(&[ct]),
+            Err(err.clone())
+        );
+        assert_eq!(gen_server_key(&FailingProvider).err(), Some(err));
+    }
+}
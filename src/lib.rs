@@ -1,13 +1,154 @@
+#![allow(clippy::needless_range_loop, clippy::should_implement_trait)]
+
+/// Counts the evaluation of `$body` as an execution of `metrics::Operation::$op`.
+/// Expands to `$body` alone without the `metrics` feature.
+macro_rules! measured {
+    ($op:ident, $body:block) => {{
+        #[cfg(feature = "metrics")]
+        let res = crate::metrics::record(crate::metrics::Operation::$op, || $body);
+        #[cfg(not(feature = "metrics"))]
+        let res = $body;
+        res
+    }};
+}
+
+/// Implements the wrapping arithmetic operators of the ciphertext type `$ct<T>`, for every scalar
+/// `T: Torus`. The type must provide `map_coefs(&mut self, f)` and `zip_coefs(&mut self, rhs, f)`
+/// applying `f` to every coefficient of the mask and of the body. A constant multiplies every
+/// coefficient, negative ones included.
+macro_rules! impl_ciphertext_ops {
+    ($ct:ident) => {
+        impl<T: crate::torus::Torus> std::ops::AddAssign<&$ct<T>> for $ct<T> {
+            fn add_assign(&mut self, rhs: &$ct<T>) {
+                self.zip_coefs(rhs, T::wrapping_add);
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::AddAssign for $ct<T> {
+            fn add_assign(&mut self, rhs: $ct<T>) {
+                *self += &rhs;
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Add<&$ct<T>> for $ct<T> {
+            type Output = $ct<T>;
+
+            fn add(mut self, rhs: &$ct<T>) -> $ct<T> {
+                self += rhs;
+                self
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Add for $ct<T> {
+            type Output = $ct<T>;
+
+            fn add(mut self, rhs: $ct<T>) -> $ct<T> {
+                self += &rhs;
+                self
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::SubAssign<&$ct<T>> for $ct<T> {
+            fn sub_assign(&mut self, rhs: &$ct<T>) {
+                self.zip_coefs(rhs, T::wrapping_sub);
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::SubAssign for $ct<T> {
+            fn sub_assign(&mut self, rhs: $ct<T>) {
+                *self -= &rhs;
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Sub<&$ct<T>> for $ct<T> {
+            type Output = $ct<T>;
+
+            fn sub(mut self, rhs: &$ct<T>) -> $ct<T> {
+                self -= rhs;
+                self
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Sub for $ct<T> {
+            type Output = $ct<T>;
+
+            fn sub(mut self, rhs: $ct<T>) -> $ct<T> {
+                self -= &rhs;
+                self
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Neg for $ct<T> {
+            type Output = $ct<T>;
+
+            fn neg(mut self) -> $ct<T> {
+                self.map_coefs(T::wrapping_neg);
+                self
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::MulAssign<i64> for $ct<T> {
+            fn mul_assign(&mut self, constant: i64) {
+                self.map_coefs(|c| c.wrapping_mul(T::from_i64(constant)));
+            }
+        }
+
+        impl<T: crate::torus::Torus> std::ops::Mul<i64> for $ct<T> {
+            type Output = $ct<T>;
+
+            fn mul(mut self, constant: i64) -> $ct<T> {
+                self *= constant;
+                self
+            }
+        }
+    };
+}
+
+pub mod boolean;
+pub mod dfa;
+pub mod digits;
+pub mod error;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
 pub mod ggsw;
 pub mod glwe;
+pub mod hamming;
+pub mod integer;
+pub mod kat;
+pub mod key_provider;
+pub mod linalg;
+pub mod lut;
 pub mod lwe;
+pub mod membership;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod noise;
+pub mod ntt;
+pub mod packing;
+pub mod params;
 pub mod poly;
+pub mod prelude;
+pub mod public_key;
+pub mod sanitize;
+pub mod security;
+pub mod serialization;
+pub mod shortint;
+pub mod size;
+pub mod slots;
+#[cfg(feature = "async")]
+pub mod spawn;
+#[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+pub mod stats;
+pub mod tally;
+pub mod torus;
+pub mod transcipher;
+pub mod uint4;
 pub mod utils;
 
-/// Decomposition basis for the external product. This value is used implicitely.
+// Decomposition basis for the external product. This value is used implicitely.
 // pub const B: usize = 256;
 
-/// Ciphertext modulus. This value is used implicitely.
+// Ciphertext modulus. This value is used implicitely.
 // pub const Q: usize = 2^64;
 
 /// Plaintext modulus
@@ -25,3 +166,37 @@ pub const N: usize = 1024;
 
 /// Dimension of LWE ciphertexts
 pub const LWE_DIM: usize = 630;
+
+/// Standard deviation of the noise of fresh LWE encryptions (2^49).
+pub const LWE_STD_DEV: f64 = (1u64 << 49) as f64;
+
+/// Standard deviation of the noise of fresh GLWE encryptions (2^39).
+pub const GLWE_STD_DEV: f64 = (1u64 << 39) as f64;
+
+#[cfg(test)]
+mod tests {
+    use crate::boolean::ServerKey;
+    use crate::ggsw::{BootstrappingKey, GgswCiphertext};
+    use crate::glwe::{GlweCiphertext, SecretKey};
+    use crate::lwe::{KeySwitchingKey, LweCiphertext, LweSecretKey};
+    use crate::params::Parameters;
+    use crate::poly::ResiduePoly;
+
+    /// Ciphertexts and keys hold no thread-local state, so that evaluation keys can be shared
+    /// across threads, e.g. behind an `Arc`.
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Parameters>();
+        assert_send_sync::<ResiduePoly>();
+        assert_send_sync::<LweCiphertext>();
+        assert_send_sync::<GlweCiphertext>();
+        assert_send_sync::<GgswCiphertext>();
+        assert_send_sync::<LweSecretKey>();
+        assert_send_sync::<SecretKey>();
+        assert_send_sync::<KeySwitchingKey>();
+        assert_send_sync::<BootstrappingKey>();
+        assert_send_sync::<ServerKey>();
+    }
+}
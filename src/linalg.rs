@@ -0,0 +1,167 @@
+//! Products of clear matrices with encrypted vectors, for encrypted linear models.
+//!
+//! With the `parallel` feature the rows are computed in parallel.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::LweCiphertext;
+use crate::P;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Computes `matrix * cts`: the i-th output encrypts the sum of the messages of `cts` weighted by
+/// the i-th row. `input_degree` bounds the messages of `cts`.
+/// Errors if the dimensions disagree, or if a row could sum to `P` or more, since its result would
+/// wrap around.
+pub fn matvec(
+    matrix: &[Vec<u64>],
+    cts: &[LweCiphertext],
+    input_degree: u64,
+) -> Result<Vec<LweCiphertext>, String> {
+    validate(matrix, cts, input_degree, P as u64 - 1)?;
+
+    Ok(map_rows(matrix, |row| row_product(row, cts)))
+}
+
+/// Computes `matrix * cts` and bootstraps each output through the LUT of `activation`.
+/// Errors if the dimensions disagree, or if a row could sum to `P/2` or more, since bootstrapping
+/// requires the padding bit to be clear.
+pub fn matvec_activated(
+    matrix: &[Vec<u64>],
+    cts: &[LweCiphertext],
+    input_degree: u64,
+    activation: impl Fn(u8) -> u8 + Sync,
+    key: &EvaluationKey,
+) -> Result<Vec<LweCiphertext>, String> {
+    validate(matrix, cts, input_degree, P as u64 / 2 - 1)?;
+
+    Ok(map_rows(matrix, |row| {
+        key.bootstrap(&row_product(row, cts), &activation)
+    }))
+}
+
+/// Checks that every row of `matrix` has one weight per input, and that the weighted sums of
+/// inputs bounded by `input_degree` are at most `max_degree`.
+fn validate(
+    matrix: &[Vec<u64>],
+    cts: &[LweCiphertext],
+    input_degree: u64,
+    max_degree: u64,
+) -> Result<(), String> {
+    if cts.is_empty() {
+        return Err("the input vector is empty".to_string());
+    }
+    for (i, row) in matrix.iter().enumerate() {
+        if row.len() != cts.len() {
+            return Err(format!(
+                "row {i} has {} weights for {} inputs",
+                row.len(),
+                cts.len()
+            ));
+        }
+        let degree = row.iter().fold(0u64, |acc, &w| {
+            acc.saturating_add(w.saturating_mul(input_degree))
+        });
+        if degree > max_degree {
+            return Err(format!(
+                "row {i} may sum to {degree}, above the maximum of {max_degree}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Sum of `cts` weighted by `row`.
+fn row_product(row: &[u64], cts: &[LweCiphertext]) -> LweCiphertext {
+    let mut res = LweCiphertext::zero(cts[0].mask.len());
+    for (&w, ct) in row.iter().zip(cts) {
+        res = res.add(ct.multiply_constant(w));
+    }
+    res
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_rows(matrix: &[Vec<u64>], f: impl Fn(&[u64]) -> LweCiphertext) -> Vec<LweCiphertext> {
+    matrix.iter().map(|row| f(row)).collect()
+}
+
+#[cfg(feature = "parallel")]
+fn map_rows(matrix: &[Vec<u64>], f: impl Fn(&[u64]) -> LweCiphertext + Sync) -> Vec<LweCiphertext> {
+    matrix.par_iter().map(|row| f(row)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::linalg::{matvec, matvec_activated};
+    use crate::lwe::{lwe_keygen_for, LweCiphertext};
+    use crate::params::presets::TEST;
+    use crate::utils::{decode, encode};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_matvec() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        for _ in 0..10 {
+            let inputs: Vec<u8> = (0..8).map(|_| thread_rng().gen_range(0..2)).collect();
+            let matrix: Vec<Vec<u64>> = (0..4)
+                .map(|_| (0..8).map(|_| thread_rng().gen_range(0..2)).collect())
+                .collect();
+            let cts: Vec<LweCiphertext> = inputs
+                .iter()
+                .map(|&m| LweCiphertext::encrypt(encode(m), &sk))
+                .collect();
+
+            let res = matvec(&matrix, &cts, 1).unwrap();
+            assert_eq!(res.len(), 4);
+            for (row, ct) in matrix.iter().zip(res) {
+                let expected: u64 = row.iter().zip(&inputs).map(|(&w, &m)| w * m as u64).sum();
+                assert_eq!(decode(ct.decrypt(&sk)) as u64, expected % 16);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matvec_rejected() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let cts = vec![LweCiphertext::encrypt(encode(1), &sk); 8];
+
+        let overflowing = vec![vec![1; 8], vec![2; 8]];
+        let err = matvec(&overflowing, &cts, 1).err().unwrap();
+        assert!(err.contains("row 1 may sum to 16"), "{err}");
+        assert!(matvec(&[vec![1; 8]], &cts, 2)
+            .err()
+            .unwrap()
+            .contains("row 0"));
+        assert!(matvec(&[vec![u64::MAX; 8]], &cts, 1).is_err());
+
+        let err = matvec(&[vec![1; 7]], &cts, 1).err().unwrap();
+        assert!(err.contains("7 weights for 8 inputs"), "{err}");
+        assert!(matvec(&[vec![]], &[], 1).is_err());
+    }
+
+    #[test]
+    fn test_matvec_activated() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let inputs = [1, 0, 1, 1];
+        let cts: Vec<LweCiphertext> = inputs
+            .iter()
+            .map(|&m| LweCiphertext::encrypt(encode(m), &keys.lwe_sk))
+            .collect();
+        let matrix = vec![vec![1, 1, 1, 1], vec![0, 1, 0, 0], vec![1, 2, 3, 1]];
+
+        let shifted_relu = |m: u8| m.saturating_sub(2);
+        let res = matvec_activated(&matrix, &cts, 1, shifted_relu, &key).unwrap();
+        let decrypted: Vec<u8> = res
+            .into_iter()
+            .map(|ct| decode(ct.decrypt(&keys.lwe_sk)))
+            .collect();
+        assert_eq!(decrypted, [1, 0, 3]);
+
+        let err = matvec_activated(&[vec![2, 2, 2, 2]], &cts, 1, shifted_relu, &key)
+            .err()
+            .unwrap();
+        assert!(err.contains("maximum of 7"), "{err}");
+    }
+}
@@ -0,0 +1,94 @@
+//! Lookup tables of bootstraps. Composing them evaluates a chain of functions with a single
+//! bootstrap.
+
+use crate::P;
+
+/// Values of a function on the messages `[0, P/2)`, the ones that can be bootstrapped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lut {
+    values: Vec<u8>,
+}
+
+impl Lut {
+    /// Table of `f`, whose outputs are reduced modulo `P`.
+    pub fn new(f: impl Fn(u8) -> u8) -> Self {
+        Lut {
+            values: (0..P as u8 / 2).map(|m| f(m) % P as u8).collect(),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(|m| m)
+    }
+
+    /// Value of the function at `m`. Panics if `m` is not below `P/2`.
+    pub fn eval(&self, m: u8) -> u8 {
+        self.values[m as usize]
+    }
+
+    /// Table of `g` applied after `self`.
+    /// Errors if an output of `self` is not below `P/2`, since it could not be an input of `g`.
+    pub fn compose(&self, g: &Lut) -> Result<Lut, String> {
+        if let Some(m) = self.values.iter().position(|&v| v as usize >= P / 2) {
+            return Err(format!(
+                "the output {} at {m} is out of the input space [0, {})",
+                self.values[m],
+                P / 2
+            ));
+        }
+
+        Ok(Lut {
+            values: self.values.iter().map(|&v| g.eval(v)).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::lut::Lut;
+    use crate::lwe::LweCiphertext;
+    use crate::params::presets::TEST;
+    use crate::utils::{decode, encode};
+
+    #[test]
+    fn test_compose() {
+        let increment = Lut::new(|m| (m + 1) % 8);
+        let square = Lut::new(|m| m * m % 8);
+
+        assert_eq!(increment.compose(&Lut::identity()).unwrap(), increment);
+        assert_eq!(Lut::identity().compose(&increment).unwrap(), increment);
+        let composed = increment.compose(&square).unwrap();
+        for m in 0..8 {
+            assert_eq!(composed.eval(m), (m + 1) % 8 * ((m + 1) % 8) % 8);
+        }
+
+        // 7 + 1 = 8 cannot be bootstrapped.
+        let err = Lut::new(|m| m + 1).compose(&square).err().unwrap();
+        assert!(err.contains("output 8 at 7"), "{err}");
+        assert_eq!(Lut::new(|m| m + 9).eval(7), 0);
+    }
+
+    #[test]
+    fn test_apply_composed() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let luts = [Lut::new(|m| (m + 1) % 8), Lut::new(|m| m * m % 16)];
+        for m in 0..8 {
+            let ct = LweCiphertext::encrypt(encode(m), &keys.lwe_sk);
+            let once = key.apply_composed(&ct, &luts).unwrap();
+            let twice = key.bootstrap_lut(&key.bootstrap_lut(&ct, &luts[0]), &luts[1]);
+            let expected = (m + 1) % 8 * ((m + 1) % 8) % 16;
+            assert_eq!(decode(once.decrypt(&keys.lwe_sk)), expected);
+            assert_eq!(decode(twice.decrypt(&keys.lwe_sk)), expected);
+        }
+
+        let ct = LweCiphertext::encrypt(encode(5), &keys.lwe_sk);
+        let refreshed = key.apply_composed(&ct, &[]).unwrap();
+        assert_eq!(decode(refreshed.decrypt(&keys.lwe_sk)), 5);
+        assert!(key
+            .apply_composed(&ct, &[Lut::new(|m| m + 8), Lut::identity()])
+            .is_err());
+    }
+}
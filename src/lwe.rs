@@ -1,32 +1,151 @@
-use crate::{utils::round_value, LWE_DIM, N};
-use rand::{thread_rng, Rng};
-use rand_distr::{Distribution, Normal};
+use crate::error::TfheError;
+use crate::params::{DecompositionBaseLog, DecompositionLevelCount, Parameters};
+use crate::torus::Torus;
+use crate::utils::{
+    decode, decode_checked, fingerprint, mask_rng, nonce_rng, par_map, round_value,
+    sample_gaussian, short_digest, wipe, NONCE_LEN, SEED_LEN,
+};
+use crate::LWE_DIM;
+use rand::{thread_rng, CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
+/// LWE ciphertext on the torus of scalars `T`, see `torus`.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct LweCiphertext {
-    pub mask: Vec<u64>,
+// `Torus` already requires the scalars to be (de)serializable.
+#[serde(bound = "")]
+pub struct LweCiphertext<T: Torus = u64> {
+    pub mask: Vec<T>,
+    pub body: T,
+}
+
+pub type LweCiphertext32 = LweCiphertext<u32>;
+pub type LweCiphertext64 = LweCiphertext<u64>;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct LweSecretKey<T: Torus = u64> {
+    /// Coefficients, `-1` being represented by the largest scalar.
+    pub coefs: Vec<T>,
+    /// Parameters the key was generated for.
+    pub params: Parameters,
+    pub distribution: KeyDistribution,
+    /// Noise level of the encryptions under the key.
+    pub noise: NoiseLevel,
+}
+
+/// Distribution of the coefficients of a LWE secret key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyDistribution {
+    /// Coefficients in `{0, 1}`.
+    Binary,
+    /// Coefficients in `{-1, 0, 1}`. Their bootstrapping keys hold two GGSW ciphertexts per
+    /// coefficient, see `ggsw::compute_bsk`.
+    Ternary,
+}
+
+/// Standard deviation of the noise of encryptions under a LWE secret key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoiseLevel {
+    /// `params.lwe_sigma`, for keys of dimension `params.lwe_dim`.
+    Lwe,
+    /// `params.glwe_sigma`, for keys recoded from a GLWE key, see `glwe::SecretKey::recode`.
+    Glwe,
+}
+
+/// Encryptions of the bits of a key under another key, the i-th bit being encrypted `levels` times,
+/// scaled by q/B^l, ..., q/B with lg(B) = `base_log`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct KeySwitchingKey<T: Torus = u64> {
+    pub base_log: DecompositionBaseLog,
+    pub levels: DecompositionLevelCount,
+    pub keys: Vec<LweCiphertext<T>>,
+}
+
+/// LWE ciphertext whose mask is replaced by the seed of the stream it is drawn from, see
+/// `utils::mask_rng`. Only the seed and the body are stored, instead of `dim + 1` coefficients.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeededLweCiphertext {
+    pub seed: [u8; SEED_LEN],
+    pub dim: usize,
     pub body: u64,
 }
 
-pub type LweSecretKey = Vec<u64>;
-pub type KeySwitchingKey = Vec<LweCiphertext>;
+/// Keyswitching key whose masks are drawn in order from the stream of `seed`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeededKeySwitchingKey {
+    pub seed: [u8; SEED_LEN],
+    pub base_log: DecompositionBaseLog,
+    pub levels: DecompositionLevelCount,
+    /// Dimension of the output key.
+    pub dim: usize,
+    pub bodies: Vec<u64>,
+}
 
-impl LweCiphertext {
-    pub fn encrypt(mu: u64, sk: &LweSecretKey) -> LweCiphertext {
-        let sigma = f64::powf(2.0, 49.0);
-        let normal = Normal::new(0.0, sigma).unwrap();
+/// LWE ciphertext switched to the modulus `2^log2_modulus`, to be sent back to the client.
+/// The mask followed by the body are packed on `log2_modulus` bits each, least significant bit
+/// first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompressedLweCiphertext {
+    pub log2_modulus: u32,
+    pub dim: usize,
+    pub bytes: Vec<u8>,
+}
 
-        let e = normal.sample(&mut rand::thread_rng()).round() as i64;
-        let mu_star = mu.wrapping_add_signed(e);
+/// Wipes the coefficients, so that they do not linger in freed memory.
+impl<T: Torus> Drop for LweSecretKey<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.coefs);
+    }
+}
 
-        let mask: Vec<u64> = (0..LWE_DIM).map(|_| rand::random::<u64>()).collect();
+impl<T: Torus> LweSecretKey<T> {
+    /// Standard deviation of the noise of encryptions under `self`, scaled to the width of `T`.
+    pub(crate) fn std_dev(&self) -> f64 {
+        T::scale_std_dev(match self.noise {
+            NoiseLevel::Lwe => self.params.lwe_sigma,
+            NoiseLevel::Glwe => self.params.glwe_sigma,
+        })
+    }
+}
 
-        let mut body = 0u64;
-        for i in 0..LWE_DIM {
-            if sk[i] == 1 {
-                body = body.wrapping_add(mask[i]);
-            }
+impl LweSecretKey {
+    /// Copy of `self` on the torus of scalars `T`, for the same parameters: the coefficients
+    /// `-1`, `0` and `1` keep their values.
+    pub fn to_torus<T: Torus>(&self) -> LweSecretKey<T> {
+        LweSecretKey {
+            coefs: self.coefs.iter().map(|&s| T::from_u64(s)).collect(),
+            params: self.params,
+            distribution: self.distribution,
+            noise: self.noise,
+        }
+    }
+
+    /// Digest identifying `self` without revealing it, e.g. in known-answer tests.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(b"ttfhe/lwe-key", &self.coefs)
+    }
+}
+
+impl<T: Torus> LweCiphertext<T> {
+    pub fn encrypt(mu: T, sk: &LweSecretKey<T>) -> Self {
+        Self::encrypt_with(mu, sk, &mut thread_rng())
+    }
+
+    /// Encrypts `mu` under `sk`, drawing the mask and the noise from `rng`.
+    /// The dimension and the noise level are the ones of the parameters of `sk`.
+    pub fn encrypt_with<R: Rng + CryptoRng>(mu: T, sk: &LweSecretKey<T>, rng: &mut R) -> Self {
+        let e = sample_gaussian(sk.std_dev(), rng);
+        let mu_star = mu.wrapping_add(T::from_i64(e));
+
+        let mask: Vec<T> = (0..sk.coefs.len())
+            .map(|_| T::from_u64(rng.gen::<u64>()))
+            .collect();
+
+        let mut body = T::default();
+        for i in 0..sk.coefs.len() {
+            body = body.wrapping_add(mask[i].wrapping_mul(sk.coefs[i]));
         }
 
         body = body.wrapping_add(mu_star);
@@ -34,29 +153,46 @@ impl LweCiphertext {
         LweCiphertext { mask, body }
     }
 
-    pub fn decrypt(self, sk: &LweSecretKey) -> u64 {
-        let mut body: u64 = 0u64;
-        for i in 0..sk.len() {
-            if sk[i] == 1 {
-                body = body.wrapping_add(self.mask[i]);
-            }
+    /// Like `decrypt`, but returns an error instead of panicking if `self` and `sk` have different
+    /// dimensions, as when they come from different parameter sets.
+    pub fn try_decrypt(self, sk: &LweSecretKey<T>) -> Result<T, TfheError> {
+        if self.mask.len() != sk.coefs.len() {
+            return Err(TfheError::KeyMismatchDimension {
+                expected: sk.coefs.len(),
+                found: self.mask.len(),
+            });
         }
-
-        self.body.wrapping_sub(body) // mu_star
+        Ok(self.decrypt(sk))
     }
 
-    pub fn decrypt_modswitched(self, sk: &LweSecretKey) -> u64 {
-        let mut dot_prod = 0u64;
-        for i in 0..LWE_DIM {
-            if sk[i] == 1 {
-                dot_prod = (dot_prod + self.mask[i]) % (2 * LWE_DIM as u64);
-            }
+    pub fn decrypt(self, sk: &LweSecretKey<T>) -> T {
+        assert_eq!(
+            self.mask.len(),
+            sk.coefs.len(),
+            "ciphertext and key dimensions differ"
+        );
+
+        let mut body = T::default();
+        for i in 0..sk.coefs.len() {
+            body = body.wrapping_add(self.mask[i].wrapping_mul(sk.coefs[i]));
         }
 
-        self.body.wrapping_sub(dot_prod) % (2 * LWE_DIM as u64) // mu_star
+        self.body.wrapping_sub(body) // mu_star
     }
 
-    pub fn add(self, rhs: Self) -> Self {
+    /// Whether `self` is a trivial encryption, with a zero mask. Operations check it to skip work
+    /// instead of tracking a flag, so that ciphertexts and their serialization are unchanged.
+    pub fn is_trivial(&self) -> bool {
+        self.mask.iter().all(|&a| a == T::default())
+    }
+
+    /// Adding a trivial ciphertext only touches the body.
+    pub fn add(mut self, rhs: Self) -> Self {
+        if rhs.is_trivial() && rhs.mask.len() == self.mask.len() {
+            self.body = self.body.wrapping_add(rhs.body);
+            return self;
+        }
+
         let mask = self
             .mask
             .iter()
@@ -82,7 +218,14 @@ impl LweCiphertext {
         LweCiphertext { mask, body }
     }
 
-    pub fn multiply_constant_assign(&mut self, constant: u64) -> &mut Self {
+    pub fn multiply_constant(&self, constant: T) -> Self {
+        LweCiphertext {
+            mask: self.mask.iter().map(|a| a.wrapping_mul(constant)).collect(),
+            body: self.body.wrapping_mul(constant),
+        }
+    }
+
+    pub fn multiply_constant_assign(&mut self, constant: T) -> &mut Self {
         self.mask = self.mask.iter().map(|a| a.wrapping_mul(constant)).collect();
 
         self.body = self.body.wrapping_mul(constant);
@@ -90,46 +233,438 @@ impl LweCiphertext {
         self
     }
 
-    /// Switch from ciphertext modulus `2^64` to `2N` (implicit `N = 1024`).
-    pub fn modswitch(&self) -> Self {
-        let mask = self.mask.iter().map(|a| ((a >> 52) + 1) >> 1).collect();
+    fn map_coefs(&mut self, f: impl Fn(T) -> T) {
+        for a in self.mask.iter_mut() {
+            *a = f(*a);
+        }
+        self.body = f(self.body);
+    }
 
-        let body = ((self.body >> 52) + 1) >> 1;
+    fn zip_coefs(&mut self, rhs: &Self, f: impl Fn(T, T) -> T) {
+        assert_eq!(
+            self.mask.len(),
+            rhs.mask.len(),
+            "ciphertext dimensions differ"
+        );
+        for (a, b) in self.mask.iter_mut().zip(&rhs.mask) {
+            *a = f(*a, *b);
+        }
+        self.body = f(self.body, rhs.body);
+    }
 
-        LweCiphertext { mask, body }
+    /// Switch from ciphertext modulus `2^BITS` to `2^log2_modulus`, rounding each coefficient `x`
+    /// to `round(x * 2^log2_modulus / 2^BITS)`.
+    pub fn modswitch_to(&self, log2_modulus: u32) -> Self {
+        measured!(Modswitch, {
+            let shift = 63 - log2_modulus;
+            // Values rounding up to `2^log2_modulus` wrap around to `0`.
+            let modulus_mask = (1u64 << log2_modulus) - 1;
+            let switch = |a: T| T::from_u64((((a.to_msb() >> shift) + 1) >> 1) & modulus_mask);
+
+            LweCiphertext {
+                mask: self.mask.iter().map(|&a| switch(a)).collect(),
+                body: switch(self.body),
+            }
+        })
+    }
+
+    /// `keyswitch`, erroring instead of panicking if `ksk` is not made of `levels` encryptions
+    /// per coefficient of the mask of `self`.
+    pub fn try_keyswitch(&self, ksk: &KeySwitchingKey<T>) -> Result<Self, TfheError> {
+        if ksk.keys.is_empty() || ksk.levels.0 == 0 || !ksk.keys.len().is_multiple_of(ksk.levels.0)
+        {
+            return Err(TfheError::MalformedKey(format!(
+                "keyswitching key of {} encryptions and {} levels",
+                ksk.keys.len(),
+                ksk.levels.0
+            )));
+        }
+        if self.mask.len() * ksk.levels.0 != ksk.keys.len() {
+            return Err(TfheError::KeyMismatchDimension {
+                expected: ksk.keys.len() / ksk.levels.0,
+                found: self.mask.len(),
+            });
+        }
+        Ok(self.keyswitch(ksk))
     }
 
     /// Switch to the key encrypted by `ksk`.
-    /// This reduces the dimension of the ciphertext.
-    // TODO: generalize for k > 1
-    pub fn keyswitch(&self, ksk: &mut KeySwitchingKey) -> Self {
-        let mut keyswitched = LweCiphertext {
-            body: self.body,
-            ..Default::default()
-        };
+    /// This reduces the dimension of the ciphertext. Trivial ciphertexts are only resized.
+    /// Panics if the dimensions do not match, see `try_keyswitch`.
+    pub fn keyswitch(&self, ksk: &KeySwitchingKey<T>) -> Self {
+        if self.is_trivial() && self.mask.len() * ksk.levels.0 == ksk.keys.len() {
+            return LweCiphertext::trivial_encrypt(self.body, ksk.keys[0].mask.len());
+        }
+
+        measured!(Keyswitch, {
+            assert_eq!(
+                self.mask.len() * ksk.levels.0,
+                ksk.keys.len(),
+                "ciphertext and keyswitching key dimensions differ"
+            );
+
+            let mut keyswitched = LweCiphertext::trivial_encrypt(self.body, ksk.keys[0].mask.len());
 
-        for i in (0..4 * N).step_by(4) {
-            let decomp = decomposition_4_4(self.mask[i / 4]);
-            keyswitched = keyswitched
-                .sub(ksk[i].multiply_constant_assign(decomp[0]))
-                .sub(ksk[i + 1].multiply_constant_assign(decomp[1]))
-                .sub(ksk[i + 2].multiply_constant_assign(decomp[2]))
-                .sub(ksk[i + 3].multiply_constant_assign(decomp[3]));
+            // The key is only read: the encryptions scaled by the digits are subtracted
+            // coefficient by coefficient.
+            let digits: Vec<T> = self
+                .mask
+                .iter()
+                .flat_map(|a| a.decompose(ksk.base_log, ksk.levels))
+                .collect();
+            for (key, digit) in ksk.keys.iter().zip(&digits) {
+                keyswitched.body = keyswitched.body.wrapping_sub(key.body.wrapping_mul(*digit));
+            }
+            sub_scaled_masks(&mut keyswitched.mask, &ksk.keys, &digits);
+
+            keyswitched
+        })
+    }
+
+    /// Trivial encryption of `0` of dimension `dim`.
+    pub fn zero(dim: usize) -> Self {
+        LweCiphertext {
+            mask: vec![T::default(); dim],
+            body: T::default(),
         }
+    }
 
-        keyswitched
+    /// Trivial encryption of `mu` of dimension `dim`, which decrypts to `mu` under any key.
+    pub fn trivial_encrypt(mu: T, dim: usize) -> Self {
+        LweCiphertext {
+            mask: vec![T::default(); dim],
+            body: mu,
+        }
+    }
+
+    /// Adds the plaintext `mu`, which only touches the body.
+    pub fn add_plaintext(mut self, mu: T) -> Self {
+        self.body = self.body.wrapping_add(mu);
+        self
+    }
+
+    /// Subtracts the plaintext `mu`, which only touches the body.
+    pub fn sub_plaintext(mut self, mu: T) -> Self {
+        self.body = self.body.wrapping_sub(mu);
+        self
     }
 }
 
-impl Default for LweCiphertext {
+impl LweCiphertext {
+    /// Encrypts `mu` under `sk` with a mask and a noise derived from `nonce` and the coefficients
+    /// of `sk`, see `utils::nonce_rng`: the same message and nonce always give the same ciphertext,
+    /// while ciphertexts with different nonces are unlinkable.
+    ///
+    /// **A nonce must never be reused for different messages.** Ciphertexts sharing a nonce share
+    /// their mask and noise: their difference is the difference of the messages, which in
+    /// particular leaks whether they are equal.
+    pub fn encrypt_deterministic(mu: u64, sk: &LweSecretKey, nonce: [u8; NONCE_LEN]) -> Self {
+        let mut rng = nonce_rng(b"ttfhe/lwe-encrypt", &sk.coefs, &nonce);
+        Self::encrypt_with(mu, sk, &mut rng)
+    }
+
+    /// Encrypts every plaintext of `mus`, in parallel with the `parallel` feature.
+    pub fn encrypt_many(mus: &[u64], sk: &LweSecretKey) -> Vec<LweCiphertext> {
+        par_map(mus, |&mu| Self::encrypt(mu, sk))
+    }
+
+    /// Decrypts every ciphertext of `cts`, in parallel with the `parallel` feature.
+    pub fn decrypt_many(cts: &[LweCiphertext], sk: &LweSecretKey) -> Vec<u64> {
+        par_map(cts, |ct| ct.clone().decrypt(sk))
+    }
+
+    /// Decrypts and decodes a ciphertext of `utils::encode_checked`, erroring on a dimension
+    /// mismatch or if the noise has overflowed the check bits, see `utils::decode_checked`.
+    pub fn decrypt_checked(self, sk: &LweSecretKey) -> Result<u8, TfheError> {
+        decode_checked(self.try_decrypt(sk)?)
+    }
+
+    /// Encrypts `mu` under `sk` with a mask drawn from the stream of `seed`, see
+    /// `SeededLweCiphertext`.
+    ///
+    /// **The seed is public and must never be reused under the same key**, for the same reason
+    /// as the nonces of `encrypt_deterministic`.
+    pub fn encrypt_seeded(mu: u64, sk: &LweSecretKey, seed: [u8; SEED_LEN]) -> SeededLweCiphertext {
+        Self::encrypt_seeded_with(mu, sk, seed, &mut thread_rng())
+    }
+
+    /// Encrypts `mu` under `sk` with a mask drawn from the stream of `seed` and a noise drawn from
+    /// `rng`.
+    pub fn encrypt_seeded_with<R: Rng + CryptoRng>(
+        mu: u64,
+        sk: &LweSecretKey,
+        seed: [u8; SEED_LEN],
+        rng: &mut R,
+    ) -> SeededLweCiphertext {
+        let mask = draw_mask(sk.coefs.len(), &mut mask_rng(&seed));
+        SeededLweCiphertext {
+            seed,
+            dim: mask.len(),
+            body: encrypt_mask(mu, &mask, sk, rng),
+        }
+    }
+
+    /// Signed error of `self` with respect to the encoded plaintext `expected_mu`, see
+    /// `noise::NoiseModel` for its predicted distribution.
+    pub fn noise(&self, sk: &LweSecretKey, expected_mu: u64) -> i64 {
+        self.clone().decrypt(sk).wrapping_sub(expected_mu) as i64
+    }
+
+    /// Decrypts the output of `modswitch`, modulo `2N` for the polynomial size of the parameters
+    /// of `sk`, see `decrypt_modswitched_to`.
+    pub fn decrypt_modswitched(self, sk: &LweSecretKey) -> u64 {
+        let log2_modulus = (2 * sk.params.poly_size.0).ilog2();
+        self.decrypt_modswitched_to(sk, log2_modulus)
+    }
+
+    /// Decrypts the output of `modswitch_to(log2_modulus)`, modulo `2^log2_modulus`. The
+    /// coefficients of the key are multiplied instead of tested, so that the running time does not
+    /// depend on them.
+    pub fn decrypt_modswitched_to(self, sk: &LweSecretKey, log2_modulus: u32) -> u64 {
+        let modulus_mask = (1u64 << log2_modulus) - 1;
+        let dot_prod = self
+            .mask
+            .iter()
+            .zip(&sk.coefs)
+            .fold(0u64, |acc, (a, s)| acc.wrapping_add(a.wrapping_mul(*s)));
+
+        self.body.wrapping_sub(dot_prod) & modulus_mask // mu_star
+    }
+
+    /// Multiplies `self` by `constant`, which may be negative. See also the `Mul<i64>` operator.
+    pub fn mul_constant(mut self, constant: i64) -> Self {
+        self *= constant;
+        self
+    }
+
+    /// Switch from ciphertext modulus `2^64` to `2N`, for the polynomial size of
+    /// `Parameters::DEFAULT`.
+    pub fn modswitch(&self) -> Self {
+        self.modswitch_to((2 * Parameters::DEFAULT.poly_size.0).ilog2())
+    }
+
+    /// Debug output of `self` followed by its message, its error and the ratio of the error to
+    /// the half width of a decoding box, see `stats::decrypt_with_noise`.
+    #[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+    pub fn fmt_with_key(&self, sk: &LweSecretKey) -> String {
+        let (msg, error, fraction) = crate::stats::decrypt_with_noise(self, sk, 4);
+        format!("{self:?} message: {msg}, error: {error}, box: {fraction:.3}")
+    }
+
+    /// Switches `self` to the modulus `2^log2_modulus` and packs its coefficients, which only
+    /// takes `log2_modulus` bits each instead of 64.
+    ///
+    /// The rounding of the `n` mask coefficients adds to the noise an error of standard
+    /// deviation about `2^(64 - log2_modulus) * sqrt(n / 24)` with respect to `q = 2^64`.
+    /// The total must stay well below `q / 32`, the half width of a decoding box: for dimensions
+    /// up to 1024, moduli of `2^13` and above are safe.
+    pub fn compress_for_transport(&self, log2_modulus: u32) -> CompressedLweCiphertext {
+        assert!(
+            (5..64).contains(&log2_modulus),
+            "the modulus 2^{log2_modulus} cannot hold a message and its padding bit"
+        );
+
+        let switched = self.modswitch_to(log2_modulus);
+        let coefs: Vec<u64> = switched.mask.into_iter().chain([switched.body]).collect();
+
+        CompressedLweCiphertext {
+            log2_modulus,
+            dim: self.mask.len(),
+            bytes: pack_bits(&coefs, log2_modulus),
+        }
+    }
+}
+
+impl_ciphertext_ops!(LweCiphertext);
+
+impl<T: Torus> Default for LweCiphertext<T> {
     fn default() -> Self {
+        LweCiphertext::zero(LWE_DIM)
+    }
+}
+
+/// Prints the dimension and a digest of the coefficients instead of the coefficients.
+impl<T: Torus> fmt::Debug for LweCiphertext<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefs: Vec<u64> = self
+            .mask
+            .iter()
+            .chain([&self.body])
+            .map(|c| c.to_u64())
+            .collect();
+        f.debug_struct("LweCiphertext")
+            .field("dim", &self.mask.len())
+            .field("digest", &format_args!("{}", short_digest(&coefs)))
+            .finish()
+    }
+}
+
+impl<T: Torus> fmt::Debug for KeySwitchingKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefs: Vec<u64> = self
+            .keys
+            .iter()
+            .flat_map(|ct| ct.mask.iter().chain([&ct.body]))
+            .map(|c| c.to_u64())
+            .collect();
+        f.debug_struct("KeySwitchingKey")
+            .field("base_log", &self.base_log)
+            .field("levels", &self.levels)
+            .field("entries", &self.keys.len())
+            .field("dim", &self.keys.first().map_or(0, |ct| ct.mask.len()))
+            .field("digest", &format_args!("{}", short_digest(&coefs)))
+            .finish()
+    }
+}
+
+impl SeededLweCiphertext {
+    /// Regenerates the mask from the seed.
+    pub fn expand(&self) -> LweCiphertext {
         LweCiphertext {
-            mask: vec![0u64; LWE_DIM],
-            body: 0u64,
+            mask: draw_mask(self.dim, &mut mask_rng(&self.seed)),
+            body: self.body,
+        }
+    }
+}
+
+impl SeededKeySwitchingKey {
+    /// Regenerates the masks from the seed.
+    pub fn expand(&self) -> KeySwitchingKey {
+        let mut rng = mask_rng(&self.seed);
+        let keys = self
+            .bodies
+            .iter()
+            .map(|&body| LweCiphertext {
+                mask: draw_mask(self.dim, &mut rng),
+                body,
+            })
+            .collect();
+
+        KeySwitchingKey {
+            base_log: self.base_log,
+            levels: self.levels,
+            keys,
+        }
+    }
+}
+
+/// Subtracts from `acc` the masks of `keys` multiplied by `digits`.
+#[cfg(not(feature = "parallel"))]
+fn sub_scaled_masks<T: Torus>(acc: &mut [T], keys: &[LweCiphertext<T>], digits: &[T]) {
+    for (key, digit) in keys.iter().zip(digits) {
+        for (a, b) in acc.iter_mut().zip(&key.mask) {
+            *a = a.wrapping_sub(b.wrapping_mul(*digit));
+        }
+    }
+}
+
+/// Subtracts from `acc` the masks of `keys` multiplied by `digits`, every thread handling
+/// `KEYSWITCH_CHUNK` coefficients of `acc`. The wrapping sums are the same in any order, so the
+/// result is bit-identical to the serial one.
+#[cfg(feature = "parallel")]
+fn sub_scaled_masks<T: Torus>(acc: &mut [T], keys: &[LweCiphertext<T>], digits: &[T]) {
+    use rayon::prelude::*;
+    const KEYSWITCH_CHUNK: usize = 64;
+
+    acc.par_chunks_mut(KEYSWITCH_CHUNK)
+        .enumerate()
+        .for_each(|(c, chunk)| {
+            let offset = c * KEYSWITCH_CHUNK;
+            for (key, digit) in keys.iter().zip(digits) {
+                for (a, b) in chunk.iter_mut().zip(&key.mask[offset..]) {
+                    *a = a.wrapping_sub(b.wrapping_mul(*digit));
+                }
+            }
+        });
+}
+
+/// Draws a uniform mask of dimension `dim`.
+fn draw_mask<T: Torus, R: Rng>(dim: usize, rng: &mut R) -> Vec<T> {
+    (0..dim).map(|_| T::from_u64(rng.gen::<u64>())).collect()
+}
+
+/// Body of the encryption of `mu` under `sk` with `mask`, the noise being drawn from `rng`.
+fn encrypt_mask<T: Torus, R: Rng + CryptoRng>(
+    mu: T,
+    mask: &[T],
+    sk: &LweSecretKey<T>,
+    rng: &mut R,
+) -> T {
+    let e = sample_gaussian(sk.std_dev(), rng);
+    mask.iter()
+        .zip(&sk.coefs)
+        .fold(mu.wrapping_add(T::from_i64(e)), |body, (a, s)| {
+            body.wrapping_add(a.wrapping_mul(*s))
+        })
+}
+
+impl CompressedLweCiphertext {
+    /// Decrypts and decodes `self`, computing the phase modulo `2^log2_modulus`.
+    /// `self` may come from an untrusted source: errors if its modulus is not one of
+    /// `LweCiphertext::compress_for_transport`, if its bytes do not hold `dim + 1` coefficients, or
+    /// if it is not of the dimension of `sk`.
+    pub fn decrypt_compressed(&self, sk: &LweSecretKey) -> Result<u8, TfheError> {
+        if !(5..64).contains(&self.log2_modulus) {
+            return Err(TfheError::InvalidEncoding(format!(
+                "compressed ciphertext modulo 2^{}",
+                self.log2_modulus
+            )));
         }
+        if self.dim != sk.coefs.len() {
+            return Err(TfheError::KeyMismatchDimension {
+                expected: sk.coefs.len(),
+                found: self.dim,
+            });
+        }
+        let expected_len = ((self.dim + 1) * self.log2_modulus as usize).div_ceil(8);
+        if self.bytes.len() != expected_len {
+            return Err(TfheError::InvalidEncoding(format!(
+                "compressed ciphertext of {} bytes instead of {expected_len}",
+                self.bytes.len()
+            )));
+        }
+        let coefs = unpack_bits(&self.bytes, self.log2_modulus, self.dim + 1);
+
+        let modulus_mask = (1u64 << self.log2_modulus) - 1;
+        let dot_prod = coefs[..self.dim]
+            .iter()
+            .zip(&sk.coefs)
+            .fold(0u64, |acc, (&a, &s)| acc.wrapping_add(a.wrapping_mul(s)));
+        let phase = coefs[self.dim].wrapping_sub(dot_prod) & modulus_mask;
+
+        Ok(decode(phase << (64 - self.log2_modulus)))
     }
 }
 
+/// Concatenates the `bits` least significant bits of every value, least significant bit first.
+fn pack_bits(values: &[u64], bits: u32) -> Vec<u8> {
+    let mut bytes = vec![0u8; (values.len() * bits as usize).div_ceil(8)];
+    for (i, &value) in values.iter().enumerate() {
+        for j in 0..bits as usize {
+            let pos = i * bits as usize + j;
+            bytes[pos / 8] |= (((value >> j) & 1) as u8) << (pos % 8);
+        }
+    }
+    bytes
+}
+
+/// Inverse of `pack_bits`. Panics if `bytes` holds less than `count` values.
+fn unpack_bits(bytes: &[u8], bits: u32, count: usize) -> Vec<u64> {
+    assert!(
+        bytes.len() * 8 >= count * bits as usize,
+        "{} bytes cannot hold {count} values of {bits} bits",
+        bytes.len()
+    );
+    (0..count)
+        .map(|i| {
+            (0..bits as usize).fold(0u64, |acc, j| {
+                let pos = i * bits as usize + j;
+                acc | (((bytes[pos / 8] >> (pos % 8)) & 1) as u64) << j
+            })
+        })
+        .collect()
+}
+
 /// Approximate decomposition with lg(B) = 4 and ell = 4.
 /// Takes a polynomial coefficient in Z_{2^64} and decomposes its 16 MSBs in 4 integers in `[-8, 7] as u64`.
 pub fn decomposition_4_4(val: u64) -> [u64; 4] {
@@ -140,7 +675,7 @@ pub fn decomposition_4_4(val: u64) -> [u64; 4] {
     for i in 0..4 {
         let mut res = ((rounded_val >> (4 * i)) & 0x0F) + carry;
 
-        let carry_bit = res & 8;
+        let carry_bit = if res >= 8 { 8 } else { 0 };
 
         res = res.wrapping_sub(carry_bit << 1);
         ret[i] = res;
@@ -152,38 +687,154 @@ pub fn decomposition_4_4(val: u64) -> [u64; 4] {
 }
 
 pub fn lwe_keygen() -> LweSecretKey {
-    let mut sk = Vec::<u64>::with_capacity(LWE_DIM);
-    for _ in 0..LWE_DIM {
-        sk.push(thread_rng().gen_range(0..=1));
+    lwe_keygen_with(&mut thread_rng())
+}
+
+/// Generates a LWE secret key, drawing its bits from `rng`.
+pub fn lwe_keygen_with<R: Rng + CryptoRng>(rng: &mut R) -> LweSecretKey {
+    lwe_keygen_for(&Parameters::DEFAULT, rng)
+}
+
+/// Generates a LWE secret key for `params`, drawing its bits from `rng`.
+/// Panics if `params` are invalid.
+pub fn lwe_keygen_for<R: Rng + CryptoRng>(params: &Parameters, rng: &mut R) -> LweSecretKey {
+    params.validate().expect("invalid parameters");
+
+    let mut coefs = Vec::<u64>::with_capacity(params.lwe_dim.0);
+    for _ in 0..params.lwe_dim.0 {
+        coefs.push(rng.gen_range(0..=1));
+    }
+
+    LweSecretKey {
+        coefs,
+        params: *params,
+        distribution: KeyDistribution::Binary,
+        noise: NoiseLevel::Lwe,
+    }
+}
+
+/// Generates a LWE secret key for `params` with coefficients drawn uniformly in `{-1, 0, 1}` from
+/// `rng`. Panics if `params` are invalid.
+pub fn lwe_keygen_ternary_for<R: Rng + CryptoRng>(
+    params: &Parameters,
+    rng: &mut R,
+) -> LweSecretKey {
+    params.validate().expect("invalid parameters");
+
+    let coefs = (0..params.lwe_dim.0)
+        .map(|_| rng.gen_range(-1i64..=1) as u64)
+        .collect();
+
+    LweSecretKey {
+        coefs,
+        params: *params,
+        distribution: KeyDistribution::Ternary,
+        noise: NoiseLevel::Lwe,
     }
+}
 
-    sk
+/// Encrypts `sk1` under `sk2`, in parallel with the `parallel` feature.
+pub fn compute_ksk<T: Torus>(sk1: &LweSecretKey<T>, sk2: &LweSecretKey<T>) -> KeySwitchingKey<T> {
+    assert!(sk1.params == sk2.params, "keys of different parameters");
+
+    KeySwitchingKey {
+        base_log: sk2.params.ks_base_log,
+        levels: sk2.params.ks_levels,
+        keys: par_map(&ksk_plaintexts(sk1), |&mu| LweCiphertext::encrypt(mu, sk2)),
+    }
 }
 
-/// Encrypts `sk1` under `sk2`.
-// TODO: generalize for k > 1
-pub fn compute_ksk(sk1: &LweSecretKey, sk2: &LweSecretKey) -> KeySwitchingKey {
-    let mut ksk = Vec::<LweCiphertext>::with_capacity(4 * N);
+/// Encrypts `sk1` under `sk2`, drawing the randomness from `rng`.
+/// The decomposition is the one of the parameters of the keys, which must agree.
+pub fn compute_ksk_with<T: Torus, R: Rng + CryptoRng>(
+    sk1: &LweSecretKey<T>,
+    sk2: &LweSecretKey<T>,
+    rng: &mut R,
+) -> KeySwitchingKey<T> {
+    assert!(sk1.params == sk2.params, "keys of different parameters");
 
-    for bit in sk1.iter().take(N) {
-        // 4 layers in the decomposition for the KSK
-        for j in 0..4 {
-            let mu = bit << (48 + (4 * j)); // lg(B) = 4
-            ksk.push(LweCiphertext::encrypt(mu, sk2));
+    KeySwitchingKey {
+        base_log: sk2.params.ks_base_log,
+        levels: sk2.params.ks_levels,
+        keys: ksk_plaintexts(sk1)
+            .into_iter()
+            .map(|mu| LweCiphertext::encrypt_with(mu, sk2, rng))
+            .collect(),
+    }
+}
+
+/// Plaintexts of a keyswitching key from `sk1`: every bit scaled by q/B^l, ..., q/B.
+fn ksk_plaintexts<T: Torus>(sk1: &LweSecretKey<T>) -> Vec<T> {
+    let (base_log, levels) = (sk1.params.ks_base_log.0, sk1.params.ks_levels.0);
+    sk1.coefs
+        .iter()
+        .flat_map(|&bit| {
+            (0..levels)
+                .map(move |j| bit.wrapping_mul(T::from_msb(1 << (64 - base_log * (levels - j)))))
+        })
+        .collect()
+}
+
+/// Encrypts `sk1` under `sk2` like `compute_ksk`, the masks being drawn in order from the stream of
+/// `seed`. The seed must never be reused under the same key.
+pub fn compute_seeded_ksk(
+    sk1: &LweSecretKey,
+    sk2: &LweSecretKey,
+    seed: [u8; SEED_LEN],
+) -> SeededKeySwitchingKey {
+    compute_seeded_ksk_with(sk1, sk2, seed, &mut thread_rng())
+}
+
+/// Encrypts `sk1` under `sk2` with masks drawn from the stream of `seed` and noises drawn from
+/// `rng`.
+pub fn compute_seeded_ksk_with<R: Rng + CryptoRng>(
+    sk1: &LweSecretKey,
+    sk2: &LweSecretKey,
+    seed: [u8; SEED_LEN],
+    rng: &mut R,
+) -> SeededKeySwitchingKey {
+    assert!(sk1.params == sk2.params, "keys of different parameters");
+    let (base_log, levels) = (sk2.params.ks_base_log.0, sk2.params.ks_levels.0);
+
+    let mut masks = mask_rng(&seed);
+    let mut bodies = Vec::with_capacity(levels * sk1.coefs.len());
+    for bit in &sk1.coefs {
+        for j in 0..levels {
+            let mu = bit << (64 - base_log * (levels - j));
+            let mask = draw_mask(sk2.coefs.len(), &mut masks);
+            bodies.push(encrypt_mask(mu, &mask, sk2, rng));
         }
     }
-    ksk
+
+    SeededKeySwitchingKey {
+        seed,
+        base_log: sk2.params.ks_base_log,
+        levels: sk2.params.ks_levels,
+        dim: sk2.coefs.len(),
+        bodies,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lwe::{lwe_keygen, LweCiphertext};
-    use crate::utils::{decode, encode};
-    use rand::{thread_rng, Rng};
+    use crate::error::TfheError;
+    use crate::glwe::keygen_for;
+    use crate::lwe::{
+        compute_ksk, compute_ksk_with, compute_seeded_ksk, lwe_keygen_for, lwe_keygen_ternary_for,
+        KeySwitchingKey, LweCiphertext, LweSecretKey,
+    };
+    use crate::params::presets::TEST;
+    use crate::params::{DecompositionBaseLog, DecompositionLevelCount, Parameters};
+    use crate::utils::{
+        decode, decode_modswitched, decompose_rounded, encode, encode_checked, nonce_rng,
+    };
+    use crate::utils::{NONCE_LEN, SEED_LEN};
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, Rng, SeedableRng};
 
     #[test]
     fn test_keygen_enc_dec() {
-        let sk = lwe_keygen();
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
         for _ in 0..100 {
             let msg = thread_rng().gen_range(0..16);
             let ct = LweCiphertext::encrypt(encode(msg), &sk);
@@ -194,7 +845,7 @@ mod tests {
 
     #[test]
     fn test_add() {
-        let sk = lwe_keygen();
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
         for _ in 0..100 {
             let msg1 = thread_rng().gen_range(0..16);
             let msg2 = thread_rng().gen_range(0..16);
@@ -206,9 +857,249 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_trivial() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let other_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode(3), &sk);
+        let trivial = LweCiphertext::trivial_encrypt(encode(9), TEST.lwe_dim.0);
+        assert!(trivial.is_trivial());
+        assert!(!ct.is_trivial());
+
+        let res = ct.clone().add(trivial.clone());
+        assert_eq!(res.mask, ct.mask);
+        assert_eq!(decode(res.decrypt(&sk)), 12);
+        assert!(trivial.clone().add(trivial.clone()).is_trivial());
+
+        let ksk = compute_ksk(&sk, &other_sk);
+        let keyswitched = trivial.keyswitch(&ksk);
+        assert!(keyswitched.is_trivial());
+        assert_eq!(keyswitched.body, trivial.body);
+        assert_eq!(decode(keyswitched.decrypt(&other_sk)), 9);
+    }
+
+    #[test]
+    fn test_plaintext_arithmetic() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode(3), &sk);
+
+        assert_eq!(decode(ct.clone().add_plaintext(encode(4)).decrypt(&sk)), 7);
+        assert_eq!(decode(ct.clone().sub_plaintext(encode(5)).decrypt(&sk)), 14);
+        let trivial = LweCiphertext::trivial_encrypt(encode(5), TEST.lwe_dim.0);
+        assert_eq!(decode(ct.clone().sub(&trivial).decrypt(&sk)), 14);
+        assert_eq!(decode(trivial.clone().sub(&ct).decrypt(&sk)), 2);
+
+        let mut one = LweCiphertext::trivial_encrypt(encode(1), TEST.lwe_dim.0);
+        one.multiply_constant_assign(6);
+        assert!(one.is_trivial());
+        assert_eq!(one.body, encode(6));
+        assert_eq!(one.add_plaintext(encode(2)).body, encode(8));
+    }
+
+    #[test]
+    fn test_operators() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let cts: Vec<LweCiphertext> = (0..16)
+            .map(|m| LweCiphertext::encrypt(encode(m), &sk))
+            .collect();
+
+        for m1 in 0..16u8 {
+            let ct1 = &cts[m1 as usize];
+            assert_eq!(decode((-ct1.clone()).decrypt(&sk)), (16 - m1) % 16);
+            assert_eq!(
+                decode(ct1.clone().mul_constant(-3).decrypt(&sk)),
+                (16 * 3 - 3 * m1) % 16
+            );
+
+            for m2 in 0..16u8 {
+                let ct2 = &cts[m2 as usize];
+                let five = LweCiphertext::trivial_encrypt(encode(5), TEST.lwe_dim.0);
+                let affine = ct1.clone() * 3 - ct2.clone() * 2 + five;
+                let expected = (3 * m1 as i32 - 2 * m2 as i32 + 5).rem_euclid(16) as u8;
+                assert_eq!(
+                    decode(affine.decrypt(&sk)),
+                    expected,
+                    "3 * {m1} - 2 * {m2} + 5"
+                );
+
+                let mut acc = ct1.clone();
+                acc -= ct2;
+                acc += ct1;
+                acc *= -1;
+                let expected = (m2 as i32 - 2 * m1 as i32).rem_euclid(16) as u8;
+                assert_eq!(decode(acc.decrypt(&sk)), expected);
+            }
+        }
+    }
+
+    /// Keyswitch decomposing the mask coefficients truncated, instead of rounded, to the
+    /// precision of `ksk`.
+    fn keyswitch_truncated(ct: &LweCiphertext, ksk: &KeySwitchingKey) -> LweCiphertext {
+        let shift = 64 - ksk.base_log.0 * ksk.levels.0;
+        let mut keyswitched = LweCiphertext::zero(ksk.keys[0].mask.len());
+        keyswitched.body = ct.body;
+        for (i, a) in ct.mask.iter().enumerate() {
+            let truncated = (a >> shift) << shift;
+            for (j, digit) in decompose_rounded(truncated, ksk.base_log, ksk.levels)
+                .into_iter()
+                .enumerate()
+            {
+                keyswitched =
+                    keyswitched.sub(&ksk.keys[i * ksk.levels.0 + j].multiply_constant(digit));
+            }
+        }
+        keyswitched
+    }
+
+    fn mean_square_error(cts: &[LweCiphertext], msg: u8, sk: &LweSecretKey) -> f64 {
+        cts.iter()
+            .map(|ct| {
+                let error = ct.clone().decrypt(sk).wrapping_sub(encode(msg)) as i64 as f64;
+                error * error
+            })
+            .sum::<f64>()
+            / cts.len() as f64
+    }
+
+    #[test]
+    fn test_keyswitch_rounding() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode();
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+
+        let cts: Vec<LweCiphertext> = (0..200)
+            .map(|_| LweCiphertext::encrypt(encode(5), &glwe_sk))
+            .collect();
+        let rounded: Vec<LweCiphertext> = cts.iter().map(|ct| ct.keyswitch(&ksk)).collect();
+        let truncated: Vec<LweCiphertext> =
+            cts.iter().map(|ct| keyswitch_truncated(ct, &ksk)).collect();
+
+        // Truncating drops on average half of the last level of every mask coefficient times
+        // its key bit: the error is biased, and much larger than the rounding error.
+        let rounded_error = mean_square_error(&rounded, 5, &lwe_sk);
+        let truncated_error = mean_square_error(&truncated, 5, &lwe_sk);
+        assert!(
+            truncated_error > 10.0 * rounded_error,
+            "rounded 2^{:.1}, truncated 2^{:.1}",
+            rounded_error.log2() / 2.0,
+            truncated_error.log2() / 2.0
+        );
+        assert!(rounded
+            .iter()
+            .all(|ct| decode(ct.clone().decrypt(&lwe_sk)) == 5));
+    }
+
+    #[test]
+    fn test_keyswitch_leaves_key_unchanged() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode();
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+        let bytes = bincode::serialize(&ksk).unwrap();
+
+        for msg in [5, 12] {
+            let ct = LweCiphertext::encrypt(encode(msg), &glwe_sk);
+            assert_eq!(decode(ct.keyswitch(&ksk).decrypt(&lwe_sk)), msg);
+        }
+        assert_eq!(bincode::serialize(&ksk).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_try_keyswitch() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode();
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+
+        let ct = LweCiphertext::encrypt(encode(9), &glwe_sk);
+        let keyswitched = ct.try_keyswitch(&ksk).unwrap();
+        assert_eq!(decode(keyswitched.decrypt(&lwe_sk)), 9);
+
+        // Under the output key instead of the input one.
+        let ct = LweCiphertext::encrypt(encode(9), &lwe_sk);
+        assert_eq!(
+            ct.try_keyswitch(&ksk).err(),
+            Some(TfheError::KeyMismatchDimension {
+                expected: TEST.poly_size.0,
+                found: TEST.lwe_dim.0
+            })
+        );
+
+        let mut truncated = ksk.clone();
+        truncated.keys.pop();
+        let err = LweCiphertext::encrypt(encode(9), &glwe_sk)
+            .try_keyswitch(&truncated)
+            .unwrap_err();
+        assert!(matches!(err, TfheError::MalformedKey(_)), "{err}");
+        let mut empty = ksk;
+        empty.keys.clear();
+        assert!(LweCiphertext::zero(0).try_keyswitch(&empty).is_err());
+    }
+
+    #[test]
+    fn test_keyswitch_matches_serial_reference() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode();
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+        let ct = LweCiphertext::encrypt(encode(11), &glwe_sk);
+
+        // One scaled encryption at a time, whatever the `parallel` feature.
+        let mut expected = LweCiphertext::trivial_encrypt(ct.body, TEST.lwe_dim.0);
+        for (i, a) in ct.mask.iter().enumerate() {
+            let decomp = decompose_rounded(*a, ksk.base_log, ksk.levels);
+            for (j, digit) in decomp.into_iter().enumerate() {
+                let key = &ksk.keys[i * ksk.levels.0 + j];
+                expected = expected.sub(&key.multiply_constant(digit));
+            }
+        }
+
+        let keyswitched = ct.keyswitch(&ksk);
+        assert_eq!(keyswitched.mask, expected.mask);
+        assert_eq!(keyswitched.body, expected.body);
+        assert_eq!(decode(keyswitched.decrypt(&lwe_sk)), 11);
+    }
+
+    #[test]
+    fn test_encrypt_many() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let msgs: Vec<u8> = (0..100).map(|_| thread_rng().gen_range(0..16)).collect();
+        let mus: Vec<u64> = msgs.iter().map(|&m| encode(m)).collect();
+
+        let cts = LweCiphertext::encrypt_many(&mus, &sk);
+        assert_eq!(cts.len(), msgs.len());
+        let decrypted: Vec<u8> = LweCiphertext::decrypt_many(&cts, &sk)
+            .into_iter()
+            .map(decode)
+            .collect();
+        assert_eq!(decrypted, msgs);
+    }
+
+    #[test]
+    fn test_keyswitch_levels() {
+        for (base_log, levels) in [(8, 2), (5, 3), (4, 4)] {
+            let params = Parameters {
+                ks_base_log: DecompositionBaseLog(base_log),
+                ks_levels: DecompositionLevelCount(levels),
+                ..TEST
+            };
+            let glwe_sk = keygen_for(&params, &mut thread_rng()).recode();
+            let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+            let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+            assert_eq!(ksk.keys.len(), levels * glwe_sk.coefs.len());
+
+            for msg in 0..16 {
+                let ct = LweCiphertext::encrypt(encode(msg), &glwe_sk);
+                let keyswitched = ct.keyswitch(&ksk);
+                assert_eq!(
+                    decode(keyswitched.decrypt(&lwe_sk)),
+                    msg,
+                    "{levels} levels of {base_log} bits"
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_sub() {
-        let sk = lwe_keygen();
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
         for _ in 0..100 {
             let msg1 = thread_rng().gen_range(0..16);
             let msg2 = thread_rng().gen_range(0..16);
@@ -219,4 +1110,371 @@ mod tests {
             assert_eq!(pt, (msg1.wrapping_sub(msg2)) % 16);
         }
     }
+
+    #[test]
+    fn test_modswitch_wraps() {
+        let ct = LweCiphertext {
+            mask: vec![u64::MAX, 1 << 53, 3 << 52],
+            body: u64::MAX - (1 << 50),
+        };
+        let switched = ct.modswitch_to(11);
+        assert_eq!(switched.mask, [0, 1, 2]);
+        assert_eq!(switched.body, 0);
+    }
+
+    #[test]
+    fn test_encrypt_deterministic() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let nonce: [u8; NONCE_LEN] = thread_rng().gen();
+
+        for msg in 0..16 {
+            let ct = LweCiphertext::encrypt_deterministic(encode(msg), &sk, nonce);
+            let again = LweCiphertext::encrypt_deterministic(encode(msg), &sk, nonce);
+            assert_eq!(
+                bincode::serialize(&ct).unwrap(),
+                bincode::serialize(&again).unwrap()
+            );
+            assert_eq!(decode(ct.decrypt(&sk)), msg);
+        }
+
+        let ct = LweCiphertext::encrypt_deterministic(encode(1), &sk, nonce);
+        let mut other_nonce = nonce;
+        other_nonce[NONCE_LEN - 1] ^= 1;
+        let other = LweCiphertext::encrypt_deterministic(encode(1), &sk, other_nonce);
+        assert_ne!(ct.mask, other.mask);
+
+        let other_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let other = LweCiphertext::encrypt_deterministic(encode(1), &other_sk, nonce);
+        assert_ne!(ct.mask, other.mask);
+
+        // The public fingerprint of the key and the nonce do not reproduce the mask, hence the
+        // noise, of the ciphertext.
+        let fingerprint = sk.fingerprint();
+        let public: Vec<u64> = fingerprint
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        for key in [&public[..], &[]] {
+            let mut rng = nonce_rng(b"ttfhe/lwe-encrypt", key, &nonce);
+            let guess = LweCiphertext::encrypt_with(encode(1), &sk, &mut rng);
+            assert_ne!(ct.mask, guess.mask);
+        }
+        let mut rng = nonce_rng(b"ttfhe/lwe-encrypt", &sk.coefs, &nonce);
+        let keyed = LweCiphertext::encrypt_with(encode(1), &sk, &mut rng);
+        assert_eq!((ct.mask, ct.body), (keyed.mask, keyed.body));
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let encrypt = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let sk = lwe_keygen_for(&TEST, &mut rng);
+            let ct = LweCiphertext::encrypt_with(encode(3), &sk, &mut rng);
+            let ksk = compute_ksk_with(&sk, &sk, &mut rng);
+            (sk, ct, ksk)
+        };
+
+        let (sk, ct, ksk) = encrypt(42);
+        let (sk_again, ct_again, ksk_again) = encrypt(42);
+        assert_eq!(sk.coefs, sk_again.coefs);
+        assert_eq!(
+            bincode::serialize(&ct).unwrap(),
+            bincode::serialize(&ct_again).unwrap()
+        );
+        assert_eq!(
+            bincode::serialize(&ksk).unwrap(),
+            bincode::serialize(&ksk_again).unwrap()
+        );
+        assert_eq!(decode(ct.decrypt(&sk)), 3);
+
+        let (_, other, _) = encrypt(43);
+        assert_ne!(ct_again.mask, other.mask);
+    }
+
+    #[test]
+    fn test_decrypt_modswitched() {
+        let two_n = 2 * TEST.poly_size.0 as u64;
+        for _ in 0..10 {
+            let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+            for msg in 0..16 {
+                let ct = LweCiphertext::encrypt(encode(msg), &sk).modswitch_to(two_n.ilog2());
+
+                // Branching on the key bits, as decryption used to.
+                let mut dot_prod = 0;
+                for (a, s) in ct.mask.iter().zip(&sk.coefs) {
+                    if *s == 1 {
+                        dot_prod = (dot_prod + a) % two_n;
+                    }
+                }
+                let expected = ct.body.wrapping_sub(dot_prod) % two_n;
+
+                let phase = ct.decrypt_modswitched(&sk);
+                assert_eq!(phase, expected);
+                assert_eq!(((phase * 16 + two_n / 2) / two_n) % 16, msg as u64);
+            }
+        }
+
+        let sk = lwe_keygen_ternary_for(&TEST, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode(5), &sk).modswitch_to(two_n.ilog2());
+        let phase = ct.decrypt_modswitched(&sk);
+        assert_eq!(((phase * 16 + two_n / 2) / two_n) % 16, 5);
+    }
+
+    #[test]
+    fn test_modswitch_round_trip() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        for log2_modulus in [10, 11, 12] {
+            for msg in 0..16 {
+                let ct = LweCiphertext::encrypt(encode(msg), &sk).modswitch_to(log2_modulus);
+                assert!(ct.mask.iter().all(|&a| a >> log2_modulus == 0));
+                let phase = ct.decrypt_modswitched_to(&sk, log2_modulus);
+                assert_eq!(
+                    decode_modswitched(phase, log2_modulus),
+                    msg,
+                    "2^{log2_modulus}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_checked() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        for msg in 0..8 {
+            let ct = LweCiphertext::encrypt(encode_checked(msg), &sk);
+            assert_eq!(ct.clone().decrypt_checked(&sk), Ok(msg));
+            let sum = ct + LweCiphertext::encrypt(encode_checked(5), &sk);
+            assert_eq!(sum.decrypt_checked(&sk), Ok((msg + 5) % 8));
+        }
+
+        // Scaling by 2^20 clears the message and blows the noise up to about 2^60: the phases are
+        // nearly uniform, and each passes the check with probability 1/16.
+        let failures = (0..100)
+            .filter(|_| {
+                let mut ct = LweCiphertext::encrypt(encode_checked(3), &sk);
+                ct.multiply_constant_assign(1 << 20);
+                let blown_up = (0..10).fold(ct, |acc, _| {
+                    acc + LweCiphertext::encrypt(encode_checked(1), &sk)
+                });
+                matches!(
+                    blown_up.decrypt_checked(&sk),
+                    Err(TfheError::NoiseOverflow { .. })
+                )
+            })
+            .count();
+        assert!(failures >= 75, "only {failures} failures detected");
+
+        let other_sk = lwe_keygen_for(&Parameters::DEFAULT, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode_checked(1), &sk);
+        assert!(matches!(
+            ct.decrypt_checked(&other_sk),
+            Err(TfheError::KeyMismatchDimension { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encrypt_seeded() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+
+        for msg in 0..16 {
+            let seed: [u8; SEED_LEN] = thread_rng().gen();
+            let seeded = LweCiphertext::encrypt_seeded(encode(msg), &sk, seed);
+            let ct = seeded.expand();
+            assert_eq!(
+                bincode::serialize(&ct).unwrap(),
+                bincode::serialize(&seeded.expand()).unwrap()
+            );
+            assert_eq!(decode(ct.decrypt(&sk)), msg);
+        }
+
+        let seeded = LweCiphertext::encrypt_seeded(encode(1), &sk, [1; SEED_LEN]);
+        let expanded_len = bincode::serialize(&seeded.expand()).unwrap().len();
+        assert!(20 * bincode::serialize(&seeded).unwrap().len() < expanded_len);
+    }
+
+    #[test]
+    fn test_seeded_ksk() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode();
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let seeded = compute_seeded_ksk(&glwe_sk, &lwe_sk, thread_rng().gen());
+        let ksk = seeded.expand();
+        assert_eq!(
+            bincode::serialize(&ksk).unwrap(),
+            bincode::serialize(&seeded.expand()).unwrap()
+        );
+
+        for msg in 0..16 {
+            let ct = LweCiphertext::encrypt(encode(msg), &glwe_sk);
+            assert_eq!(decode(ct.keyswitch(&ksk).decrypt(&lwe_sk)), msg);
+        }
+
+        // The masks are most of a keyswitching key.
+        let seeded_len = bincode::serialize(&seeded).unwrap().len();
+        assert!(100 * seeded_len < bincode::serialize(&ksk).unwrap().len());
+    }
+
+    #[test]
+    fn test_compress_for_transport() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+
+        for log2_modulus in [16, 13] {
+            for msg in 0..16 {
+                let ct = LweCiphertext::encrypt(encode(msg), &sk);
+                let compressed = ct.compress_for_transport(log2_modulus);
+                assert_eq!(compressed.decrypt_compressed(&sk), Ok(msg));
+            }
+        }
+
+        let ct = LweCiphertext::encrypt(encode(3), &sk);
+        let compressed = ct.compress_for_transport(16);
+        // 2 bytes per coefficient instead of 8.
+        assert_eq!(compressed.bytes.len(), 2 * (TEST.lwe_dim.0 + 1));
+        let size = bincode::serialize(&ct).unwrap().len();
+        let compressed_size = bincode::serialize(&compressed).unwrap().len();
+        assert!(
+            compressed_size < size / 3,
+            "{compressed_size} bytes instead of {size}"
+        );
+
+        // Untrusted moduli and lengths.
+        for log2_modulus in [0, 4, 64, 200] {
+            let mut invalid = compressed.clone();
+            invalid.log2_modulus = log2_modulus;
+            assert!(matches!(
+                invalid.decrypt_compressed(&sk),
+                Err(TfheError::InvalidEncoding(_))
+            ));
+        }
+        let mut truncated = compressed.clone();
+        truncated.bytes.pop();
+        assert!(matches!(
+            truncated.decrypt_compressed(&sk),
+            Err(TfheError::InvalidEncoding(_))
+        ));
+        let mut other_dim = compressed;
+        other_dim.dim += 1;
+        assert!(matches!(
+            other_dim.decrypt_compressed(&sk),
+            Err(TfheError::KeyMismatchDimension { .. })
+        ));
+    }
+
+    #[test]
+    fn test_debug() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode(5), &sk);
+
+        let debug = format!("{ct:?}");
+        assert!(debug.starts_with(&format!(
+            "LweCiphertext {{ dim: {}, digest: ",
+            TEST.lwe_dim.0
+        )));
+        assert!(!debug.contains(&ct.body.to_string()), "{debug}");
+        assert!(debug.len() < 64, "{debug}");
+        let other = LweCiphertext::encrypt(encode(5), &sk);
+        assert_ne!(debug, format!("{other:?}"));
+
+        #[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+        {
+            let debug = ct.fmt_with_key(&sk);
+            assert!(debug.contains("message: 5, error: "), "{debug}");
+        }
+    }
+
+    /// Tests of the operations generic over the scalar, instantiated for every backend.
+    macro_rules! torus_tests {
+        ($name:ident, $t:ty) => {
+            mod $name {
+                use crate::glwe::keygen_for;
+                use crate::lwe::{compute_ksk, lwe_keygen_for, LweCiphertext, LweSecretKey};
+                use crate::params::presets::TEST;
+                use crate::torus::Torus;
+                use crate::utils::decode_modswitched;
+                use rand::{thread_rng, Rng};
+
+                fn keygen() -> LweSecretKey<$t> {
+                    lwe_keygen_for(&TEST, &mut thread_rng()).to_torus()
+                }
+
+                fn encrypt(msg: u8, sk: &LweSecretKey<$t>) -> LweCiphertext<$t> {
+                    LweCiphertext::encrypt(<$t as Torus>::encode(msg), sk)
+                }
+
+                fn decrypt(ct: LweCiphertext<$t>, sk: &LweSecretKey<$t>) -> u8 {
+                    ct.decrypt(sk).decode()
+                }
+
+                #[test]
+                fn test_encrypt_decrypt() {
+                    let sk = keygen();
+                    for msg in 0..16 {
+                        let ct = encrypt(msg, &sk);
+                        assert!(!ct.is_trivial());
+                        assert_eq!(decrypt(ct, &sk), msg);
+                    }
+                }
+
+                #[test]
+                fn test_arithmetic() {
+                    let sk = keygen();
+                    for _ in 0..100 {
+                        let (a, b) = (thread_rng().gen_range(0..16), thread_rng().gen_range(0..16));
+                        let (ct_a, ct_b) = (encrypt(a, &sk), encrypt(b, &sk));
+                        let sum = ct_a.clone().add(ct_b.clone());
+                        assert_eq!(decrypt(sum, &sk), (a + b) % 16);
+                        let diff = ct_a.clone().sub(&ct_b);
+                        assert_eq!(decrypt(diff, &sk), a.wrapping_sub(b) % 16);
+                        let product = ct_a.multiply_constant(3);
+                        assert_eq!(decrypt(product, &sk), (3 * a) % 16);
+                    }
+                }
+
+                #[test]
+                fn test_operators() {
+                    let sk = keygen();
+                    let (ct1, ct2) = (encrypt(3, &sk), encrypt(7, &sk));
+                    assert_eq!(decrypt(-ct1.clone(), &sk), 13);
+
+                    let five =
+                        LweCiphertext::trivial_encrypt(<$t as Torus>::encode(5), TEST.lwe_dim.0);
+                    let affine = ct1.clone() * 3 - ct2.clone() * -2 + &five;
+                    assert_eq!(decrypt(affine, &sk), (3 * 3 + 2 * 7 + 5) % 16);
+
+                    let mut acc = ct2.clone();
+                    acc -= &ct1;
+                    acc += ct2;
+                    acc *= -1;
+                    assert_eq!(decrypt(acc, &sk), 5);
+                }
+
+                #[test]
+                fn test_keyswitch() {
+                    let lwe_sk = keygen();
+                    let glwe_sk = keygen_for(&TEST, &mut thread_rng()).recode().to_torus();
+                    let ksk = compute_ksk(&glwe_sk, &lwe_sk);
+                    for msg in 0..16 {
+                        let ct = encrypt(msg, &glwe_sk).keyswitch(&ksk);
+                        assert_eq!(ct.mask.len(), TEST.lwe_dim.0);
+                        assert_eq!(decrypt(ct, &lwe_sk), msg);
+                    }
+                }
+
+                #[test]
+                fn test_modswitch() {
+                    let sk = keygen();
+                    for log2_modulus in [10, 11, 12] {
+                        let modulus_mask = (1 << log2_modulus) - 1;
+                        for msg in 0..16 {
+                            let ct = encrypt(msg, &sk).modswitch_to(log2_modulus);
+                            let phase = ct.decrypt(&sk).to_u64() & modulus_mask;
+                            assert_eq!(decode_modswitched(phase, log2_modulus), msg);
+                        }
+                    }
+                }
+            }
+        };
+    }
+
+    torus_tests!(u32_backend, u32);
+    torus_tests!(u64_backend, u64);
 }
@@ -0,0 +1,55 @@
+//! Private membership tests of encrypted values against clear sets.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::LweCiphertext;
+use crate::P;
+
+/// Returns an encryption of `1` if the message of `ct` is in `set`, and of `0` otherwise.
+/// Costs one bootstrap, with the indicator function of `set` as LUT.
+/// The message of `ct` must be below `P/2`. Errors if `set` contains a larger element.
+pub fn set_membership(
+    ct: &LweCiphertext,
+    set: &[u8],
+    key: &EvaluationKey,
+) -> Result<LweCiphertext, String> {
+    if let Some(m) = set.iter().find(|&&m| m as usize >= P / 2) {
+        return Err(format!(
+            "set element {m} is out of the message space [0, {})",
+            P / 2
+        ));
+    }
+
+    Ok(key.bootstrap(ct, |m| set.contains(&m) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::lwe::LweCiphertext;
+    use crate::membership::set_membership;
+    use crate::params::presets::TEST;
+    use crate::utils::{decode, encode};
+
+    #[test]
+    fn test_set_membership() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let sets: [&[u8]; 4] = [&[], &[0, 1, 2, 3, 4, 5, 6, 7], &[0, 7], &[3, 5, 6]];
+        for m in 0..8 {
+            let ct = LweCiphertext::encrypt(encode(m), &keys.lwe_sk);
+            for set in sets {
+                let res = set_membership(&ct, set, &key).unwrap();
+                assert_eq!(
+                    decode(res.decrypt(&keys.lwe_sk)),
+                    set.contains(&m) as u8,
+                    "{m} in {set:?}"
+                );
+            }
+        }
+
+        let ct = LweCiphertext::encrypt(encode(1), &keys.lwe_sk);
+        let err = set_membership(&ct, &[1, 8], &key).err().unwrap();
+        assert!(err.contains("element 8"), "{err}");
+    }
+}
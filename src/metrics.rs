@@ -0,0 +1,114 @@
+//! Operation counters, for capacity planning.
+//!
+//! Every keyswitch, blind rotation, external product, modulus switch and polynomial product
+//! increments a process-wide atomic counter and adds its duration to a cumulative total. Counters
+//! are shared by all threads. Durations are inclusive: the time of a blind rotation contains the
+//! time of its external products, which contains the time of their polynomial products.
+//!
+//! Only compiled with the `metrics` feature. Without it the instrumented functions are unchanged.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Operations tracked by the counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Keyswitch,
+    BlindRotation,
+    ExternalProduct,
+    Modswitch,
+    PolyMul,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::Keyswitch,
+        Operation::BlindRotation,
+        Operation::ExternalProduct,
+        Operation::Modswitch,
+        Operation::PolyMul,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Keyswitch => "keyswitch",
+            Operation::BlindRotation => "blind_rotation",
+            Operation::ExternalProduct => "external_product",
+            Operation::Modswitch => "modswitch",
+            Operation::PolyMul => "poly_mul",
+        }
+    }
+}
+
+struct Counter {
+    count: AtomicU64,
+    nanos: AtomicU64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: Counter = Counter {
+    count: AtomicU64::new(0),
+    nanos: AtomicU64::new(0),
+};
+
+static COUNTERS: [Counter; Operation::ALL.len()] = [ZERO; Operation::ALL.len()];
+
+/// Values of the counters at a point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    counts: [u64; Operation::ALL.len()],
+    nanos: [u64; Operation::ALL.len()],
+}
+
+impl Snapshot {
+    /// Number of executions of `op`.
+    pub fn count(&self, op: Operation) -> u64 {
+        self.counts[op as usize]
+    }
+
+    /// Cumulative duration of the executions of `op`.
+    pub fn duration(&self, op: Operation) -> Duration {
+        Duration::from_nanos(self.nanos[op as usize])
+    }
+
+    /// Operations performed between `earlier` and `self`. Counters reset in between only count
+    /// from zero, never below.
+    pub fn since(&self, earlier: &Snapshot) -> Snapshot {
+        let mut res = *self;
+        for i in 0..Operation::ALL.len() {
+            res.counts[i] = res.counts[i].saturating_sub(earlier.counts[i]);
+            res.nanos[i] = res.nanos[i].saturating_sub(earlier.nanos[i]);
+        }
+        res
+    }
+}
+
+/// Reads all counters.
+pub fn snapshot() -> Snapshot {
+    let mut res = Snapshot::default();
+    for (i, counter) in COUNTERS.iter().enumerate() {
+        res.counts[i] = counter.count.load(Ordering::Relaxed);
+        res.nanos[i] = counter.nanos.load(Ordering::Relaxed);
+    }
+    res
+}
+
+/// Sets all counters back to zero.
+pub fn reset() {
+    for counter in &COUNTERS {
+        counter.count.store(0, Ordering::Relaxed);
+        counter.nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Runs `f`, counting it as an execution of `op`.
+pub(crate) fn record<T>(op: Operation, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    let counter = &COUNTERS[op as usize];
+    counter.count.fetch_add(1, Ordering::Relaxed);
+    counter
+        .nanos
+        .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    res
+}
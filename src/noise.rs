@@ -0,0 +1,353 @@
+use crate::params::Parameters;
+
+/// 2^64, the ciphertext modulus.
+const Q: f64 = 18446744073709551616.0;
+
+/// Variance of a digit drawn uniformly from the signed range `[-B/2, B/2)`.
+fn digit_variance(base_log: usize) -> f64 {
+    let base = (1u64 << base_log) as f64;
+    (base * base + 2.0) / 12.0
+}
+
+/// Variance of the error made by keeping only the `base_log * levels` MSBs of a coefficient.
+fn rounding_variance(base_log: usize, levels: usize) -> f64 {
+    let step = Q / f64::powi(2.0, (base_log * levels) as i32);
+    step * step / 12.0
+}
+
+/// Number of standard deviations of the noise kept below the half width of a decoding box by
+/// `NoiseModel::remaining_bits`. Decoding then fails with probability about `2^-38.5`.
+const BUDGET_STD_DEVS: f64 = 7.0;
+
+/// Predicts the variance of the noise of a ciphertext through a sequence of operations.
+/// Variances are expressed with respect to the ciphertext modulus `q = 2^64`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseModel {
+    pub variance: f64,
+}
+
+impl NoiseModel {
+    /// Noise of a fresh LWE encryption.
+    pub fn fresh(params: &Parameters) -> Self {
+        NoiseModel {
+            variance: params.lwe_sigma * params.lwe_sigma,
+        }
+    }
+
+    /// Noise of a fresh GLWE encryption.
+    pub fn fresh_glwe(params: &Parameters) -> Self {
+        NoiseModel {
+            variance: params.glwe_sigma * params.glwe_sigma,
+        }
+    }
+
+    /// Noise of a trivial encryption.
+    pub fn trivial() -> Self {
+        NoiseModel { variance: 0.0 }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        NoiseModel {
+            variance: self.variance + other.variance,
+        }
+    }
+
+    pub fn scalar_mul(&self, c: i64) -> Self {
+        NoiseModel {
+            variance: self.variance * (c as f64) * (c as f64),
+        }
+    }
+
+    /// Keyswitching of a sample-extracted ciphertext of dimension `kN` to the LWE key.
+    pub fn keyswitch(&self, params: &Parameters) -> Self {
+        let input_dim = (params.glwe_k.0 * params.poly_size.0) as f64;
+        let key_noise = input_dim
+            * params.ks_levels.0 as f64
+            * digit_variance(params.ks_base_log.0)
+            * params.lwe_sigma
+            * params.lwe_sigma;
+        // Half of the binary key coefficients are expected to be set.
+        let rounding =
+            input_dim / 2.0 * rounding_variance(params.ks_base_log.0, params.ks_levels.0);
+
+        NoiseModel {
+            variance: self.variance + key_noise + rounding,
+        }
+    }
+
+    /// Switch of an LWE ciphertext to the modulus `2N`, measured with respect to `q`.
+    pub fn modswitch(&self, params: &Parameters) -> Self {
+        let step = Q / (2 * params.poly_size.0) as f64;
+        // Rounding of the body and of the mask coefficients multiplied by a set key bit.
+        let rounding = (params.lwe_dim.0 as f64 / 2.0 + 1.0) * step * step / 12.0;
+
+        NoiseModel {
+            variance: self.variance + rounding,
+        }
+    }
+
+    /// External product between a GGSW encryption of a bit and a GLWE ciphertext.
+    /// The bit is assumed to be 1, which is the worst case.
+    pub fn external_product(&self, params: &Parameters) -> Self {
+        let n = params.poly_size.0 as f64;
+        let k = params.glwe_k.0 as f64;
+        let key_noise = (k + 1.0)
+            * params.pbs_levels.0 as f64
+            * n
+            * digit_variance(params.pbs_base_log.0)
+            * params.glwe_sigma
+            * params.glwe_sigma;
+        let rounding =
+            (k * n / 2.0 + 1.0) * rounding_variance(params.pbs_base_log.0, params.pbs_levels.0);
+
+        NoiseModel {
+            variance: self.variance + key_noise + rounding,
+        }
+    }
+
+    /// Output noise of a programmable bootstrap, before keyswitching.
+    /// It does not depend on the input noise since the accumulator starts as a trivial encryption.
+    /// Every external product of the blind rotation adds the same noise.
+    pub fn pbs(params: &Parameters) -> Self {
+        NoiseModel {
+            variance: params.blind_rotation_products()
+                * Self::trivial().external_product(params).variance,
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// Bits by which the standard deviation can still grow before `BUDGET_STD_DEVS` standard
+    /// deviations exceed half of the scaling factor `q / 2^plaintext_bits`. Negative once the
+    /// ciphertext is more likely to fail decoding than allowed; infinite for a trivial encryption.
+    pub fn remaining_bits(&self, plaintext_bits: usize) -> f64 {
+        let half_delta = Q / f64::powi(2.0, plaintext_bits as i32 + 1);
+        (half_delta / (BUDGET_STD_DEVS * self.std_dev())).log2()
+    }
+
+    /// Probability that decoding a message of `plaintext_bits` bits fails, that is, that the noise
+    /// exceeds half of the scaling factor `q / 2^plaintext_bits`.
+    pub fn decode_failure_probability(&self, plaintext_bits: usize) -> f64 {
+        if self.variance == 0.0 {
+            return 0.0;
+        }
+        let half_delta = Q / f64::powi(2.0, plaintext_bits as i32 + 1);
+        erfc(half_delta / (self.std_dev() * std::f64::consts::SQRT_2))
+    }
+}
+
+/// Complementary error function, with a relative error below 1.2e-7 (Numerical Recipes, `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let ans = t * poly.exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lwe::{lwe_keygen_for, LweCiphertext};
+    use crate::noise::{erfc, NoiseModel};
+    use crate::params::presets::TEST;
+    use crate::params::Parameters;
+    use crate::utils::{decode, encode};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_erfc() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-7);
+        assert!((erfc(1.0) - 0.157299207).abs() < 1e-7);
+        assert!((erfc(-1.0) - 1.842700793).abs() < 1e-7);
+        assert!((erfc(5.0) / 1.5374597944e-12 - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_operations() {
+        let params = Parameters::DEFAULT;
+        let fresh = NoiseModel::fresh(&params);
+
+        assert_eq!(fresh.add(&fresh).variance, 2.0 * fresh.variance);
+        assert_eq!(fresh.scalar_mul(-3).variance, 9.0 * fresh.variance);
+        assert!(fresh.keyswitch(&params).variance > fresh.variance);
+        assert!(fresh.modswitch(&params).variance > fresh.variance);
+        assert!(NoiseModel::pbs(&params).variance > fresh.external_product(&params).variance);
+    }
+
+    #[test]
+    fn test_decode_failure_probability() {
+        let params = Parameters::DEFAULT;
+        let fresh = NoiseModel::fresh(&params);
+
+        // sigma = 2^49 against a decoding bound of 2^59.
+        assert!(fresh.decode_failure_probability(4) < 1e-100);
+        assert!(fresh.scalar_mul(1 << 10).decode_failure_probability(4) > 1e-3);
+        let noisy = fresh.scalar_mul(256);
+        assert!(
+            noisy.decode_failure_probability(4) < noisy.decode_failure_probability(5),
+            "more plaintext bits leave less room for the noise"
+        );
+        assert_eq!(NoiseModel::trivial().decode_failure_probability(4), 0.0);
+    }
+
+    #[test]
+    fn test_measured_add_chain() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let fresh = NoiseModel::fresh(&TEST);
+        let predicted = (0..8).fold(fresh, |acc, _| acc.add(&fresh));
+        assert!(predicted.remaining_bits(TEST.message_bits) > 0.0);
+
+        // Eight additions of fresh encryptions of 1.
+        let samples = 200;
+        let squares: f64 = (0..samples)
+            .map(|_| {
+                let ct = (0..8).fold(LweCiphertext::encrypt(encode(1), &sk), |acc, _| {
+                    acc.add(LweCiphertext::encrypt(encode(1), &sk))
+                });
+                (ct.noise(&sk, encode(9)) as f64).powi(2)
+            })
+            .sum();
+        let measured = (squares / samples as f64).sqrt();
+        assert!(
+            measured < 2.0 * predicted.std_dev() && predicted.std_dev() < 2.0 * measured,
+            "predicted {:e}, measured {measured:e}",
+            predicted.std_dev()
+        );
+    }
+
+    #[test]
+    fn test_remaining_bits() {
+        let fresh = NoiseModel::fresh(&TEST);
+        assert_eq!(NoiseModel::trivial().remaining_bits(4), f64::INFINITY);
+        assert!(
+            (fresh.remaining_bits(4) - fresh.scalar_mul(4).remaining_bits(4) - 2.0).abs() < 1e-9
+        );
+
+        // The noise outgrows the decoding boxes.
+        let constant = 1 << 24;
+        let huge = fresh.scalar_mul(constant);
+        assert!(huge.remaining_bits(TEST.message_bits) < 0.0);
+        assert!(huge.decode_failure_probability(TEST.message_bits) > 0.5);
+
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let failures = (0..20)
+            .filter(|_| {
+                let ct = LweCiphertext::encrypt(encode(0), &sk).multiply_constant(constant as u64);
+                decode(ct.decrypt(&sk)) != 0
+            })
+            .count();
+        assert!(failures > 10, "{failures} failures out of 20");
+    }
+
+    #[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+    mod empirical {
+        use crate::glwe::{keygen, keygen_for, GlweCiphertext};
+        use crate::lwe::{compute_ksk, lwe_keygen, lwe_keygen_for};
+        use crate::noise::NoiseModel;
+        use crate::params::presets::TOY;
+        use crate::params::Parameters;
+        use crate::stats;
+        use crate::utils::encode;
+        use rand::thread_rng;
+
+        /// Maximum relative gap between predicted and measured variances.
+        const TOLERANCE: f64 = 0.2;
+
+        fn assert_close(predicted: f64, measured: f64) {
+            assert!(
+                (predicted - measured).abs() < TOLERANCE * predicted,
+                "predicted {predicted:e}, measured {measured:e}"
+            );
+        }
+
+        #[test]
+        fn test_add_chain() {
+            let params = Parameters::DEFAULT;
+            let sk = lwe_keygen();
+            let fresh = NoiseModel::fresh(&params);
+
+            for length in [2, 16] {
+                let predicted = (1..length).fold(fresh, |acc, _| acc.add(&fresh));
+                let measured = stats::add_chain(&sk, length, 5_000);
+                assert_close(predicted.variance, measured.variance);
+            }
+        }
+
+        #[test]
+        fn test_keyswitch() {
+            let params = Parameters::DEFAULT;
+            let lwe_sk = lwe_keygen();
+            let glwe_sk = keygen();
+            let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+
+            let predicted = NoiseModel::fresh_glwe(&params).keyswitch(&params);
+            let measured = stats::keyswitch(&glwe_sk, &lwe_sk, &ksk, 300);
+            // The digits average `-1/2`: for the fixed key of the measure, their mean only offsets
+            // the result by `-sum(e)/2` over the noises `e` of the key, whose variance the model
+            // counts but the measure does not.
+            let offset = (params.glwe_k.0 * params.poly_size.0 * params.ks_levels.0) as f64 / 4.0
+                * params.lwe_sigma
+                * params.lwe_sigma;
+            assert_close(predicted.variance - offset, measured.variance);
+        }
+
+        #[test]
+        fn test_amplified_failure_rates() {
+            let mut previous = 0.0;
+            // Decoding fails after about 4%, 12% and 21% of the keyswitches.
+            for factor in [1200.0, 1600.0, 2000.0] {
+                let params = TOY.with_amplified_noise(factor);
+                let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+                let glwe_sk = keygen_for(&params, &mut thread_rng());
+                let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+
+                let predicted = NoiseModel::fresh_glwe(&params)
+                    .keyswitch(&params)
+                    .decode_failure_probability(params.message_bits);
+                let samples = 2_000;
+                let measured = stats::failure_rate(
+                    |m| {
+                        GlweCiphertext::encrypt(encode(m), &glwe_sk)
+                            .sample_extract()
+                            .keyswitch(&ksk)
+                    },
+                    &lwe_sk,
+                    params.message_bits as u32,
+                    samples,
+                );
+
+                // Binomial sampling error, and the error of the model on the variance.
+                let sampling = (predicted * (1.0 - predicted) / samples as f64).sqrt();
+                assert!(
+                    (predicted - measured).abs() < 4.0 * sampling + 0.25 * predicted,
+                    "factor {factor}: predicted {predicted}, measured {measured}"
+                );
+                assert!(predicted > previous);
+                previous = predicted;
+            }
+        }
+
+        #[test]
+        fn test_modswitch() {
+            let params = Parameters::DEFAULT;
+            let sk = lwe_keygen();
+
+            let predicted = NoiseModel::fresh(&params).modswitch(&params);
+            let measured = stats::modswitch(&sk, 5_000);
+            assert_close(predicted.variance, measured.variance);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Exact negacyclic products of polynomials of Z_{2^64}\[X\]/(X^N + 1) with number theoretic
+//! transforms.
+//!
+//! The products of two coefficients below `2^64` sum up to less than `N 2^128` in absolute value,
+//! so the integer negacyclic convolution is recovered exactly by the Chinese remainder theorem from
+//! its residues modulo three primes of 62 bits, whose product exceeds `2^185`, then reduced modulo
+//! `2^64`. The primes are `1` modulo `2^17`, so that they have the primitive `2N`-th roots of unity
+//! of the negacyclic transforms of size up to `MAX_POLY_SIZE`.
+//!
+//! The transforms are those of Longa and Naehrig: a Cooley-Tukey forward transform whose outputs
+//! are in bit-reversed order, and a Gentleman-Sande inverse transform which takes them back, with
+//! the powers of the `2N`-th root folded into the twiddle factors. Multiplications by the twiddle
+//! factors use Shoup's precomputed quotients.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Largest polynomial size with a negacyclic transform.
+pub const MAX_POLY_SIZE: usize = 1 << 16;
+
+/// Primes `c 2^17 + 1` below `2^62`, for which `3` is a quadratic non-residue: `3^((p - 1) / 2^17)`
+/// is a primitive `2^17`-th root of unity.
+const PRIMES: [u64; 3] = [
+    0x3fff_ffff_ffe8_0001,
+    0x3fff_ffff_ffbe_0001,
+    0x3fff_ffff_ffb8_0001,
+];
+
+/// Prime modulus, with its Barrett constant `floor(2^124 / p)`.
+#[derive(Clone, Copy)]
+struct Modulus {
+    p: u64,
+    barrett: u64,
+}
+
+/// Precomputed tables of the transforms of one size modulo one prime.
+struct PrimeTables {
+    modulus: Modulus,
+    /// Powers of the `2N`-th root of unity in bit-reversed order, with their Shoup quotients.
+    psi: Vec<(u64, u64)>,
+    /// Powers of its inverse in bit-reversed order, with their Shoup quotients.
+    psi_inv: Vec<(u64, u64)>,
+    /// `N^-1` and its Shoup quotient.
+    n_inv: (u64, u64),
+}
+
+/// Tables of the transforms of one size modulo every prime.
+struct Plan {
+    tables: [PrimeTables; 3],
+    /// `p1^-1 mod p2`, `(p1 p2)^-1 mod p3` and `p1 mod p3`, with their Shoup quotients, for the
+    /// reconstruction from the residues.
+    crt: [(u64, u64); 3],
+}
+
+/// Negacyclic product of `lhs` and `rhs` in Z_{2^64}\[X\]/(X^N + 1), where `N` is their length.
+/// Panics if the lengths differ, or if they are not a power of two up to `MAX_POLY_SIZE`.
+pub fn negacyclic_mul(lhs: &[u64], rhs: &[u64]) -> Vec<u64> {
+    let n = lhs.len();
+    assert_eq!(n, rhs.len(), "polynomials of different sizes");
+    assert!(
+        n.is_power_of_two() && n <= MAX_POLY_SIZE,
+        "no negacyclic transform of size {n}"
+    );
+
+    let plan = plan(n);
+    let residues = plan.tables.each_ref().map(|tables| {
+        let modulus = tables.modulus;
+        let mut a: Vec<u64> = lhs.iter().map(|&c| modulus.reduce(c as u128)).collect();
+        let mut b: Vec<u64> = rhs.iter().map(|&c| modulus.reduce(c as u128)).collect();
+        tables.forward(&mut a);
+        tables.forward(&mut b);
+        for (x, y) in a.iter_mut().zip(&b) {
+            *x = modulus.mul(*x, *y);
+        }
+        tables.inverse(&mut a);
+        a
+    });
+
+    let [m1, m2, m3] = plan.tables.each_ref().map(|tables| tables.modulus);
+    let [p1_inv, p1p2_inv, p1_mod_p3] = plan.crt;
+    let p1p2 = m1.p.wrapping_mul(m2.p);
+    let product = p1p2.wrapping_mul(m3.p);
+    (0..n)
+        .map(|i| {
+            let [r1, r2, r3] = [0, 1, 2].map(|j| residues[j][i]);
+            // Mixed-radix digits of the residue modulo `p1 p2 p3`: `r1 + p1 v2 + p1 p2 v3`.
+            let v2 = mul_shoup(sub_mod(r2, m2.reduce(r1 as u128), m2.p), p1_inv, m2.p);
+            let v3 = sub_mod(
+                sub_mod(r3, m3.reduce(r1 as u128), m3.p),
+                mul_shoup(v2, p1_mod_p3, m3.p),
+                m3.p,
+            );
+            let v3 = mul_shoup(v3, p1p2_inv, m3.p);
+            let x = r1
+                .wrapping_add(m1.p.wrapping_mul(v2))
+                .wrapping_add(p1p2.wrapping_mul(v3));
+            // Coefficients are far below `p1 p2 p3 / 2` in absolute value: a top digit in the
+            // upper half of its range is that of a negative coefficient.
+            if v3 > m3.p / 2 {
+                x.wrapping_sub(product)
+            } else {
+                x
+            }
+        })
+        .collect()
+}
+
+/// The plan of size `n`, computed at the first call and shared afterwards.
+fn plan(n: usize) -> Arc<Plan> {
+    static PLANS: OnceLock<Mutex<HashMap<usize, Arc<Plan>>>> = OnceLock::new();
+    PLANS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(n)
+        .or_insert_with(|| {
+            let [p1, p2, p3] = PRIMES;
+            let (m2, m3) = (Modulus::new(p2), Modulus::new(p3));
+            let p1_inv = m2.inv(m2.reduce(p1 as u128));
+            let p1p2_inv = m3.inv(m3.mul(m3.reduce(p1 as u128), m3.reduce(p2 as u128)));
+            let p1_mod_p3 = m3.reduce(p1 as u128);
+            Arc::new(Plan {
+                tables: PRIMES.map(|p| PrimeTables::new(Modulus::new(p), n)),
+                crt: [
+                    (p1_inv, shoup(p1_inv, p2)),
+                    (p1p2_inv, shoup(p1p2_inv, p3)),
+                    (p1_mod_p3, shoup(p1_mod_p3, p3)),
+                ],
+            })
+        })
+        .clone()
+}
+
+impl Modulus {
+    fn new(p: u64) -> Self {
+        Modulus {
+            p,
+            barrett: ((1u128 << 124) / p as u128) as u64,
+        }
+    }
+
+    /// `x mod p` for `x < 2^124`.
+    fn reduce(self, x: u128) -> u64 {
+        // The estimate of the quotient is short by at most 3.
+        let q = (((x >> 60) * self.barrett as u128) >> 64) as u64;
+        let mut r = (x - q as u128 * self.p as u128) as u64;
+        while r >= self.p {
+            r -= self.p;
+        }
+        r
+    }
+
+    fn mul(self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    fn pow(self, mut base: u64, mut exponent: u64) -> u64 {
+        let mut res = 1;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                res = self.mul(res, base);
+            }
+            base = self.mul(base, base);
+            exponent >>= 1;
+        }
+        res
+    }
+
+    /// Inverse of `a`, by Fermat's little theorem.
+    fn inv(self, a: u64) -> u64 {
+        self.pow(a, self.p - 2)
+    }
+}
+
+impl PrimeTables {
+    fn new(modulus: Modulus, n: usize) -> Self {
+        let p = modulus.p;
+        let root = modulus.pow(3, (p - 1) >> 17);
+        let psi = modulus.pow(root, (MAX_POLY_SIZE / n) as u64);
+        let log_n = n.trailing_zeros();
+
+        let powers = |base: u64| {
+            let mut powers = vec![(0, 0); n];
+            let mut power = 1u64;
+            for i in 0..n {
+                let rev = if log_n == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - log_n)
+                };
+                powers[rev] = (power, shoup(power, p));
+                power = modulus.mul(power, base);
+            }
+            powers
+        };
+        let n_inv = modulus.inv(n as u64);
+
+        PrimeTables {
+            modulus,
+            psi: powers(psi),
+            psi_inv: powers(modulus.inv(psi)),
+            n_inv: (n_inv, shoup(n_inv, p)),
+        }
+    }
+
+    /// Replaces `a`, reduced modulo `p`, by its transform in bit-reversed order.
+    fn forward(&self, a: &mut [u64]) {
+        let p = self.modulus.p;
+        let n = a.len();
+        let mut t = n;
+        let mut m = 1;
+        while m < n {
+            t /= 2;
+            for (block, &w) in a.chunks_exact_mut(2 * t).zip(&self.psi[m..2 * m]) {
+                let (lo, hi) = block.split_at_mut(t);
+                for (x, y) in lo.iter_mut().zip(hi) {
+                    let u = *x;
+                    let v = mul_shoup(*y, w, p);
+                    *x = add_mod(u, v, p);
+                    *y = sub_mod(u, v, p);
+                }
+            }
+            m *= 2;
+        }
+    }
+
+    /// Inverse of `forward`.
+    fn inverse(&self, a: &mut [u64]) {
+        let p = self.modulus.p;
+        let n = a.len();
+        let mut t = 1;
+        let mut m = n;
+        while m > 1 {
+            let h = m / 2;
+            for (block, &w) in a.chunks_exact_mut(2 * t).zip(&self.psi_inv[h..m]) {
+                let (lo, hi) = block.split_at_mut(t);
+                for (x, y) in lo.iter_mut().zip(hi) {
+                    let (u, v) = (*x, *y);
+                    *x = add_mod(u, v, p);
+                    *y = mul_shoup(sub_mod(u, v, p), w, p);
+                }
+            }
+            t *= 2;
+            m = h;
+        }
+        for x in a.iter_mut() {
+            *x = mul_shoup(*x, self.n_inv, p);
+        }
+    }
+}
+
+// Reductions of values below `2p` take the minimum with the value minus `p`, which wraps around
+// below `p`, instead of branching on unpredictable comparisons.
+
+fn add_mod(a: u64, b: u64, p: u64) -> u64 {
+    let sum = a + b;
+    sum.min(sum.wrapping_sub(p))
+}
+
+fn sub_mod(a: u64, b: u64, p: u64) -> u64 {
+    let diff = a.wrapping_sub(b);
+    diff.min(diff.wrapping_add(p))
+}
+
+/// Shoup quotient `floor(w 2^64 / p)` of `w < p`.
+fn shoup(w: u64, p: u64) -> u64 {
+    (((w as u128) << 64) / p as u128) as u64
+}
+
+/// `a w mod p` for any `a` and the constant `w` with its Shoup quotient.
+fn mul_shoup(a: u64, (w, w_shoup): (u64, u64), p: u64) -> u64 {
+    let q = ((a as u128 * w_shoup as u128) >> 64) as u64;
+    let r = a.wrapping_mul(w).wrapping_sub(q.wrapping_mul(p));
+    r.min(r.wrapping_sub(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ntt::{negacyclic_mul, PRIMES};
+    use crate::poly::ResiduePoly;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_negacyclic_mul() {
+        for log_n in 0..=10 {
+            let n = 1 << log_n;
+            let lhs = ResiduePoly::random_with(n, &mut thread_rng());
+            let rhs = ResiduePoly::random_with(n, &mut thread_rng());
+            let mut expected = ResiduePoly::zero(n);
+            expected.add_mul_assign_schoolbook(&lhs, &rhs);
+            assert_eq!(
+                negacyclic_mul(&lhs.coefs, &rhs.coefs),
+                expected.coefs,
+                "N = {n}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extreme_coefficients() {
+        // The largest coefficients, whose products reach the bound of the convolution, then
+        // coefficients vanishing modulo the primes.
+        let n = 1024;
+        let max = vec![u64::MAX; n];
+        let mut expected = ResiduePoly::zero(n);
+        let poly = ResiduePoly { coefs: max.clone() };
+        expected.add_mul_assign_schoolbook(&poly, &poly);
+        assert_eq!(negacyclic_mul(&max, &max), expected.coefs);
+
+        let mut coefs: Vec<u64> = (0..n).map(|_| thread_rng().gen()).collect();
+        coefs[..3].copy_from_slice(&PRIMES);
+        let poly = ResiduePoly { coefs };
+        let mut expected = ResiduePoly::zero(n);
+        expected.add_mul_assign_schoolbook(&poly, &poly);
+        assert_eq!(negacyclic_mul(&poly.coefs, &poly.coefs), expected.coefs);
+    }
+
+    #[test]
+    #[should_panic(expected = "no negacyclic transform of size 12")]
+    fn test_size_not_power_of_two() {
+        negacyclic_mul(&[0; 12], &[0; 12]);
+    }
+}
@@ -0,0 +1,585 @@
+//! Keyswitching from LWE ciphertexts to GLWE ciphertexts, and between GLWE keys.
+//!
+//! Two routes pack several LWE ciphertexts into one GLWE ciphertext:
+//! - `functional_keyswitch` switches each LWE ciphertext, of the small LWE dimension `n`, with a
+//!   key of `n * ks_levels` GLWE ciphertexts. Packing `m` ciphertexts costs `m` keyswitches of
+//!   `n * ks_levels` scalar-polynomial products each.
+//! - `pack_lwes_via_trace` embeds ciphertexts of dimension `kN`, e.g. sample-extracted ones, into
+//!   GLWE ciphertexts and cancels the unwanted coefficients with automorphisms. Its keys are only
+//!   `lg(N) * k * levels` GLWE ciphertexts, much smaller than a functional keyswitching key, but
+//!   packing `m` ciphertexts costs `m - 1 + lg(N/m)` GLWE keyswitches of `k * levels * (k + 1)`
+//!   polynomial products each.
+//!
+//! `pack_lwe_ciphertexts` is the first route with the identity: the i-th ciphertext lands in
+//! coefficient `i`, which compresses up to `N` results into a single GLWE ciphertext.
+//!
+//! `GlweCiphertext::keyswitch` switches a GLWE ciphertext to another GLWE key of the same
+//! polynomial size, possibly of a different dimension, e.g. to move between parameter sets.
+
+use crate::error::TfheError;
+use crate::glwe::{GlweCiphertext, SecretKey};
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::params::{DecompositionBaseLog, DecompositionLevelCount, GlweDimension, PolynomialSize};
+use crate::poly::ResiduePoly;
+use crate::utils::decompose_rounded;
+use rand::{thread_rng, CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+
+/// Encryptions of the bits of a LWE key under a GLWE key, as constant polynomials. The i-th bit
+/// is encrypted `levels` times, scaled by q/B^l, ..., q/B with lg(B) = `base_log`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FunctionalKeySwitchingKey {
+    pub base_log: DecompositionBaseLog,
+    pub levels: DecompositionLevelCount,
+    pub keys: Vec<GlweCiphertext>,
+}
+
+/// Encrypts `lwe_sk` under `glwe_sk`.
+pub fn compute_functional_ksk(
+    lwe_sk: &LweSecretKey,
+    glwe_sk: &SecretKey,
+) -> FunctionalKeySwitchingKey {
+    compute_functional_ksk_with(lwe_sk, glwe_sk, &mut thread_rng())
+}
+
+/// Encrypts `lwe_sk` under `glwe_sk`, drawing the randomness from `rng`.
+/// The decomposition is the keyswitching one of the parameters of the keys, which must agree.
+pub fn compute_functional_ksk_with<R: Rng + CryptoRng>(
+    lwe_sk: &LweSecretKey,
+    glwe_sk: &SecretKey,
+    rng: &mut R,
+) -> FunctionalKeySwitchingKey {
+    assert!(
+        lwe_sk.params == glwe_sk.params,
+        "keys of different parameters"
+    );
+    let (base_log, levels) = (glwe_sk.params.ks_base_log.0, glwe_sk.params.ks_levels.0);
+
+    let mut keys = Vec::<GlweCiphertext>::with_capacity(levels * lwe_sk.coefs.len());
+    for bit in &lwe_sk.coefs {
+        for j in 0..levels {
+            let mu = bit << (64 - base_log * (levels - j));
+            keys.push(GlweCiphertext::encrypt_with(mu, glwe_sk, rng));
+        }
+    }
+
+    FunctionalKeySwitchingKey {
+        base_log: glwe_sk.params.ks_base_log,
+        levels: glwe_sk.params.ks_levels,
+        keys,
+    }
+}
+
+/// Switches `cts` to the GLWE key of `fksk` while applying the public linear function of
+/// coefficients `f_coeffs`: the result encrypts the polynomial whose `index`-th coefficient is the
+/// sum of the messages of `cts` weighted by `f_coeffs`, and whose other coefficients are `0`.
+pub fn functional_keyswitch(
+    cts: &[LweCiphertext],
+    f_coeffs: &[u64],
+    index: usize,
+    fksk: &FunctionalKeySwitchingKey,
+) -> GlweCiphertext {
+    assert!(
+        !cts.is_empty() && cts.len() == f_coeffs.len(),
+        "one coefficient is needed per ciphertext"
+    );
+
+    // The function being linear, it is applied before switching keys.
+    let mut combined = LweCiphertext::zero(cts[0].mask.len());
+    for (ct, &f) in cts.iter().zip(f_coeffs) {
+        combined = combined.add(ct.multiply_constant(f));
+    }
+
+    assert_eq!(
+        combined.mask.len() * fksk.levels.0,
+        fksk.keys.len(),
+        "ciphertext and functional keyswitching key dimensions differ"
+    );
+    let (glwe_k, poly_size) = (fksk.keys[0].mask.len(), fksk.keys[0].body.coefs.len());
+    assert!(index < poly_size, "index {index} out of [0, {poly_size})");
+
+    let mut res = GlweCiphertext::zero(GlweDimension(glwe_k), PolynomialSize(poly_size));
+    res.body.coefs[0] = combined.body;
+    for (i, a) in combined.mask.iter().enumerate() {
+        let decomp = decompose_rounded(*a, fksk.base_log, fksk.levels);
+        for (j, digit) in decomp.into_iter().enumerate() {
+            sub_scaled_assign(&mut res, &fksk.keys[i * fksk.levels.0 + j], digit);
+        }
+    }
+
+    res.rotate(index as u64)
+}
+
+/// Key of `pack_lwe_ciphertexts`, computed with `compute_functional_ksk` from the LWE key of the
+/// ciphertexts and the GLWE key of the packed result.
+pub type PackingKeySwitchingKey = FunctionalKeySwitchingKey;
+
+/// Packs `cts` into one GLWE ciphertext under the GLWE key of `pksk`, whose i-th coefficient
+/// encrypts the message of the i-th ciphertext and whose other coefficients encrypt `0`.
+/// Errors with `TfheError::MalformedKey` if `pksk` does not hold `levels` ciphertexts per LWE key
+/// coefficient, with `TfheError::KeyMismatchDimension` if a ciphertext is not of the dimension of
+/// the LWE key, and with `TfheError::MessageOutOfRange` if there are more ciphertexts than
+/// coefficients, the index of the last ciphertext being out of the coefficients.
+pub fn pack_lwe_ciphertexts(
+    cts: &[LweCiphertext],
+    pksk: &PackingKeySwitchingKey,
+) -> Result<GlweCiphertext, TfheError> {
+    let levels = pksk.levels.0;
+    let Some(first) = pksk.keys.first() else {
+        return Err(TfheError::MalformedKey(
+            "packing keyswitching key without ciphertexts".to_string(),
+        ));
+    };
+    if levels == 0 || !pksk.keys.len().is_multiple_of(levels) {
+        return Err(TfheError::MalformedKey(format!(
+            "{} ciphertexts for {levels} levels",
+            pksk.keys.len()
+        )));
+    }
+    let dim = pksk.keys.len() / levels;
+    if let Some(ct) = cts.iter().find(|ct| ct.mask.len() != dim) {
+        return Err(TfheError::KeyMismatchDimension {
+            expected: dim,
+            found: ct.mask.len(),
+        });
+    }
+    let (glwe_k, poly_size) = (first.mask.len(), first.body.coefs.len());
+    if cts.len() > poly_size {
+        return Err(TfheError::MessageOutOfRange {
+            msg: cts.len() as u64 - 1,
+            max: poly_size as u64,
+        });
+    }
+
+    let mut res = GlweCiphertext::zero(GlweDimension(glwe_k), PolynomialSize(poly_size));
+    for (i, ct) in cts.iter().enumerate() {
+        res = res.add(&functional_keyswitch(
+            std::slice::from_ref(ct),
+            &[1],
+            i,
+            pksk,
+        ));
+    }
+    Ok(res)
+}
+
+/// Subtracts `c * ct` from `acc`.
+fn sub_scaled_assign(acc: &mut GlweCiphertext, ct: &GlweCiphertext, c: u64) {
+    let polys = acc.mask.iter_mut().chain([&mut acc.body]);
+    let rhs = ct.mask.iter().chain([&ct.body]);
+    for (acc_poly, poly) in polys.zip(rhs) {
+        for (a, b) in acc_poly.coefs.iter_mut().zip(&poly.coefs) {
+            *a = a.wrapping_sub(b.wrapping_mul(c));
+        }
+    }
+}
+
+/// GLWE keyswitching key from the image of a GLWE key by the automorphism X -> X^g back to the
+/// key. The image of the i-th key polynomial is encrypted `levels` times, scaled by
+/// q/B^l, ..., q/B with lg(B) = `base_log`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AutomorphismKey {
+    pub g: usize,
+    pub base_log: DecompositionBaseLog,
+    pub levels: DecompositionLevelCount,
+    pub keys: Vec<GlweCiphertext>,
+}
+
+/// Automorphism keys for X -> X^(2^i + 1), for i in 1..=lg(N).
+pub type AutomorphismKeys = Vec<AutomorphismKey>;
+
+/// Computes the automorphism keys of `sk` used by `pack_lwes_via_trace`.
+/// The decomposition must be precise, since the noise of the early keyswitches is doubled by every
+/// later step: `base_log * levels` around 40 is suitable.
+pub fn compute_automorphism_keys(
+    sk: &SecretKey,
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+) -> AutomorphismKeys {
+    compute_automorphism_keys_with(sk, base_log, levels, &mut thread_rng())
+}
+
+/// Computes the automorphism keys of `sk`, drawing the randomness from `rng`.
+pub fn compute_automorphism_keys_with<R: Rng + CryptoRng>(
+    sk: &SecretKey,
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+    rng: &mut R,
+) -> AutomorphismKeys {
+    let poly_size = sk.params.poly_size.0;
+    let (base_log, levels) = (base_log.0, levels.0);
+    (1..=poly_size.ilog2())
+        .map(|i| {
+            let g = (1 << i) + 1;
+            let mut keys = Vec::<GlweCiphertext>::with_capacity(levels * sk.polys.len());
+            for poly in &sk.polys {
+                let image = poly.automorphism(g);
+                for j in 0..levels {
+                    let shift = 64 - base_log * (levels - j);
+                    let mut key = GlweCiphertext::encrypt_with(0, sk, rng);
+                    for (coef, bit) in key.body.coefs.iter_mut().zip(&image.coefs) {
+                        *coef = coef.wrapping_add(bit << shift);
+                    }
+                    keys.push(key);
+                }
+            }
+            AutomorphismKey {
+                g,
+                base_log: DecompositionBaseLog(base_log),
+                levels: DecompositionLevelCount(levels),
+                keys,
+            }
+        })
+        .collect()
+}
+
+/// Applies the automorphism X -> X^g of `key` to the plaintext of `ct`, keeping the key of `ct`.
+pub fn apply_automorphism(ct: &GlweCiphertext, key: &AutomorphismKey) -> GlweCiphertext {
+    assert_eq!(
+        ct.mask.len() * key.levels.0,
+        key.keys.len(),
+        "ciphertext and automorphism key dimensions differ"
+    );
+    let poly_size = ct.body.coefs.len();
+
+    let mut res = GlweCiphertext::zero(GlweDimension(ct.mask.len()), PolynomialSize(poly_size));
+    res.body = ct.body.automorphism(key.g);
+    let images: Vec<ResiduePoly> = ct.mask.iter().map(|p| p.automorphism(key.g)).collect();
+    sub_decomposed_products(&mut res, &images, key.base_log, key.levels, &key.keys);
+    res
+}
+
+/// Subtracts from `acc` the products of the decompositions of `polys` by `keys`, which hold
+/// `levels` ciphertexts per polynomial: switches a GLWE ciphertext of mask `polys` to the key of
+/// `keys`, its body being already in `acc`.
+fn sub_decomposed_products(
+    acc: &mut GlweCiphertext,
+    polys: &[ResiduePoly],
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+    keys: &[GlweCiphertext],
+) {
+    for (poly, poly_keys) in polys.iter().zip(keys.chunks(levels.0)) {
+        for (digit, key) in poly.decompose(base_log, levels).iter().zip(poly_keys) {
+            for (acc_poly, key_poly) in acc.mask.iter_mut().zip(&key.mask) {
+                *acc_poly = acc_poly.sub(&digit.mul(key_poly));
+            }
+            acc.body = acc.body.sub(&digit.mul(&key.body));
+        }
+    }
+}
+
+/// Encryptions of the polynomials of an input GLWE key under an output GLWE key of the same
+/// polynomial size. The i-th input polynomial is encrypted `levels` times, scaled by
+/// q/B^l, ..., q/B with lg(B) = `base_log`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GlweKeySwitchingKey {
+    pub base_log: DecompositionBaseLog,
+    pub levels: DecompositionLevelCount,
+    pub keys: Vec<GlweCiphertext>,
+}
+
+/// Encrypts `sk_in` under `sk_out`.
+/// Panics if the keys have different polynomial sizes.
+pub fn compute_glwe_ksk(
+    sk_in: &SecretKey,
+    sk_out: &SecretKey,
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+) -> GlweKeySwitchingKey {
+    compute_glwe_ksk_with(sk_in, sk_out, base_log, levels, &mut thread_rng())
+}
+
+/// Encrypts `sk_in` under `sk_out`, drawing the randomness from `rng`.
+pub fn compute_glwe_ksk_with<R: Rng + CryptoRng>(
+    sk_in: &SecretKey,
+    sk_out: &SecretKey,
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+    rng: &mut R,
+) -> GlweKeySwitchingKey {
+    assert_eq!(
+        sk_in.params.poly_size, sk_out.params.poly_size,
+        "keys of different polynomial sizes"
+    );
+
+    let mut keys = Vec::<GlweCiphertext>::with_capacity(levels.0 * sk_in.polys.len());
+    for poly in &sk_in.polys {
+        for j in 0..levels.0 {
+            let shift = 64 - base_log.0 * (levels.0 - j);
+            let mut key = GlweCiphertext::encrypt_with(0, sk_out, rng);
+            for (coef, bit) in key.body.coefs.iter_mut().zip(&poly.coefs) {
+                *coef = coef.wrapping_add(bit << shift);
+            }
+            keys.push(key);
+        }
+    }
+
+    GlweKeySwitchingKey {
+        base_log,
+        levels,
+        keys,
+    }
+}
+
+impl GlweCiphertext {
+    /// Switches `self` to the output key of `ksk`, with `k * levels` products of polynomials by
+    /// GLWE ciphertexts. Panics if the dimension of `self` does not match `ksk`.
+    pub fn keyswitch(&self, ksk: &GlweKeySwitchingKey) -> GlweCiphertext {
+        assert_eq!(
+            self.mask.len() * ksk.levels.0,
+            ksk.keys.len(),
+            "ciphertext and GLWE keyswitching key dimensions differ"
+        );
+        let glwe_k = GlweDimension(ksk.keys[0].mask.len());
+
+        let mut res = GlweCiphertext::zero(glwe_k, PolynomialSize(self.body.coefs.len()));
+        res.body = self.body.clone();
+        sub_decomposed_products(&mut res, &self.mask, ksk.base_log, ksk.levels, &ksk.keys);
+        res
+    }
+}
+
+/// Packs `cts`, of dimension `kN` under the recoded GLWE key of `auto_keys`, into one GLWE
+/// ciphertext. With `2^l` the smallest power of two at least `cts.len()`, the message of the i-th
+/// ciphertext is placed in coefficient `i * N / 2^l` and the other coefficients are `0`.
+pub fn pack_lwes_via_trace(cts: &[LweCiphertext], auto_keys: &AutomorphismKeys) -> GlweCiphertext {
+    let glwe_k = GlweDimension(auto_keys[0].keys[0].mask.len());
+    let poly_size = PolynomialSize(auto_keys[0].keys[0].body.coefs.len());
+    assert_eq!(
+        auto_keys.len() as u32,
+        poly_size.0.ilog2(),
+        "one automorphism key is needed per power of two up to N"
+    );
+    assert!(
+        !cts.is_empty() && cts.len() <= poly_size.0,
+        "between 1 and N ciphertexts can be packed, got {}",
+        cts.len()
+    );
+
+    // The packing multiplies the messages by N: the ciphertexts are divided by N beforehand.
+    let log_n = poly_size.0.ilog2();
+    let embedded: Vec<GlweCiphertext> = cts
+        .iter()
+        .map(|ct| {
+            assert_eq!(
+                ct.mask.len(),
+                glwe_k.0 * poly_size.0,
+                "ciphertext and automorphism key dimensions differ"
+            );
+            let scale = |a: u64| a.wrapping_add(1 << (log_n - 1)) >> log_n;
+            embed(
+                &LweCiphertext {
+                    mask: ct.mask.iter().map(|&a| scale(a)).collect(),
+                    body: scale(ct.body),
+                },
+                glwe_k,
+                poly_size,
+            )
+        })
+        .collect();
+
+    let log_count = cts.len().next_power_of_two().ilog2();
+    let mut res = pack_recursive(&embedded, log_count, glwe_k, poly_size, auto_keys);
+    for i in log_count + 1..=log_n {
+        res = res.add(&apply_automorphism(&res, &auto_keys[i as usize - 1]));
+    }
+    res
+}
+
+/// Embeds a LWE ciphertext of dimension `glwe_k * poly_size` into a GLWE ciphertext whose
+/// plaintext has the message of `ct` as constant coefficient. Inverse of the sample extraction.
+fn embed(ct: &LweCiphertext, glwe_k: GlweDimension, poly_size: PolynomialSize) -> GlweCiphertext {
+    let mut res = GlweCiphertext::zero(glwe_k, poly_size);
+    let poly_size = poly_size.0;
+    for (poly, a) in res.mask.iter_mut().zip(ct.mask.chunks(poly_size)) {
+        poly.coefs[0] = a[0];
+        for i in 1..poly_size {
+            poly.coefs[poly_size - i] = a[i].wrapping_neg();
+        }
+    }
+    res.body.coefs[0] = ct.body;
+    res
+}
+
+/// Packs the `2^log_count` first ciphertexts of `cts`, missing ones being encryptions of `0`.
+/// The constant coefficient of the i-th one is moved to coefficient `i * N / 2^log_count` and
+/// multiplied by `2^log_count`.
+fn pack_recursive(
+    cts: &[GlweCiphertext],
+    log_count: u32,
+    glwe_k: GlweDimension,
+    poly_size: PolynomialSize,
+    auto_keys: &AutomorphismKeys,
+) -> GlweCiphertext {
+    if log_count == 0 {
+        return cts
+            .first()
+            .cloned()
+            .unwrap_or_else(|| GlweCiphertext::zero(glwe_k, poly_size));
+    }
+
+    let even: Vec<GlweCiphertext> = cts.iter().step_by(2).cloned().collect();
+    let odd: Vec<GlweCiphertext> = cts.iter().skip(1).step_by(2).cloned().collect();
+    let even = pack_recursive(&even, log_count - 1, glwe_k, poly_size, auto_keys);
+    let odd = pack_recursive(&odd, log_count - 1, glwe_k, poly_size, auto_keys)
+        .rotate((poly_size.0 >> log_count) as u64);
+
+    let sum = even.add(&odd);
+    let image = apply_automorphism(&even.sub(&odd), &auto_keys[log_count as usize - 1]);
+    sum.add(&image)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TfheError;
+    use crate::glwe::keygen_for;
+    use crate::glwe::GlweCiphertext;
+    use crate::lwe::{lwe_keygen_for, LweCiphertext};
+    use crate::packing::{
+        compute_automorphism_keys, compute_functional_ksk, compute_glwe_ksk, functional_keyswitch,
+        pack_lwe_ciphertexts, pack_lwes_via_trace,
+    };
+    use crate::params::presets::TEST;
+    use crate::params::{DecompositionBaseLog, DecompositionLevelCount, GlweDimension, Parameters};
+    use crate::utils::{decode, decode_poly, encode, encode_poly};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_functional_keyswitch() {
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let fksk = compute_functional_ksk(&lwe_sk, &glwe_sk);
+
+        for _ in 0..10 {
+            let msg1 = thread_rng().gen_range(0..16);
+            let msg2 = thread_rng().gen_range(0..16);
+            let index = thread_rng().gen_range(0..TEST.poly_size.0);
+            let ct1 = LweCiphertext::encrypt(encode(msg1), &lwe_sk);
+            let ct2 = LweCiphertext::encrypt(encode(msg2), &lwe_sk);
+
+            // The identity packs the message into coefficient `index`.
+            let res = functional_keyswitch(std::slice::from_ref(&ct1), &[1], index, &fksk);
+            let decrypted: Vec<u8> = res.decrypt_poly(&glwe_sk).into_iter().map(decode).collect();
+            for (i, m) in decrypted.into_iter().enumerate() {
+                assert_eq!(m, if i == index { msg1 } else { 0 });
+            }
+
+            let sum = functional_keyswitch(&[ct1.clone(), ct2.clone()], &[1, 1], index, &fksk);
+            assert_eq!(
+                decode(sum.decrypt_poly(&glwe_sk)[index]),
+                (msg1 + msg2) % 16
+            );
+
+            // 3 * msg1 - msg2
+            let affine = functional_keyswitch(&[ct1, ct2], &[3, u64::MAX], 0, &fksk);
+            assert_eq!(
+                decode(affine.decrypt(&glwe_sk)),
+                (3 * msg1 + 16 - msg2) % 16
+            );
+        }
+    }
+
+    #[test]
+    fn test_pack_lwes_via_trace() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let auto_keys =
+            compute_automorphism_keys(&sk, DecompositionBaseLog(4), DecompositionLevelCount(10));
+        assert_eq!(auto_keys.len(), TEST.poly_size.0.ilog2() as usize);
+
+        for count in [1usize, 5, 8] {
+            let msgs: Vec<u8> = (0..count).map(|_| thread_rng().gen_range(0..16)).collect();
+            let cts: Vec<LweCiphertext> = msgs
+                .iter()
+                .map(|&m| LweCiphertext::encrypt(encode(m), &sk.recode()))
+                .collect();
+
+            let packed = pack_lwes_via_trace(&cts, &auto_keys);
+            let decrypted: Vec<u8> = packed.decrypt_poly(&sk).into_iter().map(decode).collect();
+
+            let stride = TEST.poly_size.0 / count.next_power_of_two();
+            for (i, m) in decrypted.into_iter().enumerate() {
+                let expected = if i % stride == 0 {
+                    msgs.get(i / stride).copied().unwrap_or(0)
+                } else {
+                    0
+                };
+                assert_eq!(m, expected, "coefficient {i} of {count} packed");
+            }
+        }
+    }
+
+    #[test]
+    fn test_glwe_keyswitch() {
+        let sk_a = keygen_for(&TEST, &mut thread_rng());
+        let params_b = Parameters {
+            glwe_k: GlweDimension(2),
+            ..TEST
+        };
+        let sk_b = keygen_for(&params_b, &mut thread_rng());
+        let ksk = compute_glwe_ksk(
+            &sk_a,
+            &sk_b,
+            DecompositionBaseLog(4),
+            DecompositionLevelCount(8),
+        );
+
+        let poly_size = TEST.poly_size.0;
+        let msgs: Vec<u8> = (0..16).collect();
+        let ct = GlweCiphertext::encrypt_poly(&encode_poly(&msgs, poly_size), &sk_a);
+        let switched = ct.keyswitch(&ksk);
+        assert_eq!(switched.mask.len(), 2);
+
+        let decrypted = decode_poly(&switched.decrypt_poly(&sk_b));
+        assert_eq!(decrypted[..16], msgs);
+        assert!(decrypted[16..].iter().all(|&m| m == 0));
+
+        // Under the input key, with its first polynomial, the phase is random.
+        let mut truncated = switched.clone();
+        truncated.mask.truncate(1);
+        assert_ne!(decode_poly(&truncated.decrypt_poly(&sk_a))[..16], msgs);
+    }
+
+    #[test]
+    fn test_pack_lwe_ciphertexts() {
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let pksk = compute_functional_ksk(&lwe_sk, &glwe_sk);
+
+        let msgs: Vec<u8> = (0..32).map(|_| thread_rng().gen_range(0..16)).collect();
+        let cts: Vec<LweCiphertext> = msgs
+            .iter()
+            .map(|&m| LweCiphertext::encrypt(encode(m), &lwe_sk))
+            .collect();
+        let packed = pack_lwe_ciphertexts(&cts, &pksk).unwrap();
+
+        let decrypted = decode_poly(&packed.decrypt_poly(&glwe_sk));
+        assert_eq!(decrypted[..32], msgs);
+        assert!(decrypted[32..].iter().all(|&m| m == 0));
+
+        let too_many = vec![cts[0].clone(); TEST.poly_size.0 + 1];
+        assert_eq!(
+            pack_lwe_ciphertexts(&too_many, &pksk).err(),
+            Some(TfheError::MessageOutOfRange {
+                msg: TEST.poly_size.0 as u64,
+                max: TEST.poly_size.0 as u64
+            })
+        );
+
+        let other_dim = LweCiphertext::zero(TEST.lwe_dim.0 + 1);
+        assert_eq!(
+            pack_lwe_ciphertexts(&[other_dim], &pksk).err(),
+            Some(TfheError::KeyMismatchDimension {
+                expected: TEST.lwe_dim.0,
+                found: TEST.lwe_dim.0 + 1
+            })
+        );
+
+        let mut empty = pksk.clone();
+        empty.keys.clear();
+        assert!(matches!(
+            pack_lwe_ciphertexts(&cts, &empty),
+            Err(TfheError::MalformedKey(_))
+        ));
+    }
+}
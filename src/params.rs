@@ -0,0 +1,526 @@
+use crate::noise::NoiseModel;
+use crate::security::lwe_security_bits;
+use serde::{Deserialize, Serialize};
+
+pub mod presets;
+
+/// Number of coefficients of the mask of an LWE ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LweDimension(pub usize);
+
+/// Number of polynomials of the mask of a GLWE ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GlweDimension(pub usize);
+
+/// Number of coefficients `N` of the polynomials of Z_{2^64}[X]/(X^N + 1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PolynomialSize(pub usize);
+
+/// lg(B) of a decomposition in base `B`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DecompositionBaseLog(pub usize);
+
+/// Number of levels of a decomposition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DecompositionLevelCount(pub usize);
+
+/// Set of parameters describing an instance of the scheme.
+/// Standard deviations are expressed with respect to the ciphertext modulus `q = 2^64`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameters {
+    /// Dimension of LWE ciphertexts.
+    pub lwe_dim: LweDimension,
+    /// GLWE dimension.
+    pub glwe_k: GlweDimension,
+    /// Degree `N` of the irreducible polynomial X^N + 1.
+    pub poly_size: PolynomialSize,
+    pub lwe_sigma: f64,
+    pub glwe_sigma: f64,
+    /// lg(B) of the keyswitching decomposition.
+    pub ks_base_log: DecompositionBaseLog,
+    pub ks_levels: DecompositionLevelCount,
+    /// lg(B) of the external product decomposition.
+    pub pbs_base_log: DecompositionBaseLog,
+    pub pbs_levels: DecompositionLevelCount,
+    /// Bits of the messages, at most 8. LUTs and boolean keys encode with it, see
+    /// `utils::encode_bits`; `utils::encode` and `utils::decode` are the 4-bit encoding of `P`.
+    pub message_bits: usize,
+    /// Key the ciphertexts are under between bootstraps.
+    pub pbs_order: PbsOrder,
+    /// Algorithm of the blind rotations, which determines the shape of the bootstrapping key.
+    pub blind_rotation: BlindRotationAlgorithm,
+}
+
+/// Order of the keyswitch and of the blind rotation in `ggsw::EvaluationKey::bootstrap`.
+/// In both orders the keyswitching key switches from the recoded GLWE key to the LWE key, and the
+/// noise at the input of a blind rotation is the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PbsOrder {
+    /// Ciphertexts are under the LWE key: a bootstrap blind rotates, then keyswitches back to it.
+    /// Linear operations act on the smaller ciphertexts.
+    BootstrapKeyswitch,
+    /// Ciphertexts are under the recoded GLWE key, of dimension `glwe_k * poly_size`: a bootstrap
+    /// keyswitches to the LWE key, then blind rotates back to the GLWE key. The keyswitch noise
+    /// does not reach the outputs of bootstraps.
+    KeyswitchBootstrap,
+}
+
+/// Blind rotation algorithm of `ggsw::EvaluationKey::bootstrap`. Both multiply the accumulator by
+/// `X^(a_i s_i)` for every mask coefficient `a_i`, switched to the modulus `2N`, and differ in how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlindRotationAlgorithm {
+    /// One CMUX per key coefficient, between the accumulator and its rotation by `a_i`.
+    /// The bootstrapping key holds a GGSW encryption of every key coefficient, and a blind
+    /// rotation costs `lwe_dim` external products. Keys must be binary, or ternary at twice the
+    /// key size and cost.
+    Ginx,
+    /// The rotation is an external product by a GGSW encryption of `X^(d B^j s_i)` for every
+    /// nonzero digit `d` of `a_i` in base `B = 2^base_log`. The bootstrapping key holds `B - 1`
+    /// such encryptions for each of the `ceil(lg(2N) / base_log)` digits of every key coefficient,
+    /// and a blind rotation costs an external product per nonzero digit, `(B - 1) / B` of them on
+    /// average. The noise grows with the number of external products. Keys may have any
+    /// distribution.
+    Ap { base_log: DecompositionBaseLog },
+}
+
+impl BlindRotationAlgorithm {
+    /// Number of digits of a mask coefficient modulo `2 * poly_size` with `Ap`, and `1` with
+    /// `Ginx`.
+    pub fn digits(&self, poly_size: PolynomialSize) -> usize {
+        match *self {
+            BlindRotationAlgorithm::Ginx => 1,
+            BlindRotationAlgorithm::Ap { base_log } => {
+                ((2 * poly_size.0).ilog2() as usize).div_ceil(base_log.0)
+            }
+        }
+    }
+}
+
+impl Parameters {
+    /// The parameters implied by the crate constants, see `presets::DEFAULT_4BIT`.
+    pub const DEFAULT: Parameters = presets::DEFAULT_4BIT;
+}
+
+impl Parameters {
+    /// Checks that `self` describes a usable instance of the scheme.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lwe_dim.0 == 0 || self.glwe_k.0 == 0 {
+            return Err("lwe_dim and glwe_k must be positive".to_string());
+        }
+        if !self.poly_size.0.is_power_of_two() || !(2..=1 << 62).contains(&self.poly_size.0) {
+            return Err(format!(
+                "poly_size must be a power of two between 2 and 2^62, got {}",
+                self.poly_size.0
+            ));
+        }
+        for (name, sigma) in [
+            ("lwe_sigma", self.lwe_sigma),
+            ("glwe_sigma", self.glwe_sigma),
+        ] {
+            if !(sigma.is_finite() && sigma >= 0.0) {
+                return Err(format!(
+                    "{name} must be finite and non-negative, got {sigma}"
+                ));
+            }
+        }
+        for (name, base_log, levels) in [
+            ("keyswitching", self.ks_base_log.0, self.ks_levels.0),
+            ("bootstrapping", self.pbs_base_log.0, self.pbs_levels.0),
+        ] {
+            if base_log == 0 || levels == 0 || base_log * levels >= 64 {
+                return Err(format!(
+                    "the {name} decomposition must have a positive base and number of levels \
+                     with base_log * levels < 64, got {base_log} * {levels}"
+                ));
+            }
+        }
+        if let BlindRotationAlgorithm::Ap {
+            base_log: DecompositionBaseLog(base_log),
+        } = self.blind_rotation
+        {
+            if !(1..=16).contains(&base_log) {
+                return Err(format!(
+                    "the AP blind rotation base_log must be between 1 and 16, got {base_log}"
+                ));
+            }
+        }
+        if !(1..=8).contains(&self.message_bits) {
+            return Err(format!(
+                "message_bits must be between 1 and 8, got {}",
+                self.message_bits
+            ));
+        }
+        Ok(())
+    }
+
+    /// Probability that decoding fails at the input of a bootstrap, for a ciphertext output by a
+    /// previous bootstrap and keyswitch.
+    pub fn pbs_failure_probability(&self) -> f64 {
+        NoiseModel::pbs(self)
+            .keyswitch(self)
+            .modswitch(self)
+            .decode_failure_probability(self.message_bits)
+    }
+
+    /// Parameters whose sampled noise is `factor` times larger: the standard deviations of
+    /// encryptions, and thus of the keys, are multiplied by `factor`. The rounding noise of
+    /// decompositions and modulus switches does not change. Raising noise until decoding fails
+    /// often enough to be counted validates the failure probabilities of `NoiseModel`.
+    /// Panics if `factor` is not finite and positive.
+    #[cfg(all(feature = "debug-tools", any(test, debug_assertions)))]
+    pub fn with_amplified_noise(&self, factor: f64) -> Self {
+        assert!(
+            factor.is_finite() && factor > 0.0,
+            "invalid amplification factor {factor}"
+        );
+        Parameters {
+            lwe_sigma: self.lwe_sigma * factor,
+            glwe_sigma: self.glwe_sigma * factor,
+            ..*self
+        }
+    }
+
+    /// Expected number of external products of a blind rotation with binary keys.
+    pub fn blind_rotation_products(&self) -> f64 {
+        match self.blind_rotation {
+            BlindRotationAlgorithm::Ginx => self.lwe_dim.0 as f64,
+            BlindRotationAlgorithm::Ap { base_log } => {
+                let base = (1u64 << base_log.0) as f64;
+                (self.lwe_dim.0 * self.blind_rotation.digits(self.poly_size)) as f64 * (base - 1.0)
+                    / base
+            }
+        }
+    }
+
+    /// Operation count of a bootstrap followed by a keyswitch, assuming FFT-based polynomial
+    /// products.
+    pub fn pbs_cost(&self) -> f64 {
+        let n = self.poly_size.0 as f64;
+        let glwe_k = self.glwe_k.0 as f64;
+        let blind_rotation = self.blind_rotation_products()
+            * (glwe_k + 1.0)
+            * (glwe_k + 1.0)
+            * self.pbs_levels.0 as f64
+            * n
+            * n.log2();
+        let keyswitch = glwe_k * n * self.ks_levels.0 as f64 * (self.lwe_dim.0 + 1) as f64;
+        blind_rotation + keyswitch
+    }
+}
+
+/// Requirements a parameter set found by `search` has to meet.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchConstraints {
+    /// Bits of the plaintext space, padding bit included, at most 8.
+    pub message_bits: usize,
+    /// Maximum probability of a decoding failure at the input of a bootstrap.
+    pub max_failure_probability: f64,
+    pub min_security_bits: f64,
+}
+
+/// Parameters found by `search`, along with their predicted properties.
+#[derive(Clone, Copy, Debug)]
+pub struct ParameterSet {
+    pub params: Parameters,
+    pub security_bits: f64,
+    pub failure_probability: f64,
+    pub cost: f64,
+}
+
+/// Dimensions explored by `search`. The defaults of the crate are part of the grid.
+const LWE_DIMS: std::ops::RangeInclusive<usize> = 500..=1100;
+const LWE_DIM_STEP: usize = 10;
+const GLWE_SHAPES: [(usize, usize); 5] = [(1, 512), (1, 1024), (1, 2048), (2, 512), (2, 1024)];
+
+/// Standard deviations explored by `search`, as 2^(x / 2) for x in this range.
+const HALF_LOG2_SIGMAS: std::ops::RangeInclusive<i32> = 2..=124;
+
+/// Decompositions explored by `search`: lg(B) and number of levels.
+const MAX_KS_BASE_LOG: usize = 8;
+const MAX_PBS_BASE_LOG: usize = 24;
+const MAX_LEVELS: usize = 8;
+
+/// Smallest standard deviation of the grid for which LWE of dimension `dim` meets `min_bits`,
+/// along with the estimated security.
+fn smallest_secure_sigma(dim: usize, min_bits: f64) -> Option<(f64, f64)> {
+    let sigmas: Vec<f64> = HALF_LOG2_SIGMAS
+        .map(|x| f64::powf(2.0, x as f64 / 2.0))
+        .collect();
+
+    // Security increases with the noise: binary search for the first secure standard deviation.
+    let i = sigmas.partition_point(|&sigma| lwe_security_bits(dim, 64, sigma) < min_bits);
+    sigmas
+        .get(i)
+        .map(|&sigma| (sigma, lwe_security_bits(dim, 64, sigma)))
+}
+
+/// Cheapest decompositions meeting the failure probability target for the given dimensions and
+/// standard deviations.
+fn cheapest_decompositions(base: Parameters, max_failure: f64) -> Option<Parameters> {
+    let mut best: Option<Parameters> = None;
+    for ks_base_log in 1..=MAX_KS_BASE_LOG {
+        for ks_levels in (1..=MAX_LEVELS).filter(|l| l * ks_base_log < 64) {
+            for pbs_base_log in 1..=MAX_PBS_BASE_LOG {
+                for pbs_levels in (1..=MAX_LEVELS).filter(|l| l * pbs_base_log < 64) {
+                    let params = Parameters {
+                        ks_base_log: DecompositionBaseLog(ks_base_log),
+                        ks_levels: DecompositionLevelCount(ks_levels),
+                        pbs_base_log: DecompositionBaseLog(pbs_base_log),
+                        pbs_levels: DecompositionLevelCount(pbs_levels),
+                        ..base
+                    };
+                    if params.pbs_failure_probability() <= max_failure
+                        && best.is_none_or(|best| params.pbs_cost() < best.pbs_cost())
+                    {
+                        best = Some(params);
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Explores a grid of dimensions, standard deviations and decompositions and returns the
+/// parameter sets meeting `constraints`, cheapest first.
+/// For each LWE dimension and GLWE shape, the smallest secure standard deviations and the cheapest
+/// decompositions meeting the failure probability target are selected.
+pub fn search(constraints: SearchConstraints) -> Result<Vec<ParameterSet>, String> {
+    if constraints.message_bits == 0 || constraints.message_bits > 8 {
+        return Err(format!(
+            "message_bits must be between 1 and 8, got {}",
+            constraints.message_bits
+        ));
+    }
+    if !(constraints.max_failure_probability > 0.0 && constraints.max_failure_probability < 1.0) {
+        return Err(format!(
+            "max_failure_probability must be in (0, 1), got {}",
+            constraints.max_failure_probability
+        ));
+    }
+
+    let glwe_sigmas: Vec<Option<(f64, f64)>> = GLWE_SHAPES
+        .iter()
+        .map(|(glwe_k, n)| smallest_secure_sigma(glwe_k * n, constraints.min_security_bits))
+        .collect();
+
+    let mut sets = vec![];
+    for lwe_dim in LWE_DIMS.step_by(LWE_DIM_STEP) {
+        let Some((lwe_sigma, lwe_bits)) =
+            smallest_secure_sigma(lwe_dim, constraints.min_security_bits)
+        else {
+            continue;
+        };
+
+        for (&(glwe_k, poly_size), glwe_sigma) in GLWE_SHAPES.iter().zip(&glwe_sigmas) {
+            let Some((glwe_sigma, glwe_bits)) = *glwe_sigma else {
+                continue;
+            };
+
+            let base = Parameters {
+                lwe_dim: LweDimension(lwe_dim),
+                glwe_k: GlweDimension(glwe_k),
+                poly_size: PolynomialSize(poly_size),
+                lwe_sigma,
+                glwe_sigma,
+                message_bits: constraints.message_bits,
+                ..Parameters::DEFAULT
+            };
+            if let Some(params) = cheapest_decompositions(base, constraints.max_failure_probability)
+            {
+                sets.push(ParameterSet {
+                    params,
+                    security_bits: lwe_bits.min(glwe_bits),
+                    failure_probability: params.pbs_failure_probability(),
+                    cost: params.pbs_cost(),
+                });
+            }
+        }
+    }
+
+    if sets.is_empty() {
+        return Err(format!(
+            "no parameter set reaches {} bits of security with a failure probability below {:e} \
+             for {}-bit messages: the noise of modulus switching alone is too large",
+            constraints.min_security_bits,
+            constraints.max_failure_probability,
+            constraints.message_bits
+        ));
+    }
+
+    sets.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+    Ok(sets)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TfheError;
+    use crate::ggsw::GgswCiphertext;
+    use crate::glwe::{keygen_for, GlweCiphertext};
+    use crate::lwe::{compute_ksk, lwe_keygen_for, LweCiphertext};
+    use crate::params::{
+        search, BlindRotationAlgorithm, DecompositionBaseLog, DecompositionLevelCount,
+        GlweDimension, LweDimension, Parameters, PbsOrder, PolynomialSize, SearchConstraints,
+    };
+    use crate::security::estimate_bits;
+    use crate::utils::{decode, encode};
+    use rand::thread_rng;
+
+    /// Smaller instance with k = 2 and decompositions differing from the default ones.
+    const SMALL: Parameters = Parameters {
+        lwe_dim: LweDimension(512),
+        glwe_k: GlweDimension(2),
+        poly_size: PolynomialSize(512),
+        lwe_sigma: (1u64 << 40) as f64,
+        glwe_sigma: (1u64 << 30) as f64,
+        ks_base_log: DecompositionBaseLog(3),
+        ks_levels: DecompositionLevelCount(5),
+        pbs_base_log: DecompositionBaseLog(6),
+        pbs_levels: DecompositionLevelCount(3),
+        message_bits: 4,
+        pbs_order: PbsOrder::BootstrapKeyswitch,
+        blind_rotation: BlindRotationAlgorithm::Ginx,
+    };
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Parameters::DEFAULT.validate(), Ok(()));
+        assert_eq!(SMALL.validate(), Ok(()));
+
+        let invalid = [
+            Parameters {
+                poly_size: PolynomialSize(1000),
+                ..SMALL
+            },
+            Parameters {
+                glwe_k: GlweDimension(0),
+                ..SMALL
+            },
+            Parameters {
+                lwe_sigma: f64::NAN,
+                ..SMALL
+            },
+            Parameters {
+                ks_base_log: DecompositionBaseLog(8),
+                ks_levels: DecompositionLevelCount(8),
+                ..SMALL
+            },
+            Parameters {
+                pbs_levels: DecompositionLevelCount(0),
+                ..SMALL
+            },
+            Parameters {
+                message_bits: 0,
+                ..SMALL
+            },
+            Parameters {
+                message_bits: 9,
+                ..SMALL
+            },
+        ];
+        for params in invalid {
+            assert!(params.validate().is_err(), "{params:?}");
+        }
+    }
+
+    #[test]
+    fn test_runtime_parameters() {
+        for params in [Parameters::DEFAULT, SMALL] {
+            let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+            let glwe_sk = keygen_for(&params, &mut thread_rng());
+            let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+
+            for msg in 0..16 {
+                let ct = LweCiphertext::encrypt(encode(msg), &lwe_sk);
+                assert_eq!(ct.mask.len(), params.lwe_dim.0);
+                let sum = ct.clone().add(ct);
+                assert_eq!(decode(sum.decrypt(&lwe_sk)), (2 * msg) % 16);
+
+                let ct = GlweCiphertext::encrypt(encode(msg), &glwe_sk);
+                assert_eq!(decode(ct.decrypt(&glwe_sk)), msg);
+
+                let extracted = ct.sample_extract();
+                assert_eq!(extracted.mask.len(), params.glwe_k.0 * params.poly_size.0);
+                assert_eq!(decode(extracted.clone().decrypt(&glwe_sk.recode())), msg);
+
+                let keyswitched = extracted.keyswitch(&ksk);
+                assert_eq!(decode(keyswitched.decrypt(&lwe_sk)), msg, "{params:?}");
+            }
+
+            for b in 0..2 {
+                let ggsw = GgswCiphertext::encrypt(b, &glwe_sk);
+                let ct = GlweCiphertext::encrypt(encode(7), &glwe_sk);
+                let res = ggsw.external_product(&ct);
+                assert_eq!(decode(res.decrypt(&glwe_sk)), 7 * b, "{params:?}");
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "dimensions differ")]
+    fn test_parameters_mismatch() {
+        let sk = lwe_keygen_for(&Parameters::DEFAULT, &mut thread_rng());
+        let other_sk = lwe_keygen_for(&SMALL, &mut thread_rng());
+
+        LweCiphertext::encrypt(encode(1), &sk).decrypt(&other_sk);
+    }
+
+    #[test]
+    fn test_try_decrypt_mismatch() {
+        let sk = lwe_keygen_for(&Parameters::DEFAULT, &mut thread_rng());
+        let other_sk = lwe_keygen_for(&SMALL, &mut thread_rng());
+        let ct = LweCiphertext::encrypt(encode(5), &sk);
+        assert_eq!(ct.clone().try_decrypt(&sk).map(decode), Ok(5));
+        assert!(ct.try_decrypt(&other_sk).is_err());
+
+        let glwe_sk = keygen_for(&Parameters::DEFAULT, &mut thread_rng());
+        let other_glwe_sk = keygen_for(&SMALL, &mut thread_rng());
+        let ct = GlweCiphertext::encrypt(encode(5), &glwe_sk);
+        assert_eq!(ct.try_decrypt(&glwe_sk).map(decode), Ok(5));
+        assert_eq!(
+            ct.try_decrypt(&other_glwe_sk),
+            Err(TfheError::KeyMismatchDimension {
+                expected: SMALL.glwe_k.0,
+                found: Parameters::DEFAULT.glwe_k.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_search_dominates_default() {
+        let default = Parameters::DEFAULT;
+        let constraints = SearchConstraints {
+            message_bits: default.message_bits,
+            max_failure_probability: default.pbs_failure_probability(),
+            min_security_bits: estimate_bits(&default),
+        };
+
+        let sets = search(constraints).unwrap();
+        for set in &sets {
+            assert!(set.security_bits >= constraints.min_security_bits);
+            assert!(set.failure_probability <= constraints.max_failure_probability);
+            assert!((estimate_bits(&set.params) - set.security_bits).abs() < 1e-9);
+        }
+        assert!(sets.windows(2).all(|w| w[0].cost <= w[1].cost));
+        assert!(sets[0].cost <= default.pbs_cost());
+    }
+
+    #[test]
+    fn test_search_infeasible() {
+        let constraints = SearchConstraints {
+            message_bits: 8,
+            max_failure_probability: f64::powi(2.0, -40),
+            min_security_bits: 128.0,
+        };
+        let err = search(constraints).unwrap_err();
+        assert!(err.contains("no parameter set"), "{err}");
+
+        let constraints = SearchConstraints {
+            max_failure_probability: 0.0,
+            ..constraints
+        };
+        assert!(search(constraints)
+            .unwrap_err()
+            .contains("max_failure_probability"));
+    }
+}
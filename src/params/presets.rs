@@ -0,0 +1,98 @@
+//! Named parameter sets. Security estimates are the ones of `security::estimate_bits` and failure
+//! probabilities the ones of `Parameters::pbs_failure_probability`; the tests below check that
+//! they still hold.
+
+use crate::params::{
+    BlindRotationAlgorithm, DecompositionBaseLog, DecompositionLevelCount, GlweDimension,
+    LweDimension, Parameters, PbsOrder, PolynomialSize,
+};
+use crate::{k, ELL, GLWE_STD_DEV, LWE_DIM, LWE_STD_DEV, N, P};
+
+/// Fast and insecure parameters, for tests.
+/// Hand-picked: the dimensions are as small as possible while keeping the noise far below the
+/// decoding bound. About 38 bits of security, PBS failure probability about 2^-125.
+pub const TOY: Parameters = Parameters {
+    lwe_dim: LweDimension(128),
+    glwe_k: GlweDimension(1),
+    poly_size: PolynomialSize(512),
+    lwe_sigma: (1u64 << 40) as f64,
+    glwe_sigma: (1u64 << 30) as f64,
+    ks_base_log: DecompositionBaseLog(4),
+    ks_levels: DecompositionLevelCount(4),
+    pbs_base_log: DecompositionBaseLog(8),
+    pbs_levels: DecompositionLevelCount(2),
+    message_bits: 4,
+    pbs_order: PbsOrder::BootstrapKeyswitch,
+    blind_rotation: BlindRotationAlgorithm::Ginx,
+};
+
+/// The parameters implied by the crate constants, which the const-based API and the known-answer
+/// vectors use. The dimensions and decompositions are the ones of the original implementation.
+/// About 128 bits of security, but a PBS failure probability of about 2^-8.6 due to keyswitching
+/// noise: about one bootstrap in 390 decodes to a wrong message. `SECURE_128_4BIT` stays below
+/// 2^-40.
+pub const DEFAULT_4BIT: Parameters = Parameters {
+    lwe_dim: LweDimension(LWE_DIM),
+    glwe_k: GlweDimension(k),
+    poly_size: PolynomialSize(N),
+    lwe_sigma: LWE_STD_DEV,
+    glwe_sigma: GLWE_STD_DEV,
+    ks_base_log: DecompositionBaseLog(4),
+    ks_levels: DecompositionLevelCount(4),
+    pbs_base_log: DecompositionBaseLog(8),
+    pbs_levels: DecompositionLevelCount(ELL),
+    message_bits: P.ilog2() as usize,
+    pbs_order: PbsOrder::BootstrapKeyswitch,
+    blind_rotation: BlindRotationAlgorithm::Ginx,
+};
+
+/// Cheapest parameter set found by `params::search` for 4-bit messages, at least 128 bits of
+/// security and a PBS failure probability below 2^-40
+/// (`cargo run --release --bin param-search -- 4 -40 128`).
+/// About 128.3 bits of security, PBS failure probability about 2^-42.2.
+pub const SECURE_128_4BIT: Parameters = Parameters {
+    lwe_dim: LweDimension(670),
+    glwe_k: GlweDimension(1),
+    poly_size: PolynomialSize(2048),
+    lwe_sigma: (1u64 << 48) as f64,
+    glwe_sigma: (1u64 << 12) as f64,
+    ks_base_log: DecompositionBaseLog(3),
+    ks_levels: DecompositionLevelCount(4),
+    pbs_base_log: DecompositionBaseLog(18),
+    pbs_levels: DecompositionLevelCount(1),
+    message_bits: 4,
+    pbs_order: PbsOrder::BootstrapKeyswitch,
+    blind_rotation: BlindRotationAlgorithm::Ginx,
+};
+
+/// Parameters the correctness tests run under: `TOY`, or `SECURE_128_4BIT` with the
+/// `secure-tests` feature.
+#[cfg(all(test, not(feature = "secure-tests")))]
+pub(crate) const TEST: Parameters = TOY;
+#[cfg(all(test, feature = "secure-tests"))]
+pub(crate) const TEST: Parameters = SECURE_128_4BIT;
+
+#[cfg(test)]
+mod tests {
+    use crate::params::presets::{DEFAULT_4BIT, SECURE_128_4BIT, TOY};
+    use crate::security::estimate_bits;
+
+    #[test]
+    fn test_presets_are_valid() {
+        for params in [TOY, DEFAULT_4BIT, SECURE_128_4BIT] {
+            assert_eq!(params.validate(), Ok(()), "{params:?}");
+        }
+    }
+
+    #[test]
+    fn test_documented_estimates() {
+        assert!(estimate_bits(&SECURE_128_4BIT) >= 128.0);
+        assert!(SECURE_128_4BIT.pbs_failure_probability() < f64::powi(2.0, -40));
+
+        assert!((estimate_bits(&DEFAULT_4BIT) - 128.0).abs() < 0.5);
+        assert!((DEFAULT_4BIT.pbs_failure_probability().log2() + 8.6).abs() < 0.5);
+
+        assert!((estimate_bits(&TOY) - 38.0).abs() < 1.0);
+        assert!(TOY.pbs_failure_probability() < f64::powi(2.0, -120));
+    }
+}
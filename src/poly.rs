@@ -1,78 +1,177 @@
+use crate::ntt::MAX_POLY_SIZE;
+use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+use crate::torus::Torus;
+use crate::utils::short_digest;
 use crate::N;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Index, IndexMut};
 
-/// Represents an element of Z_{q}\[X\]/(X^N + 1) with implicit q = 2^64.
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ResiduePoly {
-    pub coefs: Vec<u64>,
+/// Smallest polynomial size whose products use the number theoretic transforms, from which they
+/// beat the schoolbook product in `benches/poly_mul.rs`.
+pub const NTT_THRESHOLD: usize = 256;
+
+/// Represents an element of Z_{q}\[X\]/(X^N + 1) with implicit q = 2^BITS of the scalar `T`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ResiduePoly<T: Torus = u64> {
+    pub coefs: Vec<T>,
 }
 
-impl ResiduePoly {
+impl<T: Torus> ResiduePoly<T> {
     pub fn new() -> Self {
         ResiduePoly {
-            coefs: Vec::<u64>::with_capacity(N),
+            coefs: Vec::<T>::with_capacity(N),
         }
     }
 
-    pub fn add(&self, rhs: &ResiduePoly) -> Self {
-        let mut res = Self::default();
-        for i in 0..N {
+    /// The zero polynomial of size `poly_size`.
+    pub fn zero(poly_size: usize) -> Self {
+        ResiduePoly {
+            coefs: vec![T::default(); poly_size],
+        }
+    }
+
+    pub fn add(&self, rhs: &ResiduePoly<T>) -> Self {
+        let mut res = Self::zero(self.coefs.len());
+        for i in 0..self.coefs.len() {
             res.coefs[i] = self.coefs[i].wrapping_add(rhs.coefs[i]);
         }
         res
     }
 
-    pub fn add_assign(&mut self, rhs: &ResiduePoly) {
-        for i in 0..N {
+    pub fn add_assign(&mut self, rhs: &ResiduePoly<T>) {
+        for i in 0..self.coefs.len() {
             self.coefs[i] = self.coefs[i].wrapping_add(rhs.coefs[i]);
         }
     }
 
-    pub fn add_constant(&self, constant: u64) -> Self {
-        let mut res: ResiduePoly = self.clone();
+    pub fn add_constant(&self, constant: T) -> Self {
+        let mut res: ResiduePoly<T> = self.clone();
         res.coefs[0] = res.coefs[0].wrapping_add(constant);
         res
     }
 
-    pub fn add_constant_assign(&mut self, constant: u64) {
+    pub fn add_constant_assign(&mut self, constant: T) {
         self.coefs[0] = self.coefs[0].wrapping_add(constant);
     }
 
-    pub fn sub(&self, rhs: &ResiduePoly) -> Self {
-        let mut res = Self::default();
-        for i in 0..N {
+    pub fn sub(&self, rhs: &ResiduePoly<T>) -> Self {
+        let mut res = Self::zero(self.coefs.len());
+        for i in 0..self.coefs.len() {
             res.coefs[i] = self.coefs[i].wrapping_sub(rhs.coefs[i]);
         }
         res
     }
 
-    // TODO: use FFT for better performances
-    pub fn mul(&self, rhs: &ResiduePoly) -> Self {
-        let mut coefs = Vec::<u64>::with_capacity(N);
-        for i in 0..N {
-            let mut coef = 0u64;
-            for j in 0..i + 1 {
-                coef = coef.wrapping_add(self.coefs[j].wrapping_mul(rhs.coefs[i - j]));
-            }
-            for j in i + 1..N {
-                coef = coef.wrapping_sub(self.coefs[j].wrapping_mul(rhs.coefs[N - j + i]));
-            }
-            coefs.push(coef);
+    pub fn sub_assign(&mut self, rhs: &ResiduePoly<T>) {
+        for i in 0..self.coefs.len() {
+            self.coefs[i] = self.coefs[i].wrapping_sub(rhs.coefs[i]);
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        ResiduePoly {
+            coefs: self.coefs.iter().map(|c| c.wrapping_neg()).collect(),
+        }
+    }
+
+    /// Multiplies every coefficient by `constant`.
+    pub fn mul_constant(&self, constant: T) -> Self {
+        ResiduePoly {
+            coefs: self
+                .coefs
+                .iter()
+                .map(|c| c.wrapping_mul(constant))
+                .collect(),
+        }
+    }
+
+    pub fn mul(&self, rhs: &ResiduePoly<T>) -> Self {
+        let mut res = Self::zero(self.coefs.len());
+        res.add_mul_assign(self, rhs);
+        res
+    }
+
+    /// Product with the number theoretic transforms of `ntt`, whatever the size.
+    /// Panics if the size is not a power of two up to `ntt::MAX_POLY_SIZE`.
+    pub fn mul_ntt(&self, rhs: &ResiduePoly<T>) -> Self {
+        ResiduePoly {
+            coefs: T::negacyclic_mul(&self.coefs, &rhs.coefs),
+        }
+    }
+
+    /// Adds `lhs * rhs` to `self`, with the number theoretic transforms of `ntt` from
+    /// `NTT_THRESHOLD` coefficients on, and the schoolbook product below.
+    pub fn add_mul_assign(&mut self, lhs: &ResiduePoly<T>, rhs: &ResiduePoly<T>) {
+        let n = lhs.coefs.len();
+        if n >= NTT_THRESHOLD && n.is_power_of_two() && n <= MAX_POLY_SIZE {
+            measured!(PolyMul, {
+                for (coef, product) in self
+                    .coefs
+                    .iter_mut()
+                    .zip(T::negacyclic_mul(&lhs.coefs, &rhs.coefs))
+                {
+                    *coef = coef.wrapping_add(product);
+                }
+            })
+        } else {
+            self.add_mul_assign_schoolbook(lhs, rhs);
         }
-        ResiduePoly { coefs }
     }
 
-    /// Generates a residue polynomial with random coefficients in \[0..2^64)
+    /// Adds `lhs * rhs` to `self` with the quadratic schoolbook product, without allocating.
+    pub fn add_mul_assign_schoolbook(&mut self, lhs: &ResiduePoly<T>, rhs: &ResiduePoly<T>) {
+        measured!(PolyMul, {
+            let n = lhs.coefs.len();
+            for i in 0..n {
+                let mut coef = T::default();
+                for j in 0..i + 1 {
+                    coef = coef.wrapping_add(lhs.coefs[j].wrapping_mul(rhs.coefs[i - j]));
+                }
+                for j in i + 1..n {
+                    coef = coef.wrapping_sub(lhs.coefs[j].wrapping_mul(rhs.coefs[n - j + i]));
+                }
+                self.coefs[i] = self.coefs[i].wrapping_add(coef);
+            }
+        })
+    }
+
+    /// Generates a residue polynomial with random coefficients in \[0..2^BITS)
     pub fn get_random() -> Self {
-        let coefs = (0..N).map(|_| rand::random::<u64>()).collect();
+        Self::get_random_with(&mut thread_rng())
+    }
+
+    /// Generates a residue polynomial with random coefficients in \[0..2^BITS) drawn from `rng`
+    pub fn get_random_with<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self::random_with(N, rng)
+    }
+
+    /// Generates a residue polynomial of size `poly_size` with random coefficients in
+    /// \[0..2^BITS) drawn from `rng`
+    pub fn random_with<R: Rng + CryptoRng>(poly_size: usize, rng: &mut R) -> Self {
+        let coefs = (0..poly_size).map(|_| T::from_u64(rng.gen())).collect();
 
         Self { coefs }
     }
 
     /// Generates a residue polynomial with random coefficients in \[0..1\]
     pub fn get_random_bin() -> Self {
-        let coefs = (0..N).map(|_| thread_rng().gen_range(0..=1)).collect();
+        Self::get_random_bin_with(&mut thread_rng())
+    }
+
+    /// Generates a residue polynomial with random coefficients in \[0..1\] drawn from `rng`
+    pub fn get_random_bin_with<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self::random_bin_with(N, rng)
+    }
+
+    /// Generates a residue polynomial of size `poly_size` with random coefficients in \[0..1\]
+    /// drawn from `rng`
+    pub fn random_bin_with<R: Rng + CryptoRng>(poly_size: usize, rng: &mut R) -> Self {
+        let coefs = (0..poly_size)
+            .map(|_| T::from_u64(rng.gen_range(0..=1)))
+            .collect();
 
         Self { coefs }
     }
@@ -80,70 +179,235 @@ impl ResiduePoly {
     /// Multiplies the residue polynomial by X^{exponent} = X^{2N + exponent}.
     /// `exponent` is assumed to be reduced modulo 2N.
     pub fn multiply_by_monomial(&self, exponent: usize) -> Self {
-        let mut rotated_coefs = Vec::<u64>::with_capacity(N);
-
-        let reverse = exponent >= N;
-        let exponent = exponent % N;
-
-        for i in 0..N {
-            rotated_coefs.push({
-                if i < exponent {
-                    if reverse {
-                        self.coefs[i + N - exponent]
-                    } else {
-                        self.coefs[i + N - exponent].wrapping_neg()
-                    }
-                } else if reverse {
-                    self.coefs[i - exponent].wrapping_neg()
+        let mut res = Self::zero(self.coefs.len());
+        self.multiply_by_monomial_into(exponent, &mut res);
+        res
+    }
+
+    /// Multiplies the residue polynomial by X^{exponent}, for any exponent: since X^N = -1, it is
+    /// reduced modulo 2N, negative exponents dividing by a power of X.
+    pub fn mul_monomial(&self, exponent: i64) -> Self {
+        let two_n = 2 * self.coefs.len() as i64;
+        self.multiply_by_monomial(exponent.rem_euclid(two_n) as usize)
+    }
+
+    /// Negacyclic rotation of the coefficients by `k` positions, see `mul_monomial`.
+    pub fn rotate(&self, k: i64) -> Self {
+        self.mul_monomial(k)
+    }
+
+    /// Writes `self * X^{exponent}` into `out`, reusing its buffer.
+    pub fn multiply_by_monomial_into(&self, exponent: usize, out: &mut ResiduePoly<T>) {
+        let n = self.coefs.len();
+        out.coefs.resize(n, T::default());
+
+        let reverse = exponent >= n;
+        let exponent = exponent % n;
+
+        for (i, coef) in out.coefs.iter_mut().enumerate() {
+            *coef = if i < exponent {
+                if reverse {
+                    self.coefs[i + n - exponent]
                 } else {
-                    self.coefs[i - exponent]
+                    self.coefs[i + n - exponent].wrapping_neg()
                 }
-            })
+            } else if reverse {
+                self.coefs[i - exponent].wrapping_neg()
+            } else {
+                self.coefs[i - exponent]
+            };
         }
+    }
 
-        ResiduePoly {
-            coefs: rotated_coefs,
+    /// Coefficient-wise gadget decomposition, see `Torus::decompose`: `levels` digit
+    /// polynomials, from the least significant level.
+    pub fn decompose(
+        &self,
+        base_log: DecompositionBaseLog,
+        levels: DecompositionLevelCount,
+    ) -> Vec<ResiduePoly<T>> {
+        let mut digits = vec![ResiduePoly::zero(self.coefs.len()); levels.0];
+        for (i, coef) in self.coefs.iter().enumerate() {
+            for (digit, d) in digits.iter_mut().zip(coef.decompose(base_log, levels)) {
+                digit.coefs[i] = d;
+            }
         }
+        digits
+    }
+
+    /// Applies the automorphism X -> X^g, for an odd `g`.
+    pub fn automorphism(&self, g: usize) -> Self {
+        let n = self.coefs.len();
+        let mut coefs = vec![T::default(); n];
+        for (i, coef) in self.coefs.iter().enumerate() {
+            let exponent = (i * g) % (2 * n);
+            if exponent < n {
+                coefs[exponent] = *coef;
+            } else {
+                coefs[exponent - n] = coef.wrapping_neg();
+            }
+        }
+        ResiduePoly { coefs }
     }
 }
 
-impl Default for ResiduePoly {
-    fn default() -> Self {
+impl ResiduePoly {
+    /// Copy of `self` on the torus of scalars `T`, for polynomials of small integers such as
+    /// secret keys: the coefficients `-1`, `0` and `1` keep their values.
+    pub fn to_torus<T: Torus>(&self) -> ResiduePoly<T> {
         ResiduePoly {
-            coefs: vec![0u64; N],
+            coefs: self.coefs.iter().map(|&c| T::from_u64(c)).collect(),
         }
     }
 }
 
+impl<T: Torus> Index<usize> for ResiduePoly<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        &self.coefs[i]
+    }
+}
+
+impl<T: Torus> IndexMut<usize> for ResiduePoly<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        &mut self.coefs[i]
+    }
+}
+
+/// Prints the size and a digest of the coefficients, which may be those of a secret key.
+impl<T: Torus> fmt::Debug for ResiduePoly<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefs: Vec<u64> = self.coefs.iter().map(|c| c.to_u64()).collect();
+        f.debug_struct("ResiduePoly")
+            .field("poly_size", &self.coefs.len())
+            .field("digest", &format_args!("{}", short_digest(&coefs)))
+            .finish()
+    }
+}
+
+impl<T: Torus> Default for ResiduePoly<T> {
+    fn default() -> Self {
+        ResiduePoly::zero(N)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use rand::{thread_rng, Rng};
+    /// Tests of the polynomial operations, instantiated for every scalar.
+    macro_rules! torus_tests {
+        ($name:ident, $t:ty) => {
+            mod $name {
+                use rand::{thread_rng, Rng};
 
-    use crate::{poly::ResiduePoly, N};
+                use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+                use crate::torus::Torus;
+                use crate::utils::recompose;
+                use crate::{poly::ResiduePoly, N};
 
-    #[test]
-    /// Tests that the monomial multiplication is coherent with monomial multiplication.
-    fn test_monomial_mult() {
-        for _ in 0..1000 {
-            let mut monomial_coefs = vec![0u64; N];
-            let monomial_non_null_term = thread_rng().gen_range(0..2 * N);
+                type Poly = ResiduePoly<$t>;
 
-            if monomial_non_null_term < 1024 {
-                monomial_coefs[monomial_non_null_term] = 1;
-            } else {
-                monomial_coefs[monomial_non_null_term % 1024] = 1u64.wrapping_neg();
-            }
+                #[test]
+                fn test_decompose() {
+                    let poly = Poly::random_with(64, &mut thread_rng());
+                    let (base_log, levels) = (DecompositionBaseLog(8), DecompositionLevelCount(2));
+                    let digits = poly.decompose(base_log, levels);
+                    assert_eq!(digits.len(), 2);
+                    for (i, coef) in poly.coefs.iter().enumerate() {
+                        let coef_digits: Vec<$t> =
+                            digits.iter().map(|digit| digit.coefs[i]).collect();
+                        assert_eq!(coef_digits, coef.decompose(base_log, levels));
+                        // The digits are signed: they are sign-extended to recompose on a `u64`.
+                        let signed: Vec<u64> = coef_digits
+                            .iter()
+                            .map(|d| ((d.to_msb() as i64) >> (64 - <$t>::BITS)) as u64)
+                            .collect();
+                        let error = recompose(&signed, base_log).wrapping_sub(coef.to_msb()) as i64;
+                        assert!(error.unsigned_abs() <= 1 << 47);
+                    }
+                }
 
-            let monomial = ResiduePoly {
-                coefs: monomial_coefs,
-            };
+                #[test]
+                /// Tests that the monomial multiplication is coherent with monomial multiplication.
+                fn test_monomial_mult() {
+                    for _ in 0..1000 {
+                        let mut monomial_coefs = vec![0; N];
+                        let monomial_non_null_term = thread_rng().gen_range(0..2 * N);
 
-            let polynomial = ResiduePoly::get_random();
+                        if monomial_non_null_term < N {
+                            monomial_coefs[monomial_non_null_term] = 1;
+                        } else {
+                            monomial_coefs[monomial_non_null_term % N] = <$t>::MAX;
+                        }
 
-            let res_mul = polynomial.mul(&monomial);
-            let res_monomial_mul = polynomial.multiply_by_monomial(monomial_non_null_term);
+                        let monomial = Poly {
+                            coefs: monomial_coefs,
+                        };
 
-            assert_eq!(res_mul.coefs, res_monomial_mul.coefs);
-        }
+                        let polynomial = Poly::get_random();
+
+                        let res_mul = polynomial.mul(&monomial);
+                        let res_monomial_mul =
+                            polynomial.multiply_by_monomial(monomial_non_null_term);
+
+                        assert_eq!(res_mul.coefs, res_monomial_mul.coefs);
+                    }
+                }
+
+                #[test]
+                fn test_mul_monomial_identities() {
+                    let n = 64;
+                    let poly = Poly::random_with(n, &mut thread_rng());
+                    let n_i64 = n as i64;
+
+                    assert_eq!(poly.mul_monomial(n_i64), poly.neg());
+                    assert_eq!(poly.mul_monomial(2 * n_i64), poly);
+                    assert_eq!(poly.mul_monomial(-1), poly.mul_monomial(2 * n_i64 - 1));
+                    assert_eq!(poly.rotate(3), poly.mul_monomial(3));
+                    for a in [0, 1, n_i64 - 1, n_i64, 2 * n_i64, 3 * n_i64 + 5, -7] {
+                        assert_eq!(poly.mul_monomial(a).mul_monomial(-a), poly, "X^{a}");
+                    }
+
+                    // X^1 shifts the coefficients up, the top one wrapping around negated.
+                    let shifted = poly.mul_monomial(1);
+                    assert_eq!(shifted[0], poly[n - 1].wrapping_neg());
+                    assert_eq!(shifted[1], poly[0]);
+                }
+
+                #[test]
+                fn test_coefficient_wise_operations() {
+                    let lhs = Poly::random_with(64, &mut thread_rng());
+                    let rhs = Poly::random_with(64, &mut thread_rng());
+
+                    assert_eq!(lhs.add(&lhs.neg()), Poly::zero(64));
+                    let mut diff = lhs.clone();
+                    diff.sub_assign(&rhs);
+                    assert_eq!(diff, lhs.sub(&rhs));
+                    assert_eq!(lhs.mul_constant(3), lhs.add(&lhs).add(&lhs));
+                    let mut constant = Poly::zero(64);
+                    constant[0] = <$t>::MAX;
+                    assert_eq!(lhs.mul_constant(<$t>::MAX), lhs.mul(&constant));
+                }
+
+                #[test]
+                /// Tests that automorphisms are ring homomorphisms mapping X to X^g.
+                fn test_automorphism() {
+                    for g in [1, 3, 5, 2 * N - 1] {
+                        let x = Poly::zero(N).add_constant(1).multiply_by_monomial(1);
+                        assert_eq!(x.automorphism(g).coefs, x.multiply_by_monomial(g - 1).coefs);
+
+                        let a = Poly::get_random();
+                        let b = Poly::get_random();
+                        assert_eq!(
+                            a.mul(&b).automorphism(g).coefs,
+                            a.automorphism(g).mul(&b.automorphism(g)).coefs
+                        );
+                    }
+                }
+            }
+        };
     }
+
+    torus_tests!(u32_backend, u32);
+    torus_tests!(u64_backend, u64);
 }
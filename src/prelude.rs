@@ -0,0 +1,35 @@
+//! Common types, traits and functions, for a single import:
+//!
+//! ```
+//! use ttfhe::prelude::*;
+//!
+//! let lwe_sk = lwe_keygen_for(&presets::TOY, &mut rand::thread_rng());
+//! let ct = LweCiphertext::encrypt(encode(3), &lwe_sk);
+//! assert_eq!(decode(ct.decrypt(&lwe_sk)), 3);
+//! ```
+//!
+//! Dimensions and decompositions have their own types, so that they cannot be swapped:
+//!
+//! ```compile_fail
+//! use ttfhe::prelude::*;
+//! use ttfhe::utils::decompose_rounded;
+//!
+//! let (base_log, levels) = (DecompositionBaseLog(4), DecompositionLevelCount(4));
+//! decompose_rounded(0, levels, base_log);
+//! ```
+
+pub use crate::error::TfheError;
+pub use crate::ggsw::{compute_bsk, BootstrappingKey, EvaluationKey, GgswCiphertext};
+pub use crate::glwe::{keygen_for, GlweCiphertext, SecretKey};
+pub use crate::key_provider::{Decryptor, KeyProvider};
+pub use crate::lut::Lut;
+pub use crate::lwe::{compute_ksk, lwe_keygen_for, KeySwitchingKey, LweCiphertext, LweSecretKey};
+pub use crate::params::{
+    presets, BlindRotationAlgorithm, DecompositionBaseLog, DecompositionLevelCount, GlweDimension,
+    LweDimension, Parameters, PbsOrder, PolynomialSize,
+};
+pub use crate::serialization::Serializable;
+pub use crate::size::SizeInfo;
+pub use crate::utils::{
+    decode, decode_checked, decode_poly, encode, encode_checked, encode_poly, try_encode,
+};
@@ -0,0 +1,128 @@
+//! Public-key encryption, for third parties encrypting toward the key holder.
+//!
+//! The public key is a list of `m` encryptions of zero under the secret key. An encryption with
+//! the public key sums a random subset of them, which is an encryption of zero with a fresh-looking
+//! mask, and adds the message and a fresh Gaussian noise to its body. The noise is that of about
+//! `m / 2` encryptions of zero, plus the fresh one.
+//!
+//! The subset sums only hide which encryptions of zero were picked, and thus the message, if `m`
+//! is large enough for the leftover hash lemma: about `(dim + 1) * 64` encryptions plus a security
+//! margin, for a 64-bit modulus. Smaller keys are accepted, for tests and experiments.
+
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::utils::sample_gaussian;
+use rand::{thread_rng, CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+
+/// Encryptions of zero with which anyone can encrypt under a LWE secret key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LwePublicKey {
+    pub zeros: Vec<LweCiphertext>,
+    /// Standard deviation of the fresh noise of an encryption.
+    pub noise_sigma: f64,
+    /// Dimension of the ciphertexts, which does not depend on `zeros` being non-empty.
+    dim: usize,
+}
+
+/// Computes the public key of `sk` made of `m` encryptions of zero.
+/// Panics if `m` is zero.
+pub fn compute_public_key(sk: &LweSecretKey, m: usize) -> LwePublicKey {
+    compute_public_key_with(sk, m, &mut thread_rng())
+}
+
+/// Computes the public key of `sk` made of `m` encryptions of zero, drawing the randomness from
+/// `rng`.
+pub fn compute_public_key_with<R: Rng + CryptoRng>(
+    sk: &LweSecretKey,
+    m: usize,
+    rng: &mut R,
+) -> LwePublicKey {
+    assert!(m > 0, "a public key needs at least one encryption of zero");
+
+    LwePublicKey {
+        zeros: (0..m)
+            .map(|_| LweCiphertext::encrypt_with(0, sk, rng))
+            .collect(),
+        noise_sigma: sk.std_dev(),
+        dim: sk.coefs.len(),
+    }
+}
+
+impl LwePublicKey {
+    /// Dimension of the ciphertexts.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Encrypts `mu` as a random subset sum of the encryptions of zero of `self`, drawing the
+    /// subset and the noise from `rng`.
+    /// Panics if the encryptions of zero of `self` are not of dimension `dim()`.
+    pub fn encrypt_with<R: Rng + CryptoRng>(&self, mu: u64, rng: &mut R) -> LweCiphertext {
+        let mut ct = LweCiphertext::zero(self.dim());
+        for zero in &self.zeros {
+            if rng.gen::<bool>() {
+                ct += zero;
+            }
+        }
+
+        let e = sample_gaussian(self.noise_sigma, rng);
+        ct.add_plaintext(mu.wrapping_add_signed(e))
+    }
+}
+
+impl LweCiphertext {
+    /// Encrypts `mu` under the secret key of `pk`, see `LwePublicKey::encrypt_with`.
+    pub fn encrypt_with_pk(mu: u64, pk: &LwePublicKey) -> LweCiphertext {
+        pk.encrypt_with(mu, &mut thread_rng())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lwe::{lwe_keygen_for, LweCiphertext};
+    use crate::params::presets::TEST;
+    use crate::public_key::compute_public_key;
+    use crate::utils::{decode, encode};
+    use rand::{thread_rng, Rng};
+
+    const NUM_ZEROS: usize = 1_000;
+
+    #[test]
+    fn test_encrypt_with_pk() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let pk = compute_public_key(&sk, NUM_ZEROS);
+
+        for msg in 0..16 {
+            let ct = LweCiphertext::encrypt_with_pk(encode(msg), &pk);
+            assert_eq!(decode(ct.clone().decrypt(&sk)), msg);
+            assert!(!ct.is_trivial());
+
+            // With a ciphertext encrypted under the secret key.
+            let sum = ct + LweCiphertext::encrypt(encode(3), &sk);
+            assert_eq!(decode(sum.decrypt(&sk)), (msg + 3) % 16);
+        }
+    }
+
+    #[test]
+    fn test_dim_without_zeros() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let mut pk = compute_public_key(&sk, 1);
+        pk.zeros.clear();
+
+        assert_eq!(pk.dim(), TEST.lwe_dim.0);
+        let ct = pk.encrypt_with(encode(3), &mut thread_rng());
+        assert_eq!(ct.mask.len(), TEST.lwe_dim.0);
+    }
+
+    #[test]
+    fn test_no_failures() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let pk = compute_public_key(&sk, NUM_ZEROS);
+
+        for _ in 0..1_000 {
+            let msg = thread_rng().gen_range(0..16);
+            let ct = LweCiphertext::encrypt_with_pk(encode(msg), &pk);
+            assert_eq!(decode(ct.decrypt(&sk)), msg);
+        }
+    }
+}
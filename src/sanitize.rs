@@ -0,0 +1,215 @@
+//! Noise flooding, for circuit privacy.
+//!
+//! The mask and the noise of a computed ciphertext depend on the operations that produced it, so
+//! returning it as is may leak the circuit to the key holder. Sanitizing re-randomizes the mask by
+//! adding a random subset of encryptions of zero provided by the key holder, and adds to the body
+//! a fresh Gaussian noise of standard deviation `flood_sigma`.
+//!
+//! The subset sum hides the mask by the leftover hash lemma, given `(dim + 1) * 64` encryptions of
+//! zero as for `public_key`. If `flood_sigma` is `2^r` times the noise of the computation, two
+//! noises of the computation become indistinguishable after flooding up to a statistical distance
+//! of about `2^-r`, which is only negligible for `r` of `STATISTICAL_SECURITY_BITS` or more.
+//!
+//! The flood consumes correctness budget: the sum of the computation noise, of the noise of the
+//! encryptions of zero and of the flood must still decode with a failure probability below
+//! `MAX_FAILURE_PROBABILITY`. With 4-bit messages on 64 bits, this leaves about 16 bits for the
+//! noise of a bootstrap followed by a keyswitch, far below the noise of the presets of the crate:
+//! their keys cannot be sanitized, and circuit privacy needs parameters with much smaller noise.
+
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::noise::NoiseModel;
+use crate::params::PbsOrder;
+use crate::utils::sample_gaussian;
+use rand::{thread_rng, CryptoRng, Rng};
+
+/// Maximum probability that a sanitized output of a bootstrap fails to decode.
+pub const MAX_FAILURE_PROBABILITY: f64 = 1.0 / (1u64 << 40) as f64;
+
+/// Minimum `log2` of the ratio of the flood to the noise of a bootstrap.
+pub const STATISTICAL_SECURITY_BITS: i32 = 40;
+
+/// Encryptions of zero and flooding level with which a server sanitizes its outputs.
+#[derive(Clone)]
+pub struct SanitizationKey {
+    /// `(dim + 1) * 64` encryptions of zero under the key of the ciphertexts.
+    pub zeros: Vec<LweCiphertext>,
+    pub flood_sigma: f64,
+}
+
+/// Computes the sanitization key of the ciphertexts under `sk`, which are outputs of bootstraps.
+/// Errors if `flood_sigma` is not `2^STATISTICAL_SECURITY_BITS` times the noise of a bootstrap,
+/// which it would not drown, or if the sanitized outputs of bootstraps would fail to decode with a probability above
+/// `MAX_FAILURE_PROBABILITY`.
+pub fn compute_sanitization_key(
+    sk: &LweSecretKey,
+    flood_sigma: f64,
+) -> Result<SanitizationKey, String> {
+    compute_sanitization_key_with(sk, flood_sigma, &mut thread_rng())
+}
+
+/// Computes the sanitization key of the ciphertexts under `sk`, drawing the randomness from `rng`.
+pub fn compute_sanitization_key_with<R: Rng + CryptoRng>(
+    sk: &LweSecretKey,
+    flood_sigma: f64,
+    rng: &mut R,
+) -> Result<SanitizationKey, String> {
+    let params = &sk.params;
+    let computation = match params.pbs_order {
+        PbsOrder::BootstrapKeyswitch => NoiseModel::pbs(params).keyswitch(params),
+        PbsOrder::KeyswitchBootstrap => NoiseModel::pbs(params),
+    };
+    let min_flood_sigma = computation.std_dev() * 2f64.powi(STATISTICAL_SECURITY_BITS);
+    if !(flood_sigma.is_finite() && flood_sigma >= min_flood_sigma) {
+        return Err(format!(
+            "a flood of standard deviation 2^{:.1} does not drown the noise of a bootstrap, 2^{:.1}, \
+             by a factor 2^{STATISTICAL_SECURITY_BITS}",
+            flood_sigma.log2(),
+            computation.std_dev().log2()
+        ));
+    }
+
+    let num_zeros = (sk.coefs.len() + 1) * 64;
+    let sanitized = NoiseModel {
+        variance: computation.variance
+            + flood_sigma * flood_sigma
+            + num_zeros as f64 / 2.0 * sk.std_dev() * sk.std_dev(),
+    };
+    let failure = sanitized.decode_failure_probability(params.message_bits);
+    if failure > MAX_FAILURE_PROBABILITY {
+        return Err(format!(
+            "sanitized ciphertexts would fail to decode with probability 2^{:.1}",
+            failure.log2()
+        ));
+    }
+
+    Ok(SanitizationKey {
+        zeros: (0..num_zeros)
+            .map(|_| LweCiphertext::encrypt_with(0, sk, rng))
+            .collect(),
+        flood_sigma,
+    })
+}
+
+impl SanitizationKey {
+    pub fn sanitize(&self, ct: &LweCiphertext) -> LweCiphertext {
+        self.sanitize_with(ct, &mut thread_rng())
+    }
+
+    /// Adds to `ct` a random subset of the encryptions of zero of `self`, and a Gaussian noise of
+    /// standard deviation `flood_sigma` to its body. The message is unchanged.
+    /// Panics if `ct` is not under the key of `self`.
+    pub fn sanitize_with<R: Rng + CryptoRng>(
+        &self,
+        ct: &LweCiphertext,
+        rng: &mut R,
+    ) -> LweCiphertext {
+        assert_eq!(
+            ct.mask.len(),
+            self.zeros[0].mask.len(),
+            "ciphertext and sanitization key dimensions differ"
+        );
+
+        let mut res = self
+            .zeros
+            .iter()
+            .filter(|_| rng.gen::<bool>())
+            .fold(ct.clone(), |acc, zero| acc.add(zero.clone()));
+
+        res.body = res
+            .body
+            .wrapping_add_signed(sample_gaussian(self.flood_sigma, rng));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::lwe::{lwe_keygen_for, LweCiphertext};
+    use crate::params::presets::TOY;
+    use crate::params::{DecompositionBaseLog, DecompositionLevelCount, Parameters};
+    use crate::sanitize::compute_sanitization_key;
+    use crate::utils::{decode, encode};
+    use rand::thread_rng;
+
+    /// Insecure parameters whose bootstraps leave room for the flood: unit noise and
+    /// decompositions of 63 bits, for a noise of about `2^12` after a bootstrap and a keyswitch.
+    const ROOMY: Parameters = Parameters {
+        lwe_sigma: 1.0,
+        glwe_sigma: 1.0,
+        ks_base_log: DecompositionBaseLog(3),
+        ks_levels: DecompositionLevelCount(21),
+        pbs_base_log: DecompositionBaseLog(3),
+        pbs_levels: DecompositionLevelCount(21),
+        ..TOY
+    };
+
+    const FLOOD_SIGMA: f64 = (1u64 << 53) as f64;
+
+    #[test]
+    fn test_sanitize() {
+        let keys = TestKeys::new(&ROOMY);
+        let key = keys.evaluation_key();
+        let sanitization_key = compute_sanitization_key(&keys.lwe_sk, FLOOD_SIGMA).unwrap();
+
+        for msg in [0, 5, 13] {
+            let ct = key.bootstrap(
+                &LweCiphertext::encrypt(encode(msg / 2), &keys.lwe_sk),
+                |m| 2 * m + msg % 2,
+            );
+            let sanitized1 = sanitization_key.sanitize(&ct);
+            let sanitized2 = sanitization_key.sanitize(&ct);
+            assert_eq!(decode(sanitized1.clone().decrypt(&keys.lwe_sk)), msg);
+            assert_eq!(decode(sanitized2.clone().decrypt(&keys.lwe_sk)), msg);
+
+            // Unlinkable to the input and to each other.
+            assert_ne!(sanitized1.mask, ct.mask);
+            assert_ne!(sanitized1.mask, sanitized2.mask);
+        }
+    }
+
+    #[test]
+    fn test_flood_variance() {
+        let sk = lwe_keygen_for(&ROOMY, &mut thread_rng());
+        let sanitization_key = compute_sanitization_key(&sk, FLOOD_SIGMA).unwrap();
+
+        let samples = 5_000;
+        let errors: Vec<f64> = (0..samples)
+            .map(|_| {
+                let ct = LweCiphertext::encrypt(encode(3), &sk);
+                sanitization_key
+                    .sanitize(&ct)
+                    .decrypt(&sk)
+                    .wrapping_sub(encode(3)) as i64 as f64
+            })
+            .collect();
+        let variance = errors.iter().map(|e| e * e).sum::<f64>() / samples as f64;
+
+        // The flood dominates the noise of the encryptions of zero and of the input.
+        let expected = FLOOD_SIGMA * FLOOD_SIGMA;
+        assert!(
+            (variance - expected).abs() < 0.1 * expected,
+            "expected {expected:e}, measured {variance:e}"
+        );
+    }
+
+    #[test]
+    fn test_rejected_floods() {
+        let sk = lwe_keygen_for(&ROOMY, &mut thread_rng());
+
+        let err = compute_sanitization_key(&sk, (1u64 << 50) as f64)
+            .err()
+            .unwrap();
+        assert!(err.contains("does not drown"), "{err}");
+        let err = compute_sanitization_key(&sk, (1u64 << 57) as f64)
+            .err()
+            .unwrap();
+        assert!(err.contains("fail to decode"), "{err}");
+
+        // The noise of the bootstraps of the toy parameters leaves no room for the flood.
+        let sk = lwe_keygen_for(&TOY, &mut thread_rng());
+        for log2_flood in [55, 62] {
+            assert!(compute_sanitization_key(&sk, 2f64.powi(log2_flood)).is_err());
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Estimation of the security level of the LWE and GLWE problems underlying a parameter set.
+//!
+//! The estimate follows the primal uSVP attack of [[ADPS16]](https://eprint.iacr.org/2015/1092):
+//! an instance with `m` samples is embedded in a lattice of dimension `d = m + n + 1` and BKZ
+//! with block size `β` recovers the secret when `σ·sqrt(β) <= δ(β)^(2β - d - 1) · vol^(1/d)`.
+//! The binary secret is accounted for by rescaling it to the size of the noise (Bai-Galbraith
+//! embedding), and the cost of BKZ-`β` is the sieving estimate `0.292β + 16.4 + lg(8d)` used by the
+//! lattice estimator. With this model, the parameters of the Homomorphic Encryption Standard land
+//! within a few bits of their claimed 128-bit level.
+//!
+//! Limitations: only the primal attack is considered, so hybrid, dual and combinatorial attacks
+//! exploiting the small secret can make the real security level lower. Quantum speedups are not
+//! modeled either. Use the numbers to rule out weak parameters, not to certify strong ones.
+
+use crate::params::Parameters;
+use std::f64::consts::{E, PI};
+
+/// Smallest and largest BKZ block sizes considered.
+const MIN_BLOCK_SIZE: usize = 40;
+const MAX_BLOCK_SIZE: usize = 4096;
+
+/// Standard deviation of the coefficients of a uniform binary secret.
+const BINARY_SECRET_STD_DEV: f64 = 0.5;
+
+/// Root-Hermite factor achieved by BKZ with block size `beta`.
+fn root_hermite_factor(beta: usize) -> f64 {
+    let beta = beta as f64;
+    ((PI * beta).powf(1.0 / beta) * beta / (2.0 * PI * E)).powf(1.0 / (2.0 * (beta - 1.0)))
+}
+
+/// Estimated security in bits of LWE with a binary secret of dimension `dimension`, modulus
+/// `2^log2_modulus` and Gaussian noise of standard deviation `std_dev`.
+pub fn lwe_security_bits(dimension: usize, log2_modulus: u32, std_dev: f64) -> f64 {
+    if std_dev < 1.0 {
+        // Noise rounds to zero: the secret is recovered by linear algebra.
+        return 0.0;
+    }
+
+    let n = dimension as f64;
+    let log2_q = log2_modulus as f64;
+    let log2_sigma = std_dev.log2();
+    let log2_scaling = (std_dev / BINARY_SECRET_STD_DEV).log2();
+
+    for beta in MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE {
+        let log2_delta = root_hermite_factor(beta).log2();
+        let lhs = log2_sigma + 0.5 * (beta as f64).log2();
+
+        for m in 1..=2 * dimension {
+            let d = (m + dimension + 1) as f64;
+            let log2_volume = m as f64 * log2_q + n * log2_scaling;
+            let rhs = (2.0 * beta as f64 - d - 1.0) * log2_delta + log2_volume / d;
+            if lhs <= rhs {
+                return 0.292 * beta as f64 + 16.4 + (8.0 * d).log2();
+            }
+        }
+    }
+
+    f64::INFINITY
+}
+
+/// Estimated security in bits of `params`: the minimum over its LWE and GLWE instances.
+/// A GLWE instance of dimension `k` and degree `N` is estimated as an LWE instance of dimension `kN`.
+pub fn estimate_bits(params: &Parameters) -> f64 {
+    let lwe = lwe_security_bits(params.lwe_dim.0, 64, params.lwe_sigma);
+    let glwe = lwe_security_bits(params.glwe_k.0 * params.poly_size.0, 64, params.glwe_sigma);
+    lwe.min(glwe)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::params::{LweDimension, Parameters};
+    use crate::security::{estimate_bits, lwe_security_bits};
+
+    /// Estimates must land within this many bits of the levels claimed in the literature.
+    const TOLERANCE: f64 = 5.0;
+
+    #[test]
+    fn test_he_standard() {
+        // 128-bit parameters of the Homomorphic Encryption Standard (sigma = 3.19).
+        for (n, log2_q) in [(1024, 27), (2048, 54), (4096, 109)] {
+            let bits = lwe_security_bits(n, log2_q, 3.19);
+            assert!((bits - 128.0).abs() < TOLERANCE, "n = {n}: {bits}");
+        }
+    }
+
+    #[test]
+    fn test_default_parameters() {
+        // n = 630 with sigma = 2^-15 and N = 1024 with sigma = 2^-25 were chosen for 128 bits.
+        let bits = estimate_bits(&Parameters::DEFAULT);
+        assert!((bits - 128.0).abs() < TOLERANCE, "{bits}");
+    }
+
+    #[test]
+    fn test_weak_parameters() {
+        assert!(lwe_security_bits(256, 64, f64::powf(2.0, 49.0)) < 64.0);
+        assert!(lwe_security_bits(630, 64, 0.5) == 0.0);
+        let toy = Parameters {
+            lwe_dim: LweDimension(128),
+            ..Parameters::DEFAULT
+        };
+        assert!(estimate_bits(&toy) < 40.0);
+    }
+
+    #[test]
+    fn test_monotonicity() {
+        let sigma = f64::powf(2.0, 40.0);
+        assert!(lwe_security_bits(512, 64, sigma) < lwe_security_bits(1024, 64, sigma));
+        assert!(lwe_security_bits(1024, 64, sigma) < lwe_security_bits(1024, 64, sigma * 16.0));
+    }
+}
@@ -0,0 +1,572 @@
+use crate::boolean::{ClientKey, ServerKey};
+use crate::ggsw::{BootstrappingKey, GgswCiphertext};
+use crate::glwe::{GlweCiphertext, SecretKey};
+use crate::lwe::{KeyDistribution, KeySwitchingKey, LweCiphertext, LweSecretKey, NoiseLevel};
+use crate::params::{BlindRotationAlgorithm, Parameters};
+use crate::poly::ResiduePoly;
+use crate::shortint;
+use crate::uint4::FheUint4;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Upper bound on the size of the serialization of `Parameters`.
+const PARAMS_SIZE: u64 = 128;
+
+/// Upper bound on the size of the serialization of a key, whatever its parameters: the limit of
+/// `SerializableKey::from_bytes` until the parameters embedded in the key are read.
+pub const MAX_KEY_SIZE: u64 = 1 << 32;
+
+/// Conversion to and from the bincode wire format.
+/// `from_bytes_for` is meant for untrusted inputs: it never panics, bounds the size of the input,
+/// rejects trailing bytes and checks the dimensions so that the result can be fed to the
+/// evaluation functions of the given parameters.
+pub trait Serializable: Serialize + DeserializeOwned {
+    /// Upper bound on the size of a valid serialization under `params`.
+    fn max_size(params: &Parameters) -> u64;
+
+    /// Whether the dimensions of `self` are consistent with `params`.
+    fn is_valid_for(&self, params: &Parameters) -> bool;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    /// Deserializes a value of the dimensions of `params`.
+    /// Errors if `params` are invalid, if `bytes` exceed `max_size(params)`, or if the value is not
+    /// valid for `params`.
+    fn from_bytes_for(bytes: &[u8], params: &Parameters) -> bincode::Result<Self> {
+        params.validate().map_err(invalid)?;
+        let value: Self = deserialize(bytes, Self::max_size(params))?;
+
+        if value.is_valid_for(params) {
+            Ok(value)
+        } else {
+            Err(invalid("invalid dimensions"))
+        }
+    }
+}
+
+/// Keys, which embed the parameters they were generated for and can thus be deserialized without
+/// being told them.
+pub trait SerializableKey: Serializable {
+    fn params(&self) -> &Parameters;
+
+    /// Deserializes a key of at most `MAX_KEY_SIZE` bytes, then checks it against its own
+    /// parameters as `from_bytes_for` does.
+    fn from_bytes(bytes: &[u8]) -> bincode::Result<Self> {
+        let value: Self = deserialize(bytes, MAX_KEY_SIZE)?;
+        let params = value.params();
+        params.validate().map_err(invalid)?;
+
+        if bytes.len() as u64 <= Self::max_size(params) && value.is_valid_for(params) {
+            Ok(value)
+        } else {
+            Err(invalid("invalid dimensions"))
+        }
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8], limit: u64) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(limit)
+        .deserialize(bytes)
+}
+
+fn invalid(reason: impl ToString) -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(reason.to_string()))
+}
+
+/// Product of `factors`, saturated: sizes of absurd but valid parameters exceed any input.
+fn size(factors: &[u64]) -> u64 {
+    factors.iter().fold(1, |acc, &f| acc.saturating_mul(f))
+}
+
+/// Product of `factors`, or `None` on overflow, which no length can then equal.
+fn count(factors: &[usize]) -> Option<usize> {
+    factors
+        .iter()
+        .try_fold(1usize, |acc, &f| acc.checked_mul(f))
+}
+
+/// Dimension of the ciphertexts sample-extracted from GLWE ciphertexts of `params`.
+fn extracted_dim(params: &Parameters) -> Option<usize> {
+    count(&[params.glwe_k.0, params.poly_size.0])
+}
+
+/// Size of the serialization of a LWE ciphertext of dimension `dim`.
+fn lwe_size(dim: u64) -> u64 {
+    // length prefix, mask and body
+    size(&[8, dim]).saturating_add(16)
+}
+
+/// Size of the serialization of a polynomial of `params`.
+fn poly_size(params: &Parameters) -> u64 {
+    size(&[8, params.poly_size.0 as u64]).saturating_add(8)
+}
+
+/// Size of the serialization of a GLWE ciphertext of `params`: length prefix, mask and body.
+fn glwe_size(params: &Parameters) -> u64 {
+    size(&[
+        (params.glwe_k.0 as u64).saturating_add(1),
+        poly_size(params),
+    ])
+    .saturating_add(8)
+}
+
+/// Size of the serialization of a GGSW ciphertext of `params`: length prefix, rows and
+/// decomposition.
+fn ggsw_size(params: &Parameters) -> u64 {
+    size(&[
+        (params.glwe_k.0 as u64).saturating_add(1),
+        params.pbs_levels.0 as u64,
+        glwe_size(params),
+    ])
+    .saturating_add(24)
+}
+
+/// Number of GGSW ciphertexts of the bootstrapping keys of `params` for keys of `distribution`,
+/// see `ggsw::compute_bsk`.
+fn bsk_len(params: &Parameters, distribution: KeyDistribution) -> Option<usize> {
+    let dim = params.lwe_dim.0;
+    match (params.blind_rotation, distribution) {
+        (BlindRotationAlgorithm::Ginx, KeyDistribution::Binary) => Some(dim),
+        (BlindRotationAlgorithm::Ginx, KeyDistribution::Ternary) => count(&[2, dim]),
+        (BlindRotationAlgorithm::Ap { base_log }, _) => count(&[
+            dim,
+            params.blind_rotation.digits(params.poly_size),
+            (1 << base_log.0) - 1,
+        ]),
+    }
+}
+
+impl Serializable for LweCiphertext {
+    fn max_size(params: &Parameters) -> u64 {
+        let extracted = size(&[params.glwe_k.0 as u64, params.poly_size.0 as u64]);
+        lwe_size(extracted.max(params.lwe_dim.0 as u64))
+    }
+
+    /// Ciphertexts are either under the LWE key or sample-extracted under the GLWE key.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.mask.len() == params.lwe_dim.0 || Some(self.mask.len()) == extracted_dim(params)
+    }
+}
+
+impl Serializable for KeySwitchingKey {
+    fn max_size(params: &Parameters) -> u64 {
+        // decomposition, length prefix and ciphertexts
+        let keys = size(&[
+            params.ks_levels.0 as u64,
+            params.glwe_k.0 as u64,
+            params.poly_size.0 as u64,
+            lwe_size(params.lwe_dim.0 as u64),
+        ]);
+        keys.saturating_add(24)
+    }
+
+    /// `ks_levels` encryptions under the LWE key for each coefficient of the GLWE key.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.base_log == params.ks_base_log
+            && self.levels == params.ks_levels
+            && Some(self.keys.len())
+                == count(&[params.ks_levels.0, params.glwe_k.0, params.poly_size.0])
+            && self.keys.iter().all(|ct| ct.mask.len() == params.lwe_dim.0)
+    }
+}
+
+impl Serializable for GlweCiphertext {
+    fn max_size(params: &Parameters) -> u64 {
+        glwe_size(params)
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.mask.len() == params.glwe_k.0
+            && self
+                .mask
+                .iter()
+                .chain([&self.body])
+                .all(|poly| is_valid_poly(poly, params))
+    }
+}
+
+impl Serializable for GgswCiphertext {
+    fn max_size(params: &Parameters) -> u64 {
+        ggsw_size(params)
+    }
+
+    /// `(k + 1) pbs_levels` GLWE rows, with the decomposition of `params`.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.base_log == params.pbs_base_log.0
+            && self.levels == params.pbs_levels.0
+            && Some(self.z_m_gt.len()) == count(&[params.glwe_k.0.saturating_add(1), self.levels])
+            && self.z_m_gt.iter().all(|row| row.is_valid_for(params))
+    }
+}
+
+impl Serializable for BootstrappingKey {
+    fn max_size(params: &Parameters) -> u64 {
+        // length prefix, GGSW ciphertexts and distribution
+        let len = [KeyDistribution::Binary, KeyDistribution::Ternary]
+            .into_iter()
+            .filter_map(|distribution| bsk_len(params, distribution))
+            .max()
+            .unwrap_or(0);
+        size(&[len as u64, ggsw_size(params)]).saturating_add(8 + 4)
+    }
+
+    /// The GGSW ciphertexts of the blind rotation algorithm of `params` for the distribution of
+    /// the key, see `ggsw::compute_bsk`.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        Some(self.len()) == bsk_len(params, self.distribution)
+            && self.iter().all(|ggsw| ggsw.is_valid_for(params))
+    }
+}
+
+impl Serializable for SecretKey {
+    fn max_size(params: &Parameters) -> u64 {
+        // length prefix, polynomials and parameters
+        size(&[params.glwe_k.0 as u64, poly_size(params)]).saturating_add(8 + PARAMS_SIZE)
+    }
+
+    /// `k` binary polynomials, for `params`.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.params == *params
+            && self.polys.len() == params.glwe_k.0
+            && self
+                .polys
+                .iter()
+                .all(|poly| is_valid_poly(poly, params) && poly.coefs.iter().all(|&c| c <= 1))
+    }
+}
+
+impl SerializableKey for SecretKey {
+    fn params(&self) -> &Parameters {
+        &self.params
+    }
+}
+
+impl Serializable for LweSecretKey {
+    fn max_size(params: &Parameters) -> u64 {
+        // length prefix, coefficients, parameters, distribution and noise level
+        let extracted = size(&[params.glwe_k.0 as u64, params.poly_size.0 as u64]);
+        size(&[8, extracted.max(params.lwe_dim.0 as u64)]).saturating_add(8 + PARAMS_SIZE + 4 + 4)
+    }
+
+    /// A LWE key or a recoded GLWE key, for `params`, whose coefficients follow its distribution
+    /// and whose dimension follows its noise level.
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        let coefs_in_range = match self.distribution {
+            KeyDistribution::Binary => self.coefs.iter().all(|&c| c <= 1),
+            KeyDistribution::Ternary => self.coefs.iter().all(|&c| c <= 1 || c == u64::MAX),
+        };
+        let dim = match self.noise {
+            NoiseLevel::Lwe => Some(params.lwe_dim.0),
+            NoiseLevel::Glwe => extracted_dim(params),
+        };
+        self.params == *params && Some(self.coefs.len()) == dim && coefs_in_range
+    }
+}
+
+impl SerializableKey for LweSecretKey {
+    fn params(&self) -> &Parameters {
+        &self.params
+    }
+}
+
+impl Serializable for ClientKey {
+    fn max_size(params: &Parameters) -> u64 {
+        LweSecretKey::max_size(params).saturating_add(SecretKey::max_size(params))
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.sk.is_valid_for(params) && self.glwe_sk.is_valid_for(params)
+    }
+}
+
+impl SerializableKey for ClientKey {
+    fn params(&self) -> &Parameters {
+        &self.sk.params
+    }
+}
+
+impl Serializable for ServerKey {
+    fn max_size(params: &Parameters) -> u64 {
+        BootstrappingKey::max_size(params)
+            .saturating_add(KeySwitchingKey::max_size(params))
+            .saturating_add(PARAMS_SIZE)
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.params == *params && self.bsk.is_valid_for(params) && self.ksk.is_valid_for(params)
+    }
+}
+
+impl SerializableKey for ServerKey {
+    fn params(&self) -> &Parameters {
+        &self.params
+    }
+}
+
+impl Serializable for FheUint4 {
+    fn max_size(params: &Parameters) -> u64 {
+        size(&[2, LweCiphertext::max_size(params)])
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.digits.iter().all(|ct| ct.is_valid_for(params))
+    }
+}
+
+impl Serializable for shortint::ClientKey {
+    fn max_size(params: &Parameters) -> u64 {
+        ClientKey::max_size(params)
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.key.is_valid_for(params)
+    }
+}
+
+impl SerializableKey for shortint::ClientKey {
+    fn params(&self) -> &Parameters {
+        self.key.params()
+    }
+}
+
+impl Serializable for shortint::ServerKey {
+    fn max_size(params: &Parameters) -> u64 {
+        ServerKey::max_size(params)
+    }
+
+    fn is_valid_for(&self, params: &Parameters) -> bool {
+        self.key.is_valid_for(params)
+    }
+}
+
+impl SerializableKey for shortint::ServerKey {
+    fn params(&self) -> &Parameters {
+        self.key.params()
+    }
+}
+
+fn is_valid_poly(poly: &ResiduePoly, params: &Parameters) -> bool {
+    poly.coefs.len() == params.poly_size.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boolean::{gen_keys, ClientKey, ServerKey};
+    use crate::ggsw::{compute_bsk, BootstrappingKey, GgswCiphertext};
+    use crate::glwe::{keygen, keygen_for, GlweCiphertext, SecretKey};
+    use crate::lwe::{
+        compute_ksk, lwe_keygen, lwe_keygen_for, KeyDistribution, KeySwitchingKey, LweCiphertext,
+        LweSecretKey, NoiseLevel,
+    };
+    use crate::params::presets::TOY;
+    use crate::params::{
+        DecompositionBaseLog, DecompositionLevelCount, GlweDimension, LweDimension, Parameters,
+        PolynomialSize,
+    };
+    use crate::serialization::{Serializable, SerializableKey};
+    use crate::utils::{decode, encode};
+    use crate::N;
+    use rand::thread_rng;
+
+    const DEFAULT: Parameters = Parameters::DEFAULT;
+
+    #[test]
+    fn test_lwe_round_trip() {
+        let sk = lwe_keygen();
+        let ct = LweCiphertext::encrypt(encode(11), &sk);
+
+        let bytes = ct.to_bytes();
+        assert_eq!(bytes, bincode::serialize(&ct).unwrap());
+
+        let deserialized = LweCiphertext::from_bytes_for(&bytes, &DEFAULT).unwrap();
+        assert_eq!(decode(deserialized.decrypt(&sk)), 11);
+    }
+
+    #[test]
+    fn test_ksk_round_trip() {
+        let sk1 = keygen();
+        let sk2 = lwe_keygen();
+        let ksk = compute_ksk(&sk1.recode(), &sk2);
+
+        let deserialized = KeySwitchingKey::from_bytes_for(&ksk.to_bytes(), &DEFAULT).unwrap();
+        assert_eq!(deserialized.to_bytes(), ksk.to_bytes());
+    }
+
+    #[test]
+    fn test_glwe_round_trip() {
+        let sk = SecretKey::from_bytes(&keygen().to_bytes()).unwrap();
+        let ct = GlweCiphertext::encrypt(encode(6), &sk);
+
+        let deserialized = GlweCiphertext::from_bytes_for(&ct.to_bytes(), &DEFAULT).unwrap();
+        assert_eq!(decode(deserialized.decrypt(&sk)), 6);
+
+        let bytes = GgswCiphertext::encrypt(1, &sk).to_bytes();
+        let ggsw = GgswCiphertext::from_bytes_for(&bytes, &DEFAULT).unwrap();
+        assert_eq!(decode(ggsw.external_product(&deserialized).decrypt(&sk)), 6);
+    }
+
+    #[test]
+    fn test_bsk_round_trip() {
+        let lwe_sk = LweSecretKey::from_bytes(&lwe_keygen().to_bytes()).unwrap();
+        let glwe_sk = keygen();
+        let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+
+        let bytes = bsk.to_bytes();
+        let deserialized = BootstrappingKey::from_bytes_for(&bytes, &DEFAULT).unwrap();
+        assert_eq!(deserialized.to_bytes(), bytes);
+
+        // Blind rotation alone: the keyswitching noise of the default parameters would make a
+        // full bootstrap fail from time to time.
+        let params = Parameters::DEFAULT;
+        let lut = GlweCiphertext::trivial_encrypt_lut_for(&params, |m| 7 - m);
+        let ct = LweCiphertext::encrypt(encode(2), &lwe_sk)
+            .modswitch_to((2 * params.poly_size.0).ilog2());
+        let res = lut.blind_rotate(ct, &deserialized).sample_extract();
+        assert_eq!(decode(res.decrypt(&glwe_sk.recode())), 5);
+    }
+
+    #[test]
+    fn test_boolean_keys_round_trip() {
+        let (client_key, server_key) = gen_keys(&Parameters::DEFAULT);
+
+        let client_key = ClientKey::from_bytes(&client_key.to_bytes()).unwrap();
+        let bytes = server_key.to_bytes();
+        assert_eq!(ServerKey::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        let bytes = client_key.encrypt(true).to_bytes();
+        let ct = LweCiphertext::from_bytes_for(&bytes, &DEFAULT).unwrap();
+        assert!(client_key.decrypt(&ct));
+    }
+
+    #[test]
+    fn test_other_parameters() {
+        let (client_key, server_key) = gen_keys(&TOY);
+
+        // Keys are checked against the parameters they embed.
+        let client_key = ClientKey::from_bytes(&client_key.to_bytes()).unwrap();
+        let server_key = ServerKey::from_bytes(&server_key.to_bytes()).unwrap();
+        assert!(ServerKey::from_bytes_for(&server_key.to_bytes(), &DEFAULT).is_err());
+
+        let bytes = client_key.encrypt(true).to_bytes();
+        assert!(LweCiphertext::from_bytes_for(&bytes, &DEFAULT).is_err());
+        let ct = LweCiphertext::from_bytes_for(&bytes, &TOY).unwrap();
+        let ct = server_key.not(&ct);
+        assert!(!client_key.decrypt(&ct));
+
+        let glwe_sk = keygen_for(&TOY, &mut thread_rng());
+        let bytes = GlweCiphertext::encrypt(encode(3), &glwe_sk).to_bytes();
+        assert!(GlweCiphertext::from_bytes_for(&bytes, &DEFAULT).is_err());
+        assert_eq!(
+            decode(
+                GlweCiphertext::from_bytes_for(&bytes, &TOY)
+                    .unwrap()
+                    .decrypt(&glwe_sk)
+            ),
+            3
+        );
+
+        // The size limit follows the parameters.
+        let lwe_sk = lwe_keygen_for(&TOY, &mut thread_rng());
+        let bytes = compute_bsk(&lwe_sk, &glwe_sk).unwrap().to_bytes();
+        assert!(bytes.len() as u64 <= BootstrappingKey::max_size(&TOY));
+        assert!(BootstrappingKey::max_size(&TOY) < BootstrappingKey::max_size(&DEFAULT));
+        assert!(BootstrappingKey::from_bytes_for(&bytes, &TOY).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_invalid_parameters() {
+        let ct = LweCiphertext::encrypt(encode(1), &lwe_keygen()).to_bytes();
+        let invalid = Parameters {
+            lwe_dim: LweDimension(0),
+            ..DEFAULT
+        };
+        assert!(LweCiphertext::from_bytes_for(&ct, &invalid).is_err());
+
+        // Absurd but valid parameters saturate the size limits instead of overflowing.
+        let huge = Parameters {
+            lwe_dim: LweDimension(usize::MAX),
+            glwe_k: GlweDimension(usize::MAX),
+            poly_size: PolynomialSize(1 << 62),
+            ..DEFAULT
+        };
+        assert_eq!(BootstrappingKey::max_size(&huge), u64::MAX);
+        assert!(BootstrappingKey::from_bytes_for(&[0; 8], &huge).is_err());
+        assert!(LweCiphertext::from_bytes_for(&ct, &huge).is_err());
+
+        let mut sk = lwe_keygen();
+        sk.params = invalid;
+        assert!(LweSecretKey::from_bytes(&sk.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_keys() {
+        let mut sk = keygen();
+        sk.polys[0].coefs[3] = 2;
+        assert!(SecretKey::from_bytes(&sk.to_bytes()).is_err());
+
+        // A LWE key labelled with the noise level of a recoded key does not have its dimension.
+        let mut lwe_sk = lwe_keygen();
+        lwe_sk.noise = NoiseLevel::Glwe;
+        assert!(LweSecretKey::from_bytes(&lwe_sk.to_bytes()).is_err());
+
+        let ct = GlweCiphertext::zero(GlweDimension(2), PolynomialSize(N));
+        assert!(GlweCiphertext::from_bytes_for(&ct.to_bytes(), &DEFAULT).is_err());
+
+        let bsk = BootstrappingKey {
+            ggsws: vec![GgswCiphertext::default(); 3],
+            distribution: KeyDistribution::Binary,
+        };
+        assert!(BootstrappingKey::from_bytes_for(&bsk.to_bytes(), &DEFAULT).is_err());
+
+        // A binary key relabelled as ternary has half the ciphertexts of its distribution.
+        let mut bsk = compute_bsk(
+            &lwe_keygen_for(&TOY, &mut thread_rng()),
+            &keygen_for(&TOY, &mut thread_rng()),
+        )
+        .unwrap();
+        assert!(BootstrappingKey::from_bytes_for(&bsk.to_bytes(), &TOY).is_ok());
+        bsk.distribution = KeyDistribution::Ternary;
+        assert!(BootstrappingKey::from_bytes_for(&bsk.to_bytes(), &TOY).is_err());
+    }
+
+    /// Regression tests for inputs that used to be accepted and later made `keyswitch` or `decrypt` panic.
+    #[test]
+    fn test_rejects_malformed_inputs() {
+        let valid = LweCiphertext::default().to_bytes();
+        let from_bytes = |bytes: &[u8]| LweCiphertext::from_bytes_for(bytes, &DEFAULT);
+
+        // truncated
+        assert!(from_bytes(&valid[..valid.len() - 1]).is_err());
+        // trailing bytes
+        assert!(from_bytes(&[valid.clone(), vec![0]].concat()).is_err());
+        // empty mask
+        let empty = LweCiphertext {
+            mask: vec![],
+            body: 0,
+        };
+        assert!(from_bytes(&empty.to_bytes()).is_err());
+        // huge length prefix
+        let mut huge = valid.clone();
+        huge[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(from_bytes(&huge).is_err());
+
+        // KSK with a missing entry
+        let ksk = KeySwitchingKey {
+            base_log: DecompositionBaseLog(4),
+            levels: DecompositionLevelCount(4),
+            keys: vec![LweCiphertext::default(); 4 * N - 1],
+        };
+        assert!(KeySwitchingKey::from_bytes_for(&ksk.to_bytes(), &DEFAULT).is_err());
+        // KSK with another decomposition
+        let ksk = KeySwitchingKey {
+            base_log: DecompositionBaseLog(2),
+            levels: DecompositionLevelCount(8),
+            keys: vec![LweCiphertext::default(); 4 * N],
+        };
+        assert!(KeySwitchingKey::from_bytes_for(&ksk.to_bytes(), &DEFAULT).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! High-level API on encrypted nibbles, with keys split between a client and a server.
+//!
+//! The client generates both keys, encrypts and decrypts `uint4::FheUint4`s, and sends the
+//! serialized server key to the server, which evaluates the operations without the secret key:
+//!
+//! ```no_run
+//! use ttfhe::params::presets::TOY;
+//! use ttfhe::shortint::ClientKey;
+//!
+//! let (client_key, server_key) = ClientKey::generate(&TOY);
+//! let (a, b) = (client_key.encrypt(9), client_key.encrypt(10));
+//! let sum = server_key.add(&a, &b);
+//! assert_eq!(client_key.decrypt(&sum), 3);
+//! ```
+//!
+//! Operations wrap around modulo 16. The keys are those of `boolean`, whose gates are evaluated by
+//! the same bootstraps.
+
+use crate::boolean::{self, gen_keys};
+use crate::params::Parameters;
+use crate::uint4::{eval_poly, FheUint4};
+use serde::{Deserialize, Serialize};
+
+/// Secret key of the client, which encrypts and decrypts nibbles.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ClientKey {
+    pub key: boolean::ClientKey,
+}
+
+/// Evaluation keys of the server, which computes on nibbles.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerKey {
+    pub key: boolean::ServerKey,
+}
+
+impl ClientKey {
+    /// Generates the keys of a client and of its server for `params`.
+    /// Panics if `params` are invalid.
+    pub fn generate(params: &Parameters) -> (ClientKey, ServerKey) {
+        let (client_key, server_key) = gen_keys(params);
+        (ClientKey { key: client_key }, ServerKey { key: server_key })
+    }
+
+    /// Panics if `m` exceeds `uint4::MAX`.
+    pub fn encrypt(&self, m: u8) -> FheUint4 {
+        FheUint4::encrypt(m, &self.key.sk)
+    }
+
+    pub fn decrypt(&self, ct: &FheUint4) -> u8 {
+        ct.decrypt(&self.key.sk)
+    }
+}
+
+impl ServerKey {
+    /// `a + b` modulo 16, with 3 bootstraps.
+    pub fn add(&self, a: &FheUint4, b: &FheUint4) -> FheUint4 {
+        a.wrapping_add(b, &self.key.evaluation_key())
+    }
+
+    /// `a - b` modulo 16, with 3 bootstraps.
+    pub fn sub(&self, a: &FheUint4, b: &FheUint4) -> FheUint4 {
+        a.wrapping_sub(b, &self.key.evaluation_key())
+    }
+
+    /// `a + m` modulo 16, with 3 bootstraps. Panics if `m` exceeds `uint4::MAX`.
+    pub fn add_scalar(&self, a: &FheUint4, m: u8) -> FheUint4 {
+        a.wrapping_add_scalar(m, &self.key.evaluation_key())
+    }
+
+    /// `a - m` modulo 16, with 3 bootstraps. Panics if `m` exceeds `uint4::MAX`.
+    pub fn sub_scalar(&self, a: &FheUint4, m: u8) -> FheUint4 {
+        a.wrapping_sub_scalar(m, &self.key.evaluation_key())
+    }
+
+    /// `a * m` modulo 16, with the 7 bootstraps of `uint4::eval_poly`.
+    pub fn mul_scalar(&self, a: &FheUint4, m: u8) -> FheUint4 {
+        eval_poly(&[0, m as u64], a, &self.key.evaluation_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::params::presets::TEST;
+    use crate::shortint::ClientKey;
+
+    #[test]
+    fn test_scalar_ops() {
+        let (client_key, server_key) = ClientKey::generate(&TEST);
+
+        for (a, m) in [(0, 0), (3, 14), (9, 7), (15, 15)] {
+            let ct = client_key.encrypt(a);
+            let decrypt = |ct| client_key.decrypt(&ct);
+            assert_eq!(decrypt(server_key.add_scalar(&ct, m)), (a + m) % 16);
+            assert_eq!(
+                decrypt(server_key.sub_scalar(&ct, m)),
+                a.wrapping_sub(m) % 16
+            );
+            assert_eq!(decrypt(server_key.mul_scalar(&ct, m)), (a * m) % 16);
+        }
+    }
+}
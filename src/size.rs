@@ -0,0 +1,268 @@
+//! Sizes of ciphertexts and evaluation keys, in memory and serialized.
+//!
+//! `serialized_bytes` is computed from the dimensions, without serializing. It is exact for the
+//! bincode format of `serialization::Serializable::to_bytes`: lengths are 8-byte prefixes and
+//! integers have a fixed width, so the slack with the real serialization is zero.
+
+use crate::ggsw::EvaluationKey;
+use crate::glwe::GlweCiphertext;
+use crate::lwe::{CompressedLweCiphertext, KeySwitchingKey, LweCiphertext};
+use crate::packing::{AutomorphismKey, FunctionalKeySwitchingKey};
+use crate::poly::ResiduePoly;
+use std::mem::size_of;
+
+/// Size of the length prefix of a serialized sequence.
+const LEN_PREFIX: usize = 8;
+
+pub trait SizeInfo {
+    /// Bytes allocated on the heap, the capacity of vectors included.
+    fn heap_bytes(&self) -> usize;
+
+    /// Length of the bincode serialization.
+    fn serialized_bytes(&self) -> usize;
+
+    /// Number of elements of Z_{2^64} held: the coefficients of the masks and the bodies.
+    fn element_count(&self) -> usize;
+}
+
+impl SizeInfo for ResiduePoly {
+    fn heap_bytes(&self) -> usize {
+        self.coefs.capacity() * size_of::<u64>()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        LEN_PREFIX + 8 * self.coefs.len()
+    }
+
+    fn element_count(&self) -> usize {
+        self.coefs.len()
+    }
+}
+
+impl SizeInfo for LweCiphertext {
+    fn heap_bytes(&self) -> usize {
+        self.mask.capacity() * size_of::<u64>()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        LEN_PREFIX + 8 * self.mask.len() + 8
+    }
+
+    fn element_count(&self) -> usize {
+        self.mask.len() + 1
+    }
+}
+
+impl SizeInfo for CompressedLweCiphertext {
+    fn heap_bytes(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // log2_modulus, dim and bytes
+        4 + 8 + LEN_PREFIX + self.bytes.len()
+    }
+
+    fn element_count(&self) -> usize {
+        self.dim + 1
+    }
+}
+
+impl SizeInfo for GlweCiphertext {
+    fn heap_bytes(&self) -> usize {
+        self.mask.heap_bytes() + self.body.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        self.mask.serialized_bytes() + self.body.serialized_bytes()
+    }
+
+    fn element_count(&self) -> usize {
+        self.mask.element_count() + self.body.element_count()
+    }
+}
+
+impl SizeInfo for KeySwitchingKey {
+    fn heap_bytes(&self) -> usize {
+        self.keys.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // base_log, levels and keys
+        8 + 8 + self.keys.serialized_bytes()
+    }
+
+    fn element_count(&self) -> usize {
+        self.keys.element_count()
+    }
+}
+
+impl SizeInfo for FunctionalKeySwitchingKey {
+    fn heap_bytes(&self) -> usize {
+        self.keys.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // base_log, levels and keys
+        8 + 8 + self.keys.serialized_bytes()
+    }
+
+    fn element_count(&self) -> usize {
+        self.keys.element_count()
+    }
+}
+
+impl SizeInfo for AutomorphismKey {
+    fn heap_bytes(&self) -> usize {
+        self.keys.heap_bytes()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        // g, base_log, levels and keys
+        8 + 8 + 8 + self.keys.serialized_bytes()
+    }
+
+    fn element_count(&self) -> usize {
+        self.keys.element_count()
+    }
+}
+
+/// Lists of ciphertexts and sets of automorphism keys.
+impl<T: SizeInfo> SizeInfo for Vec<T> {
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_bytes).sum::<usize>()
+    }
+
+    fn serialized_bytes(&self) -> usize {
+        LEN_PREFIX + self.iter().map(T::serialized_bytes).sum::<usize>()
+    }
+
+    fn element_count(&self) -> usize {
+        self.iter().map(T::element_count).sum()
+    }
+}
+
+/// Serialized sizes of the components of a set of keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Name and serialized size of every component.
+    pub components: Vec<(&'static str, usize)>,
+}
+
+impl SizeReport {
+    /// Adds the component `value` under `name`.
+    pub fn with(mut self, name: &'static str, value: &impl SizeInfo) -> Self {
+        self.components.push((name, value.serialized_bytes()));
+        self
+    }
+
+    /// Serialized size of all the components.
+    pub fn total(&self) -> usize {
+        self.components.iter().map(|(_, bytes)| bytes).sum()
+    }
+}
+
+impl EvaluationKey<'_> {
+    /// Serialized sizes of the bootstrapping and keyswitching keys. Other keys, such as
+    /// automorphism keys, are added with `SizeReport::with`.
+    pub fn size_report(&self) -> SizeReport {
+        SizeReport::default()
+            .with("bsk", self.bsk)
+            .with("ksk", self.ksk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::{compute_bsk, GgswCiphertext, TestKeys};
+    use crate::glwe::{keygen_for, GlweCiphertext};
+    use crate::lwe::{compute_ksk, lwe_keygen_for, LweCiphertext};
+    use crate::packing::{compute_automorphism_keys, compute_functional_ksk};
+    use crate::params::presets::TOY;
+    use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+    use crate::poly::ResiduePoly;
+    use crate::serialization::Serializable;
+    use crate::size::SizeInfo;
+    use crate::utils::encode;
+    use rand::thread_rng;
+    use serde::Serialize;
+
+    fn assert_serialized_size(value: &(impl SizeInfo + Serialize)) {
+        assert_eq!(
+            value.serialized_bytes(),
+            bincode::serialize(value).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_serialized_bytes() {
+        let lwe_sk = lwe_keygen_for(&TOY, &mut thread_rng());
+        let glwe_sk = keygen_for(&TOY, &mut thread_rng());
+
+        let lwe = LweCiphertext::encrypt(encode(3), &lwe_sk);
+        assert_eq!(lwe.serialized_bytes(), lwe.to_bytes().len());
+        assert_eq!(lwe.element_count(), TOY.lwe_dim.0 + 1);
+        assert_serialized_size(&lwe.compress_for_transport(16));
+        assert_serialized_size(&vec![lwe; 3]);
+
+        let glwe = GlweCiphertext::encrypt(encode(3), &glwe_sk);
+        assert_serialized_size(&glwe);
+        assert_eq!(glwe.element_count(), (TOY.glwe_k.0 + 1) * TOY.poly_size.0);
+        assert_serialized_size(&GgswCiphertext::encrypt(1, &glwe_sk));
+        assert_serialized_size(&ResiduePoly::zero(TOY.poly_size.0));
+
+        let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+        assert_eq!(ksk.serialized_bytes(), ksk.to_bytes().len());
+        assert_serialized_size(&compute_functional_ksk(&lwe_sk, &glwe_sk));
+        assert_serialized_size(&compute_automorphism_keys(
+            &glwe_sk,
+            DecompositionBaseLog(10),
+            DecompositionLevelCount(4),
+        ));
+
+        let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+        assert_serialized_size(&bsk);
+        assert_eq!(
+            bsk.element_count(),
+            TOY.lwe_dim.0
+                * (TOY.glwe_k.0 + 1)
+                * TOY.pbs_levels.0
+                * (TOY.glwe_k.0 + 1)
+                * TOY.poly_size.0
+        );
+    }
+
+    #[test]
+    fn test_heap_bytes() {
+        let glwe = GlweCiphertext::zero(TOY.glwe_k, TOY.poly_size);
+        assert!(glwe.heap_bytes() >= 8 * glwe.element_count());
+
+        let mut list = Vec::with_capacity(4);
+        list.push(LweCiphertext::zero(TOY.lwe_dim.0));
+        assert_eq!(
+            list.heap_bytes(),
+            4 * std::mem::size_of::<LweCiphertext>() + 8 * TOY.lwe_dim.0
+        );
+    }
+
+    #[test]
+    fn test_size_report() {
+        let keys = TestKeys::new(&TOY);
+        let key = keys.evaluation_key();
+        let auto_keys = compute_automorphism_keys(
+            &keys.glwe_sk,
+            DecompositionBaseLog(10),
+            DecompositionLevelCount(4),
+        );
+
+        let report = key.size_report().with("automorphism keys", &auto_keys);
+        let names: Vec<&str> = report.components.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["bsk", "ksk", "automorphism keys"]);
+        assert_eq!(
+            report.total(),
+            bincode::serialize(&keys.bsk).unwrap().len()
+                + keys.ksk.to_bytes().len()
+                + bincode::serialize(&auto_keys).unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Layout of byte strings in the coefficients of message polynomials.
+//!
+//! The bytes are read as a stream of bits, from the least significant bit of the first byte: bit
+//! `i` of byte `j` is bit `8j + i` of the stream. The stream is cut into slots of
+//! `bits_per_slot` bits, the first bit of a slot being its least significant one, and the last
+//! slot is padded with zeros. Slot `s` is encoded as the `s`-th coefficient, scaled by
+//! `2^(64 - bits_per_slot)` like `utils::encode`; the remaining coefficients are `0`.
+//!
+//! With 4 bits per slot, byte `j` is thus in slots `2j` (low nibble) and `2j + 1` (high nibble),
+//! and `GlweCiphertext::sample_extract_at(2j)` gives an encryption of its low nibble.
+
+use crate::poly::ResiduePoly;
+
+/// Maps `bytes` to a message polynomial of degree `poly_size`.
+/// Errors if `bits_per_slot` is not in `[1, 8]` or if the bytes need more than `poly_size` slots.
+pub fn bytes_to_poly(
+    bytes: &[u8],
+    bits_per_slot: u32,
+    poly_size: usize,
+) -> Result<ResiduePoly, String> {
+    validate(bits_per_slot)?;
+    let num_slots = num_slots(bytes.len(), bits_per_slot);
+    if num_slots > poly_size {
+        return Err(format!(
+            "{} bytes take {num_slots} slots of {bits_per_slot} bits, above the {poly_size} coefficients",
+            bytes.len()
+        ));
+    }
+
+    let mut poly = ResiduePoly::zero(poly_size);
+    for s in 0..num_slots {
+        let slot = (0..bits_per_slot as usize).fold(0u64, |acc, b| {
+            let pos = s * bits_per_slot as usize + b;
+            let bit = bytes.get(pos / 8).map_or(0, |byte| (byte >> (pos % 8)) & 1);
+            acc | (bit as u64) << b
+        });
+        poly.coefs[s] = slot << (64 - bits_per_slot);
+    }
+    Ok(poly)
+}
+
+/// Recovers `len` bytes from the coefficients of a decrypted message polynomial, rounding every
+/// coefficient to its slot.
+/// Errors if `bits_per_slot` is not in `[1, 8]` or if `len` bytes need more slots than `coefs`.
+pub fn poly_to_bytes(coefs: &[u64], bits_per_slot: u32, len: usize) -> Result<Vec<u8>, String> {
+    validate(bits_per_slot)?;
+    let num_slots = num_slots(len, bits_per_slot);
+    if num_slots > coefs.len() {
+        return Err(format!(
+            "{len} bytes take {num_slots} slots of {bits_per_slot} bits, above the {} coefficients",
+            coefs.len()
+        ));
+    }
+
+    let mut bytes = vec![0u8; len];
+    for (s, &coef) in coefs[..num_slots].iter().enumerate() {
+        let slot = (((coef >> (63 - bits_per_slot)) + 1) >> 1) % (1 << bits_per_slot);
+        for b in 0..bits_per_slot as usize {
+            let pos = s * bits_per_slot as usize + b;
+            if pos / 8 < len {
+                bytes[pos / 8] |= (((slot >> b) & 1) as u8) << (pos % 8);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Number of slots of `bits_per_slot` bits holding `len` bytes.
+pub fn num_slots(len: usize, bits_per_slot: u32) -> usize {
+    (8 * len).div_ceil(bits_per_slot as usize)
+}
+
+fn validate(bits_per_slot: u32) -> Result<(), String> {
+    if (1..=8).contains(&bits_per_slot) {
+        Ok(())
+    } else {
+        Err(format!("slots of {bits_per_slot} bits are not supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glwe::{keygen_for, GlweCiphertext};
+    use crate::params::presets::TEST;
+    use crate::slots::{bytes_to_poly, num_slots, poly_to_bytes};
+    use crate::utils::decode;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_round_trip() {
+        let poly_size = TEST.poly_size.0;
+        for bits_per_slot in [1, 3, 4, 8] {
+            let max_len = poly_size * bits_per_slot as usize / 8;
+            for len in [0, 1, 5, max_len] {
+                let bytes: Vec<u8> = (0..len).map(|_| thread_rng().gen()).collect();
+                let poly = bytes_to_poly(&bytes, bits_per_slot, poly_size).unwrap();
+                assert_eq!(
+                    poly_to_bytes(&poly.coefs, bits_per_slot, len).unwrap(),
+                    bytes
+                );
+            }
+
+            let err = bytes_to_poly(&vec![0; max_len + 1], bits_per_slot, poly_size)
+                .err()
+                .unwrap();
+            assert!(err.contains("above the"), "{err}");
+        }
+
+        assert_eq!(num_slots(2, 3), 6);
+        assert!(bytes_to_poly(&[1], 0, poly_size).is_err());
+        assert!(poly_to_bytes(&[0; 4], 4, 3).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_slots() {
+        let sk = keygen_for(&TEST, &mut thread_rng());
+        let bytes: Vec<u8> = (0..100).map(|_| thread_rng().gen()).collect();
+
+        let poly = bytes_to_poly(&bytes, 4, TEST.poly_size.0).unwrap();
+        let ct = GlweCiphertext::encrypt_poly(&poly, &sk);
+        assert_eq!(poly_to_bytes(&ct.decrypt_poly(&sk), 4, 100).unwrap(), bytes);
+
+        let lwe_sk = sk.recode();
+        for j in [0, 1, 57, 99] {
+            let low = ct.sample_extract_at(2 * j).decrypt(&lwe_sk);
+            let high = ct.sample_extract_at(2 * j + 1).decrypt(&lwe_sk);
+            assert_eq!(decode(low), bytes[j] & 15);
+            assert_eq!(decode(high), bytes[j] >> 4);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Bootstraps off the calling thread, for async servers.
+//!
+//! `boolean::ServerKey::spawn_bootstrap` runs a bootstrap on the global rayon thread pool and
+//! returns a `Spawned` future of its output, so that an executor is not blocked for the duration
+//! of a bootstrap. The pool has one thread per CPU by default: at most that many bootstraps run at
+//! once, and the others wait in its queue instead of each taking an OS thread. The future is
+//! runtime-agnostic: it is completed through a channel and woken by the pool, and needs no
+//! reactor.
+//!
+//! Only compiled with the `async` feature.
+
+use crate::boolean::ServerKey;
+use crate::lwe::LweCiphertext;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Output of a computation running on the thread pool.
+/// Resolves to the output of the computation, and panics if the computation panicked.
+pub struct Spawned<T> {
+    receiver: Receiver<T>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl<T> Future for Spawned<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        // The thread sends its output before taking the waker: checking the channel under the
+        // lock of the waker cannot miss both.
+        let mut waker = self.waker.lock().unwrap();
+        match self.receiver.try_recv() {
+            Ok(output) => Poll::Ready(output),
+            Err(TryRecvError::Empty) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(TryRecvError::Disconnected) => panic!("spawned computation panicked"),
+        }
+    }
+}
+
+/// Wakes the future of a computation when dropped, once its output is sent or if it panics.
+struct WakeOnDrop(Arc<Mutex<Option<Waker>>>);
+
+impl Drop for WakeOnDrop {
+    fn drop(&mut self) {
+        if let Some(waker) = self.0.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Runs `f` on the global rayon thread pool.
+pub fn spawn<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Spawned<T> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let waker = Arc::new(Mutex::new(None));
+    let wake_on_drop = WakeOnDrop(waker.clone());
+    rayon::spawn(move || {
+        let _wake_on_drop = wake_on_drop;
+        // Dropped before `_wake_on_drop`, so that a woken future sees the output or the panic.
+        let sender = sender;
+        // rayon aborts on a panic of a spawned task: a panic only drops the sender, which the
+        // future reports. The future may also have been dropped, in which case the output is
+        // discarded.
+        if let Ok(output) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            let _ = sender.send(output);
+        }
+    });
+    Spawned { receiver, waker }
+}
+
+impl ServerKey {
+    /// Bootstraps `ct` with the function `f` of its message on the thread pool, see
+    /// `ggsw::EvaluationKey::bootstrap`. The key is shared with the pool.
+    pub fn spawn_bootstrap(
+        self: &Arc<Self>,
+        ct: LweCiphertext,
+        f: impl Fn(u8) -> u8 + Send + 'static,
+    ) -> Spawned<LweCiphertext> {
+        let key = Arc::clone(self);
+        spawn(move || key.evaluation_key().bootstrap(&ct, f))
+    }
+}
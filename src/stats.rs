@@ -0,0 +1,473 @@
+use crate::error::TfheError;
+use crate::ggsw::BootstrappingKey;
+use crate::glwe::{GlweCiphertext, SecretKey};
+use crate::key_provider::KeyProvider;
+use crate::lwe::{KeySwitchingKey, LweCiphertext, LweSecretKey};
+use crate::utils::decode_bits;
+use crate::N;
+use rand::{thread_rng, Rng};
+
+/// Number of equal-width buckets in `NoiseStats::histogram`.
+pub const HISTOGRAM_BINS: usize = 32;
+
+/// Empirical distribution of the centered error of a ciphertext-producing operation.
+#[derive(Clone, Debug)]
+pub struct NoiseStats {
+    pub samples: usize,
+    pub mean: f64,
+    pub variance: f64,
+    pub min: i64,
+    pub max: i64,
+    /// Counts of errors falling in `HISTOGRAM_BINS` equal-width buckets spanning `[min, max]`.
+    pub histogram: Vec<usize>,
+}
+
+impl NoiseStats {
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// Width of a histogram bucket.
+    pub fn bin_width(&self) -> f64 {
+        (self.max as f64 - self.min as f64 + 1.0) / HISTOGRAM_BINS as f64
+    }
+
+    fn from_errors(errors: &[i64]) -> Self {
+        assert!(!errors.is_empty(), "at least one sample is needed");
+
+        let samples = errors.len();
+        let mean = errors.iter().map(|&e| e as f64).sum::<f64>() / samples as f64;
+        let variance = errors
+            .iter()
+            .map(|&e| (e as f64 - mean).powi(2))
+            .sum::<f64>()
+            / samples as f64;
+        let min = *errors.iter().min().unwrap();
+        let max = *errors.iter().max().unwrap();
+
+        let mut stats = NoiseStats {
+            samples,
+            mean,
+            variance,
+            min,
+            max,
+            histogram: vec![0; HISTOGRAM_BINS],
+        };
+
+        let bin_width = stats.bin_width();
+        for &e in errors {
+            let bin = ((e as f64 - min as f64) / bin_width) as usize;
+            stats.histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+
+        stats
+    }
+}
+
+/// Runs `op` `samples` times and measures the error of the resulting ciphertexts with respect to
+/// the encoded plaintext `expected`.
+pub fn noise_distribution(
+    op: impl Fn() -> LweCiphertext,
+    sk: &LweSecretKey,
+    expected: u64,
+    samples: usize,
+) -> NoiseStats {
+    let errors: Vec<i64> = (0..samples)
+        .map(|_| op().decrypt(sk).wrapping_sub(expected) as i64)
+        .collect();
+
+    NoiseStats::from_errors(&errors)
+}
+
+/// Error of `ct` with respect to the encoded plaintext `expected`, under the LWE key of `provider`.
+/// Errors if the provider fails or if `ct` is not of the dimension of the key.
+pub fn measure_noise(
+    ct: &LweCiphertext,
+    expected: u64,
+    provider: &dyn KeyProvider,
+) -> Result<i64, TfheError> {
+    let sk = provider.lwe_key()?;
+    Ok(ct.clone().try_decrypt(&sk)?.wrapping_sub(expected) as i64)
+}
+
+/// Decrypts `ct` as a message of `plaintext_bits` bits and reports its noise.
+/// Returns the decoded message, the centered error with respect to its encoding, and the ratio of
+/// the magnitude of the error to the half width of a decoding box. A ratio close to `1` means the
+/// ciphertext is close to decrypting to a neighbouring message. The error is measured against the
+/// nearest encoding, so the ratio never exceeds `1`: an overflow past the edge of the box shows as
+/// a small error around the wrong message, see `decrypt_with_noise_against`.
+/// Panics if `plaintext_bits` is not between 1 and 8.
+pub fn decrypt_with_noise(
+    ct: &LweCiphertext,
+    sk: &LweSecretKey,
+    plaintext_bits: u32,
+) -> (u8, i64, f64) {
+    let phase = ct.clone().decrypt(sk);
+    let msg = decode_bits(phase, plaintext_bits);
+    let (error, fraction) = error_against(phase, msg, plaintext_bits);
+
+    (msg, error, fraction)
+}
+
+/// Like `decrypt_with_noise`, but measures the error with respect to the encoding of `expected`,
+/// the message `ct` should hold. Past a ratio of `1` the decoded message is wrong, and the ratio
+/// tells by how much the noise overflowed, up to a magnitude of half the torus.
+pub fn decrypt_with_noise_against(
+    ct: &LweCiphertext,
+    sk: &LweSecretKey,
+    plaintext_bits: u32,
+    expected: u8,
+) -> (u8, i64, f64) {
+    let phase = ct.clone().decrypt(sk);
+    let msg = decode_bits(phase, plaintext_bits);
+    let (error, fraction) = error_against(phase, expected, plaintext_bits);
+
+    (msg, error, fraction)
+}
+
+/// Centered error of `phase` with respect to the encoding of `msg`, and its ratio to the half width
+/// of a decoding box.
+fn error_against(phase: u64, msg: u8, plaintext_bits: u32) -> (i64, f64) {
+    let shift = 64 - plaintext_bits;
+    let error = phase.wrapping_sub((msg as u64) << shift) as i64;
+    (
+        error,
+        error.unsigned_abs() as f64 / (1u64 << (shift - 1)) as f64,
+    )
+}
+
+/// Fraction of `samples` runs of `op` on random messages of `plaintext_bits` bits whose output does
+/// not decrypt to the message under `sk`.
+pub fn failure_rate(
+    mut op: impl FnMut(u8) -> LweCiphertext,
+    sk: &LweSecretKey,
+    plaintext_bits: u32,
+    samples: usize,
+) -> f64 {
+    let failures = (0..samples)
+        .filter(|_| {
+            let msg = thread_rng().gen_range(0..1u16 << plaintext_bits) as u8;
+            decrypt_with_noise_against(&op(msg), sk, plaintext_bits, msg).0 != msg
+        })
+        .count();
+
+    failures as f64 / samples as f64
+}
+
+/// Noise of fresh LWE encryptions of zero.
+pub fn fresh_encryption(sk: &LweSecretKey, samples: usize) -> NoiseStats {
+    noise_distribution(|| LweCiphertext::encrypt(0, sk), sk, 0, samples)
+}
+
+/// Noise of the sum of `length` fresh LWE encryptions of zero.
+pub fn add_chain(sk: &LweSecretKey, length: usize, samples: usize) -> NoiseStats {
+    noise_distribution(
+        || {
+            (1..length).fold(LweCiphertext::encrypt(0, sk), |acc, _| {
+                acc.add(LweCiphertext::encrypt(0, sk))
+            })
+        },
+        sk,
+        0,
+        samples,
+    )
+}
+
+/// Noise of fresh LWE encryptions of zero switched to the modulus `2N`.
+/// The switched ciphertexts are scaled back by `2^64 / 2N` so that the error is measured with respect to `q`.
+pub fn modswitch(sk: &LweSecretKey, samples: usize) -> NoiseStats {
+    let scale = 1u64 << (64 - (2 * N).ilog2());
+    noise_distribution(
+        || {
+            let mut ct = LweCiphertext::encrypt(0, sk).modswitch();
+            ct.multiply_constant_assign(scale);
+            ct
+        },
+        sk,
+        0,
+        samples,
+    )
+}
+
+/// Noise after keyswitching sample-extracted GLWE encryptions of zero from `glwe_sk` to `lwe_sk`.
+/// `ksk` is expected to be `compute_ksk(&glwe_sk.recode(), lwe_sk)`.
+pub fn keyswitch(
+    glwe_sk: &SecretKey,
+    lwe_sk: &LweSecretKey,
+    ksk: &KeySwitchingKey,
+    samples: usize,
+) -> NoiseStats {
+    noise_distribution(
+        || {
+            GlweCiphertext::encrypt(0, glwe_sk)
+                .sample_extract()
+                .keyswitch(ksk)
+        },
+        lwe_sk,
+        0,
+        samples,
+    )
+}
+
+/// State of the accumulator after one CMUX of a blind rotation.
+#[derive(Clone, Debug)]
+pub struct StepTrace {
+    /// Mask coefficient of the step: the accumulator is rotated by it if the key bit is set.
+    pub rotation: u64,
+    /// Error of the coefficients of the accumulator.
+    pub noise: NoiseStats,
+}
+
+/// Blind rotates the trivial encryption `lut` by the modswitched ciphertext `c` and measures the
+/// noise of the accumulator after every CMUX.
+/// `c` is encrypted under `lwe_sk`, and `bsk` encrypts `lwe_sk` under `glwe_sk`.
+pub fn trace_blind_rotation(
+    lut: &GlweCiphertext,
+    c: LweCiphertext,
+    bsk: &BootstrappingKey,
+    lwe_sk: &LweSecretKey,
+    glwe_sk: &SecretKey,
+) -> (GlweCiphertext, Vec<StepTrace>) {
+    let two_n = 2 * glwe_sk.params.poly_size.0 as u64;
+    let mask = c.mask.clone();
+    let mut exponent = (two_n - c.body) % two_n;
+    let mut trace = Vec::with_capacity(mask.len());
+
+    let acc = lut.blind_rotate_observed(c, bsk, |i, acc| {
+        exponent = (exponent + mask[i] * lwe_sk.coefs[i]) % two_n;
+        trace.push(StepTrace {
+            rotation: mask[i],
+            noise: accumulator_noise(acc, lut, exponent, glwe_sk),
+        });
+    });
+
+    (acc, trace)
+}
+
+/// Error of the coefficients of `acc` with respect to the body of `lut` rotated by `X^exponent`.
+fn accumulator_noise(
+    acc: &GlweCiphertext,
+    lut: &GlweCiphertext,
+    exponent: u64,
+    glwe_sk: &SecretKey,
+) -> NoiseStats {
+    let expected = lut.body.multiply_by_monomial(exponent as usize);
+    let errors: Vec<i64> = acc
+        .decrypt_poly(glwe_sk)
+        .iter()
+        .zip(&expected.coefs)
+        .map(|(&phase, &coef)| phase.wrapping_sub(coef) as i64)
+        .collect();
+
+    NoiseStats::from_errors(&errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boolean::gen_keys;
+    use crate::error::TfheError;
+    use crate::ggsw::compute_bsk;
+    use crate::glwe::{keygen, keygen_for, GlweCiphertext};
+    use crate::lwe::{compute_ksk, lwe_keygen, lwe_keygen_for, LweCiphertext};
+    use crate::noise::NoiseModel;
+    use crate::params::presets::TEST;
+    use crate::params::BlindRotationAlgorithm::{Ap, Ginx};
+    use crate::params::{DecompositionBaseLog, LweDimension, Parameters};
+    use crate::stats::{
+        accumulator_noise, add_chain, decrypt_with_noise, decrypt_with_noise_against,
+        fresh_encryption, keyswitch, measure_noise, trace_blind_rotation, HISTOGRAM_BINS,
+    };
+    use crate::utils::{decompose_rounded, encode};
+    use crate::{LWE_STD_DEV, N};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_fresh_encryption_variance() {
+        let sk = lwe_keygen();
+        let stats = fresh_encryption(&sk, 100_000);
+
+        let expected = LWE_STD_DEV * LWE_STD_DEV;
+        assert!((stats.variance - expected).abs() < 0.1 * expected);
+        assert!(stats.mean.abs() < 0.05 * LWE_STD_DEV);
+        assert_eq!(stats.histogram.len(), HISTOGRAM_BINS);
+        assert_eq!(stats.histogram.iter().sum::<usize>(), 100_000);
+    }
+
+    #[test]
+    fn test_add_chain_variance() {
+        let sk = lwe_keygen();
+        let stats = add_chain(&sk, 8, 10_000);
+
+        let expected = 8.0 * LWE_STD_DEV * LWE_STD_DEV;
+        assert!((stats.variance - expected).abs() < 0.1 * expected);
+    }
+
+    #[test]
+    fn test_keyswitch_variance() {
+        let lwe_sk = lwe_keygen();
+        let glwe_sk = keygen();
+        let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+        let stats = keyswitch(&glwe_sk, &lwe_sk, &ksk, 300);
+
+        // Every digit `d` multiplies the noise of one KSK entry. The key being fixed, the mean digit
+        // only offsets the result: the keyswitch adds `sum(Var(d)) * sigma^2` to the variance.
+        let params = Parameters::DEFAULT;
+        let (base_log, levels) = (params.ks_base_log, params.ks_levels);
+        let digits: Vec<f64> = (0..10_000)
+            .flat_map(|_| decompose_rounded(rand::random::<u64>(), base_log, levels))
+            .map(|d| d as i64 as f64)
+            .collect();
+        let mean = digits.iter().sum::<f64>() / digits.len() as f64;
+        let digit_variance =
+            digits.iter().map(|d| d * d).sum::<f64>() / digits.len() as f64 - mean * mean;
+        // Dropping the LSBs of each mask coefficient adds a uniform error for every key bit set.
+        let step = 2f64.powi(64 - (base_log.0 * levels.0) as i32);
+        let rounding = (N / 2) as f64 * step * step / 12.0;
+        let expected =
+            (N * levels.0) as f64 * digit_variance * LWE_STD_DEV * LWE_STD_DEV + rounding;
+
+        assert!((stats.variance - expected).abs() < 0.25 * expected);
+    }
+
+    #[test]
+    fn test_trace_blind_rotation() {
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+
+        let lut = GlweCiphertext::trivial_encrypt_lut_poly_for(&TEST);
+        let two_n = 2 * TEST.poly_size.0 as u64;
+        let c = LweCiphertext::encrypt(encode(3), &lwe_sk).modswitch_to(two_n.ilog2());
+        let (acc, trace) = trace_blind_rotation(&lut, c.clone(), &bsk, &lwe_sk, &glwe_sk);
+
+        assert_eq!(trace.len(), TEST.lwe_dim.0);
+        assert_eq!(trace[0].rotation, c.mask[0]);
+        // Every CMUX adds the noise of an external product.
+        assert!(trace[0].noise.variance < trace[TEST.lwe_dim.0 - 1].noise.variance);
+
+        let phase = c
+            .mask
+            .iter()
+            .zip(&lwe_sk.coefs)
+            .fold(c.body, |acc, (&a, &s)| acc + two_n - a * s % two_n)
+            % two_n;
+        let noise = accumulator_noise(&acc, &lut, (two_n - phase) % two_n, &glwe_sk);
+        let last = &trace[TEST.lwe_dim.0 - 1].noise;
+        assert_eq!((noise.min, noise.max), (last.min, last.max));
+        assert_eq!(noise.variance, last.variance);
+    }
+
+    #[test]
+    fn test_blind_rotation_algorithms_noise() {
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let lut = GlweCiphertext::trivial_encrypt_lut_poly_for(&TEST);
+        let two_n = 2 * TEST.poly_size.0 as u64;
+
+        let mut variances = vec![];
+        for algorithm in [
+            Ginx,
+            Ap {
+                base_log: DecompositionBaseLog(2),
+            },
+        ] {
+            let params = Parameters {
+                blind_rotation: algorithm,
+                ..TEST
+            };
+            let mut glwe_sk = glwe_sk.clone();
+            glwe_sk.params = params;
+            let lwe_sk = lwe_keygen_for(&params, &mut thread_rng());
+            let bsk = compute_bsk(&lwe_sk, &glwe_sk).unwrap();
+
+            let c = LweCiphertext::encrypt(encode(3), &lwe_sk).modswitch_to(two_n.ilog2());
+            let phase = c
+                .mask
+                .iter()
+                .zip(&lwe_sk.coefs)
+                .fold(c.body, |acc, (&a, &s)| acc + two_n - a * s % two_n)
+                % two_n;
+            let acc = match algorithm {
+                Ginx => lut.blind_rotate(c, &bsk),
+                Ap { base_log } => lut.blind_rotate_ap(c, &bsk, base_log),
+            };
+            let noise = accumulator_noise(&acc, &lut, (two_n - phase) % two_n, &glwe_sk);
+
+            // The model assumes every key bit to be set, and the coefficients of one accumulator
+            // are not independent: only the order of magnitude is checked.
+            let predicted = NoiseModel::pbs(&params).variance;
+            assert!(
+                (0.1..1.5).contains(&(noise.variance / predicted)),
+                "{algorithm:?}: predicted {predicted:e}, measured {:e}",
+                noise.variance
+            );
+            variances.push(noise.variance);
+        }
+
+        // AP performs more external products with base 4 digits.
+        assert!(variances[0] < variances[1]);
+    }
+
+    #[test]
+    fn test_decrypt_with_noise() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let max_fraction = |op: &dyn Fn() -> LweCiphertext| {
+            (0..10)
+                .map(|_| {
+                    let (msg, _, fraction) = decrypt_with_noise(&op(), &sk, 4);
+                    assert_eq!(msg, 5);
+                    fraction
+                })
+                .fold(0.0, f64::max)
+        };
+
+        let fresh = max_fraction(&|| LweCiphertext::encrypt(encode(5), &sk));
+        assert!(fresh < 0.05, "{fresh}");
+        let summed = max_fraction(&|| {
+            (0..1024).fold(LweCiphertext::encrypt(encode(5), &sk), |acc, _| {
+                acc.add(LweCiphertext::encrypt(0, &sk))
+            })
+        });
+        assert!(summed > fresh, "{summed} <= {fresh}");
+
+        let mut ct = LweCiphertext::encrypt(encode(5), &sk);
+        ct.body = ct.body.wrapping_add(3 << 57);
+        let (msg, error, fraction) = decrypt_with_noise(&ct, &sk, 4);
+        assert_eq!(msg, 5);
+        assert!(error > 0 && fraction > 0.7, "{fraction}");
+
+        // Past the edge of the box, the message is wrong. Measured against the nearest encoding,
+        // that of the wrong message, the error looks small.
+        ct.body = ct.body.wrapping_add(1 << 59);
+        let (msg, error, fraction) = decrypt_with_noise(&ct, &sk, 4);
+        assert_eq!(msg, 6);
+        assert!(error < 0 && fraction < 0.3, "{fraction}");
+        // Measured against the right message, the ratio exceeds 1.
+        let (msg, error, fraction) = decrypt_with_noise_against(&ct, &sk, 4, 5);
+        assert_eq!(msg, 6);
+        assert!(error > 0 && (1.7..1.8).contains(&fraction), "{fraction}");
+    }
+
+    #[test]
+    fn test_measure_noise() {
+        let (client_key, _) = gen_keys(&TEST);
+        let mut ct = client_key.encrypt(true);
+        ct.body = ct.body.wrapping_add(1 << 50);
+        let noise = measure_noise(&ct, encode(1), &client_key).unwrap();
+        assert!(
+            ((noise - (1 << 50)) as f64).abs() < 8.0 * TEST.lwe_sigma,
+            "{noise}"
+        );
+
+        let (other_key, _) = gen_keys(&Parameters {
+            lwe_dim: LweDimension(TEST.lwe_dim.0 + 1),
+            ..TEST
+        });
+        assert_eq!(
+            measure_noise(&ct, encode(1), &other_key),
+            Err(TfheError::KeyMismatchDimension {
+                expected: TEST.lwe_dim.0 + 1,
+                found: TEST.lwe_dim.0
+            })
+        );
+    }
+}
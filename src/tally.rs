@@ -0,0 +1,217 @@
+//! Private vote tallying.
+//!
+//! A ballot is a one-hot vector of LWE encryptions, one per candidate. Ballots are summed into
+//! per-candidate counters without decrypting them. A counter is a list of encrypted base-4 digits,
+//! so that it can count past the plaintext modulus: each digit absorbs votes until its message
+//! could reach the padding bit, at which point a bootstrap extracts its carry into the next digit.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::utils::{decode, encode};
+use crate::P;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of candidates of a ballot.
+pub const MAX_CANDIDATES: usize = 16;
+
+/// Base of the digits of the counters.
+const DIGIT_BASE: u64 = (P / 4) as u64;
+
+/// Largest message a digit may hold: bootstrapping requires the padding bit to be clear.
+const MAX_DEGREE: u64 = (P / 2 - 1) as u64;
+
+/// Encrypted vote count of a candidate.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Counter {
+    /// Base-4 digits, from the least significant.
+    pub digits: Vec<LweCiphertext>,
+    /// Upper bound of the message of each digit.
+    degrees: Vec<u64>,
+}
+
+/// Encrypts a ballot for candidate `choice` among `num_candidates`: an encryption of `1` at
+/// position `choice` and of `0` elsewhere.
+/// Panics if `num_candidates` exceeds `MAX_CANDIDATES` or if `choice` is not a candidate.
+pub fn cast_vote(choice: u8, num_candidates: usize, sk: &LweSecretKey) -> Vec<LweCiphertext> {
+    assert!(
+        (1..=MAX_CANDIDATES).contains(&num_candidates),
+        "the number of candidates must be between 1 and {MAX_CANDIDATES}, got {num_candidates}"
+    );
+    assert!(
+        (choice as usize) < num_candidates,
+        "invalid choice {choice} for {num_candidates} candidates"
+    );
+
+    (0..num_candidates)
+        .map(|i| LweCiphertext::encrypt(encode((i == choice as usize) as u8), sk))
+        .collect()
+}
+
+/// Sums `ballots` into one counter per candidate.
+/// Carries are extracted lazily, when a digit could otherwise overflow, so that most votes cost a
+/// single addition. Every digit of the returned counters is below the digit base.
+/// Returns no counters if there are no ballots or no candidates.
+/// Panics if the ballots do not all have the same number of candidates.
+pub fn aggregate(ballots: &[Vec<LweCiphertext>], key: &EvaluationKey) -> Vec<Counter> {
+    let Some(first) = ballots.first() else {
+        return vec![];
+    };
+    let dim = first.first().map_or(0, |vote| vote.mask.len());
+
+    let mut counters = vec![Counter::zero(dim, num_digits_for(ballots.len() as u64)); first.len()];
+    for ballot in ballots {
+        assert_eq!(
+            ballot.len(),
+            counters.len(),
+            "ballots of different numbers of candidates"
+        );
+        for (counter, vote) in counters.iter_mut().zip(ballot) {
+            counter.add_at(0, vote, 1, key);
+        }
+    }
+
+    for counter in &mut counters {
+        counter.normalize(key);
+    }
+    counters
+}
+
+/// Number of digits with which a counter counts up to `max_count`.
+pub(crate) fn num_digits_for(max_count: u64) -> usize {
+    let mut num_digits = 1;
+    while DIGIT_BASE.pow(num_digits) <= max_count {
+        num_digits += 1;
+    }
+    num_digits as usize
+}
+
+impl Counter {
+    /// Encryption of `0` with `num_digits` digits of dimension `dim`.
+    pub(crate) fn zero(dim: usize, num_digits: usize) -> Self {
+        Counter {
+            digits: vec![LweCiphertext::zero(dim); num_digits],
+            degrees: vec![0; num_digits],
+        }
+    }
+
+    /// Adds `ct`, whose message is at most `degree`, to the `i`-th digit, first extracting the
+    /// carry of the digit if the sum could overflow. Carries out of the last digit are dropped.
+    pub(crate) fn add_at(
+        &mut self,
+        i: usize,
+        ct: &LweCiphertext,
+        degree: u64,
+        key: &EvaluationKey,
+    ) {
+        if i >= self.digits.len() {
+            return;
+        }
+        if self.degrees[i] + degree > MAX_DEGREE {
+            self.extract_carry(i, key);
+        }
+        self.digits[i] = self.digits[i].clone().add(ct.clone());
+        self.degrees[i] += degree;
+    }
+
+    /// Replaces the `i`-th digit by its remainder modulo the digit base and adds the quotient to
+    /// the next digit. Costs two bootstraps: computing the remainder as the difference of the digit
+    /// and the scaled quotient would be cheaper, but the noise of the quotient, multiplied by the
+    /// base, would accumulate in the digit at every extraction.
+    fn extract_carry(&mut self, i: usize, key: &EvaluationKey) {
+        let carry_degree = self.degrees[i] / DIGIT_BASE;
+        let carry = key.bootstrap(&self.digits[i], |m| m / DIGIT_BASE as u8);
+        self.digits[i] = key.bootstrap(&self.digits[i], |m| m % DIGIT_BASE as u8);
+        self.degrees[i] = self.degrees[i].min(DIGIT_BASE - 1);
+        if carry_degree > 0 {
+            self.add_at(i + 1, &carry, carry_degree, key);
+        }
+    }
+
+    /// Extracts the carries of all digits, from the least significant.
+    pub(crate) fn normalize(&mut self, key: &EvaluationKey) {
+        for i in 0..self.digits.len() {
+            if self.degrees[i] >= DIGIT_BASE {
+                self.extract_carry(i, key);
+            }
+        }
+    }
+
+    /// Returns an encryption of `1` if the count is at most `bound`, and of `0` otherwise.
+    /// Propagates the borrow of the subtraction of `bound + 1` from the least significant digit,
+    /// with one bootstrap per digit: the count is below `bound + 1` if the last digit borrows.
+    /// The digits must be normalized, which they are once aggregated.
+    pub fn at_most(&self, bound: u64, key: &EvaluationKey) -> LweCiphertext {
+        let dim = self.digits[0].mask.len();
+        let mut borrow = LweCiphertext::zero(dim);
+        let limit = bound.saturating_add(1);
+        if DIGIT_BASE
+            .checked_pow(self.digits.len() as u32)
+            .is_some_and(|max_count| limit >= max_count)
+        {
+            // Every count of the digits is at most `bound`.
+            borrow.body = encode(1);
+            return borrow;
+        }
+
+        for (i, digit) in self.digits.iter().enumerate() {
+            let limit_digit = (limit / DIGIT_BASE.pow(i as u32) % DIGIT_BASE) as u8;
+            let mut input = borrow;
+            input.multiply_constant_assign(DIGIT_BASE);
+            borrow = key.bootstrap(&input.add(digit.clone()), |m| {
+                ((m % DIGIT_BASE as u8) < limit_digit + m / DIGIT_BASE as u8) as u8
+            });
+        }
+        borrow
+    }
+
+    /// Decrypts the vote count.
+    pub fn decrypt(&self, sk: &LweSecretKey) -> u64 {
+        self.digits.iter().rev().fold(0u64, |acc, digit| {
+            acc * DIGIT_BASE + decode(digit.clone().decrypt(sk)) as u64
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::lwe::lwe_keygen_for;
+    use crate::params::presets::TEST;
+    use crate::tally::{aggregate, cast_vote};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_tally() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let mut expected = [0u64; 4];
+        let ballots: Vec<_> = (0..200)
+            .map(|_| {
+                let choice = thread_rng().gen_range(0..4);
+                expected[choice as usize] += 1;
+                cast_vote(choice, 4, &keys.lwe_sk)
+            })
+            .collect();
+
+        let counters = aggregate(&ballots, &key);
+        let counts: Vec<u64> = counters.iter().map(|c| c.decrypt(&keys.lwe_sk)).collect();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_aggregate_empty() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        assert!(aggregate(&[], &key).is_empty());
+        assert!(aggregate(&[vec![], vec![]], &key).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "number of candidates")]
+    fn test_too_many_candidates() {
+        let sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        cast_vote(0, 17, &sk);
+    }
+}
@@ -0,0 +1,189 @@
+//! Scalars of the discretized torus: `u64`, the default of the crate, and `u32`, as in the
+//! original TFHE, for smaller ciphertexts and faster experiments.
+//!
+//! A scalar of `BITS` bits stands for the value `x / 2^BITS` of the torus. Encodings, noise
+//! levels and gadget decompositions are defined on `u64`: the other widths go through the MSBs of a
+//! `u64`, where `x` is `x << (64 - BITS)`, so that the same parameters and messages apply to all of
+//! them. `u64` values are unchanged by these conversions.
+//!
+//! LWE ciphertexts and keys, keyswitching keys, polynomials, GLWE ciphertexts and keys are generic
+//! over the scalar, `u64` being the default type parameter, and the tests of their operations run
+//! for both widths. GGSW ciphertexts and bootstrapping are `u64` only, as are the seeded,
+//! compressed and checked encryptions.
+
+use crate::ntt;
+use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+use crate::utils::{decode, decompose_rounded, encode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+/// Unsigned scalar of `BITS` bits with wrapping arithmetic, the arithmetic of the torus
+/// discretized modulo `2^BITS`.
+pub trait Torus:
+    Copy + Default + Eq + fmt::Debug + Send + Sync + Serialize + DeserializeOwned + 'static
+{
+    /// Width of the scalar, the torus being discretized modulo `2^BITS`.
+    const BITS: u32;
+
+    /// Low `BITS` bits of `x`.
+    fn from_u64(x: u64) -> Self;
+
+    /// `self` zero-extended to a `u64`, as a plain integer rather than a torus value: see
+    /// `to_msb` for the same point of the torus on a `u64`.
+    fn to_u64(self) -> u64;
+
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    fn wrapping_mul(self, rhs: Self) -> Self;
+
+    fn wrapping_neg(self) -> Self;
+
+    /// `self` in the MSBs of a `u64`.
+    fn to_msb(self) -> u64 {
+        self.to_u64() << (64 - Self::BITS)
+    }
+
+    /// MSBs of `x`, truncated to `BITS` bits.
+    fn from_msb(x: u64) -> Self {
+        Self::from_u64(x >> (64 - Self::BITS))
+    }
+
+    /// `x` modulo `2^BITS`, in two's complement.
+    fn from_i64(x: i64) -> Self {
+        Self::from_u64(x as u64)
+    }
+
+    /// `utils::encode` at the width of `Self`.
+    fn encode(msg: u8) -> Self {
+        Self::from_msb(encode(msg))
+    }
+
+    /// `utils::decode` at the width of `Self`.
+    fn decode(self) -> u8 {
+        decode(self.to_msb())
+    }
+
+    /// `utils::decompose_rounded` at the width of `Self`: the digits are the same as those of
+    /// `to_msb()`, in two's complement on `BITS` bits.
+    fn decompose(
+        self,
+        base_log: DecompositionBaseLog,
+        levels: DecompositionLevelCount,
+    ) -> Vec<Self> {
+        decompose_rounded(self.to_msb(), base_log, levels)
+            .into_iter()
+            .map(Self::from_u64)
+            .collect()
+    }
+
+    /// Negacyclic product of `lhs` and `rhs` with the number theoretic transforms, see
+    /// `ntt::negacyclic_mul`. Products modulo `2^64` reduce to any narrower width.
+    fn negacyclic_mul(lhs: &[Self], rhs: &[Self]) -> Vec<Self> {
+        let widen = |p: &[Self]| p.iter().map(|c| c.to_u64()).collect::<Vec<_>>();
+        ntt::negacyclic_mul(&widen(lhs), &widen(rhs))
+            .into_iter()
+            .map(Self::from_u64)
+            .collect()
+    }
+
+    /// Standard deviation, in units of `2^-BITS`, of the noise of standard deviation `std_dev` in
+    /// units of `2^-64`, as in `Parameters`.
+    fn scale_std_dev(std_dev: f64) -> f64 {
+        std_dev / 2f64.powi(64 - Self::BITS as i32)
+    }
+}
+
+macro_rules! impl_torus {
+    ($t:ty $(, $($overrides:tt)*)?) => {
+        impl Torus for $t {
+            const BITS: u32 = <$t>::BITS;
+
+            fn from_u64(x: u64) -> Self {
+                x as $t
+            }
+
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn wrapping_add(self, rhs: Self) -> Self {
+                <$t>::wrapping_add(self, rhs)
+            }
+
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                <$t>::wrapping_sub(self, rhs)
+            }
+
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                <$t>::wrapping_mul(self, rhs)
+            }
+
+            fn wrapping_neg(self) -> Self {
+                <$t>::wrapping_neg(self)
+            }
+
+            $($($overrides)*)?
+        }
+    };
+}
+
+impl_torus!(u32);
+impl_torus!(
+    u64,
+    fn negacyclic_mul(lhs: &[u64], rhs: &[u64]) -> Vec<u64> {
+        ntt::negacyclic_mul(lhs, rhs)
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+    use crate::torus::Torus;
+    use crate::utils::{decompose_rounded, encode, recompose};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_encode_decode() {
+        for msg in 0..16 {
+            assert_eq!(<u64 as Torus>::encode(msg), encode(msg));
+            assert_eq!(<u32 as Torus>::encode(msg), (msg as u32) << 28);
+            assert_eq!(<u32 as Torus>::encode(msg).decode(), msg);
+            // Noise below half a box rounds away.
+            assert_eq!(
+                <u32 as Torus>::encode(msg).wrapping_add(1 << 26).decode(),
+                msg
+            );
+            assert_eq!(
+                <u32 as Torus>::encode(msg).wrapping_sub(1 << 26).decode(),
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompose() {
+        let (base_log, levels) = (DecompositionBaseLog(4), DecompositionLevelCount(4));
+        for _ in 0..1000 {
+            let x: u64 = thread_rng().gen();
+            assert_eq!(
+                x.decompose(base_log, levels),
+                decompose_rounded(x, base_log, levels)
+            );
+
+            let y: u32 = thread_rng().gen();
+            let digits: Vec<u64> = y
+                .decompose(base_log, levels)
+                .into_iter()
+                .map(|d| d as i32 as u64)
+                .collect();
+            let error = y.wrapping_sub((recompose(&digits, base_log) >> 32) as u32) as i32;
+            assert!(
+                error.unsigned_abs() <= 1 << (32 - 16 - 1),
+                "{y}: error {error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Transciphering: homomorphic decryption of data encrypted under a lightweight stream cipher.
+//!
+//! A client sends its data XORed with a keystream, which is as large as the data, together with
+//! LWE encryptions of the bits of the symmetric key, once. The server evaluates the keystream
+//! homomorphically and removes it, obtaining LWE encryptions of the data.
+//!
+//! The keystream is a toy filter generator in the style of FiLIP. Bit `i` of the keystream selects
+//! 5 bits `x1, ..., x5` of the key at public positions derived from the nonce and computes
+//! `x1 ^ x2 ^ x3 ^ (x4 & x5)`. Bit `i` of the stream is XORed with bit `i % 8` of byte `i / 8`.
+//! **This filter is far too small to be a secure cipher.** It only demonstrates the evaluation,
+//! which costs two bootstraps per keystream bit: the parity of `x1 + x2 + x3`, then the filter of
+//! that parity `p` from `p + 2 (x4 + x5)`, which stays below `P/2`.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::utils::{encode, nonce_rng, NONCE_LEN};
+use rand::seq::index::sample;
+
+/// Number of bits of a symmetric key.
+pub const KEY_BITS: usize = 64;
+
+/// Number of key bits selected for every keystream bit.
+const SELECTED_BITS: usize = 5;
+
+/// Encrypts or decrypts `data` by XORing it with the keystream of `sym_key` and `nonce`.
+/// `sym_key` holds one bit per byte. Errors if it is not made of `KEY_BITS` bits.
+pub fn apply_keystream(
+    data: &[u8],
+    sym_key: &[u8],
+    nonce: [u8; NONCE_LEN],
+) -> Result<Vec<u8>, String> {
+    if sym_key.len() != KEY_BITS || sym_key.iter().any(|&b| b > 1) {
+        return Err(format!("a symmetric key is made of {KEY_BITS} bits"));
+    }
+
+    let selections = selections(nonce, 8 * data.len());
+    Ok(data
+        .iter()
+        .enumerate()
+        .map(|(j, &byte)| {
+            (0..8).fold(byte, |acc, b| {
+                let x = selections[8 * j + b].map(|i| sym_key[i]);
+                acc ^ ((x[0] ^ x[1] ^ x[2] ^ (x[3] & x[4])) << b)
+            })
+        })
+        .collect())
+}
+
+/// Encrypts the bits of `sym_key` under `sk`, for the server.
+pub fn encrypt_key(sym_key: &[u8], sk: &LweSecretKey) -> Vec<LweCiphertext> {
+    sym_key
+        .iter()
+        .map(|&bit| LweCiphertext::encrypt(encode(bit), sk))
+        .collect()
+}
+
+/// Removes the keystream of the encrypted key `enc_key_bits` and `nonce` from `sym_ct`, returning
+/// encryptions of the nibbles of the data, the low nibble of every byte first.
+/// The nibbles are sums of refreshed bits, and may be up to 15: they can be decrypted, but must be
+/// split into bits again, e.g. with `digits::extract_digits`, before being bootstrapped.
+/// Errors if `enc_key_bits` does not hold `KEY_BITS` ciphertexts.
+pub fn transcipher(
+    sym_ct: &[u8],
+    nonce: [u8; NONCE_LEN],
+    enc_key_bits: &[LweCiphertext],
+    key: &EvaluationKey,
+) -> Result<Vec<LweCiphertext>, String> {
+    if enc_key_bits.len() != KEY_BITS {
+        return Err(format!(
+            "the key is made of {KEY_BITS} bits, got {} ciphertexts",
+            enc_key_bits.len()
+        ));
+    }
+    let dim = enc_key_bits[0].mask.len();
+
+    let selections = selections(nonce, 8 * sym_ct.len());
+    let mut nibbles = Vec::with_capacity(2 * sym_ct.len());
+    for (j, &byte) in sym_ct.iter().enumerate() {
+        for half in 0..2 {
+            let mut nibble = LweCiphertext::zero(dim);
+            for b in 4 * half..4 * half + 4 {
+                let x: Vec<&LweCiphertext> = selections[8 * j + b]
+                    .iter()
+                    .map(|&i| &enc_key_bits[i])
+                    .collect();
+                let keystream = keystream_bit(&x, key);
+
+                // The data bit is the keystream bit, flipped if the ciphertext bit is set.
+                let mut bit = if (byte >> b) & 1 == 1 {
+                    LweCiphertext::trivial_encrypt(encode(1), dim).sub(&keystream)
+                } else {
+                    keystream
+                };
+                bit.multiply_constant_assign(1 << (b - 4 * half));
+                nibble = nibble.add(bit);
+            }
+            nibbles.push(nibble);
+        }
+    }
+
+    Ok(nibbles)
+}
+
+/// Evaluates `x[0] ^ x[1] ^ x[2] ^ (x[3] & x[4])` on encrypted bits with two bootstraps.
+fn keystream_bit(x: &[&LweCiphertext], key: &EvaluationKey) -> LweCiphertext {
+    let sum = x[0].clone().add(x[1].clone()).add(x[2].clone());
+    let parity = key.bootstrap(&sum, |m| m % 2);
+
+    let mut and_inputs = x[3].clone().add(x[4].clone());
+    and_inputs.multiply_constant_assign(2);
+    key.bootstrap(&parity.add(and_inputs), |m| (m % 2) ^ (m / 2 == 2) as u8)
+}
+
+/// Positions of the key bits selected by the first `num_bits` bits of the keystream of `nonce`.
+fn selections(nonce: [u8; NONCE_LEN], num_bits: usize) -> Vec<[usize; SELECTED_BITS]> {
+    // The positions are public: they do not depend on the key.
+    let mut rng = nonce_rng(b"ttfhe/transcipher", &[], &nonce);
+    (0..num_bits)
+        .map(|_| {
+            let positions = sample(&mut rng, KEY_BITS, SELECTED_BITS);
+            std::array::from_fn(|i| positions.index(i))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::TestKeys;
+    use crate::params::presets::TEST;
+    use crate::transcipher::{apply_keystream, encrypt_key, transcipher, KEY_BITS};
+    use crate::utils::{decode, NONCE_LEN};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_apply_keystream() {
+        let sym_key: Vec<u8> = (0..KEY_BITS)
+            .map(|_| thread_rng().gen_range(0..2))
+            .collect();
+        let nonce: [u8; NONCE_LEN] = thread_rng().gen();
+        let msg = b"transciphering";
+
+        let ct = apply_keystream(msg, &sym_key, nonce).unwrap();
+        assert_ne!(&ct, msg);
+        assert_eq!(apply_keystream(&ct, &sym_key, nonce).unwrap(), msg);
+
+        let other_nonce: [u8; NONCE_LEN] = thread_rng().gen();
+        assert_ne!(apply_keystream(msg, &sym_key, other_nonce).unwrap(), ct);
+        assert!(apply_keystream(msg, &sym_key[1..], nonce).is_err());
+        assert!(apply_keystream(msg, &[2; KEY_BITS], nonce).is_err());
+    }
+
+    #[test]
+    fn test_transcipher() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let sym_key: Vec<u8> = (0..KEY_BITS)
+            .map(|_| thread_rng().gen_range(0..2))
+            .collect();
+        let nonce: [u8; NONCE_LEN] = thread_rng().gen();
+        let msg = [0x5a, 0xf0];
+        let sym_ct = apply_keystream(&msg, &sym_key, nonce).unwrap();
+
+        let enc_key_bits = encrypt_key(&sym_key, &keys.lwe_sk);
+        let nibbles = transcipher(&sym_ct, nonce, &enc_key_bits, &key).unwrap();
+        let decrypted: Vec<u8> = nibbles
+            .into_iter()
+            .map(|ct| decode(ct.decrypt(&keys.lwe_sk)))
+            .collect();
+        assert_eq!(decrypted, [0xa, 0x5, 0x0, 0xf]);
+
+        assert!(transcipher(&sym_ct, nonce, &enc_key_bits[1..], &key).is_err());
+    }
+}
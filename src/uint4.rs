@@ -0,0 +1,334 @@
+//! Encrypted 4-bit unsigned integers, with wrapping and saturating arithmetic.
+//!
+//! A nibble is held as two base-4 digits. The sum or difference of two digits, offset by a carry
+//! or a borrow, stays below `P/2`, so that a bootstrap can split it into a digit and a carry.
+//!
+//! Wrapping operations cost 3 bootstraps: the carry and the digit of the low sum, then the digit of
+//! the high sum. Saturating operations cost 2 more: one for the overflow flag of the high sum,
+//! and one to clamp the low digit where it overflowed, the high digit being clamped by the
+//! bootstrap that computes it.
+//!
+//! `eval_poly` evaluates a clear polynomial at an encrypted nibble with 7 bootstraps, whatever its
+//! degree. A nibble does not fit below `P/2`, so a single lookup table cannot read it whole, but
+//! modulo 16 `p(y + 8) = p(y) + 8 p'(y)`, the higher powers of 8 vanishing: the top bit of the
+//! nibble only flips the top bit of `p(y)`, where `y` is the nibble modulo 8, and only where
+//! `p'(y)` is odd.
+
+use crate::ggsw::EvaluationKey;
+use crate::lwe::{LweCiphertext, LweSecretKey};
+use crate::utils::{decode, encode};
+use serde::{Deserialize, Serialize};
+
+/// Base of the digits of a nibble.
+const DIGIT_BASE: u8 = 4;
+
+/// Largest value of a nibble.
+pub const MAX: u8 = 15;
+
+/// Encrypted integer in `[0, 15]`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FheUint4 {
+    /// Base-4 digits, the low one first.
+    pub digits: [LweCiphertext; 2],
+}
+
+impl FheUint4 {
+    /// Encrypts `m` under `sk`. Panics if `m` exceeds `MAX`.
+    pub fn encrypt(m: u8, sk: &LweSecretKey) -> Self {
+        assert!(m <= MAX, "{m} does not fit in 4 bits");
+        FheUint4 {
+            digits: [m % DIGIT_BASE, m / DIGIT_BASE].map(|d| LweCiphertext::encrypt(encode(d), sk)),
+        }
+    }
+
+    /// Trivial encryption of `m` of dimension `dim`. Panics if `m` exceeds `MAX`.
+    pub fn trivial(m: u8, dim: usize) -> Self {
+        assert!(m <= MAX, "{m} does not fit in 4 bits");
+        FheUint4 {
+            digits: [m % DIGIT_BASE, m / DIGIT_BASE]
+                .map(|d| LweCiphertext::trivial_encrypt(encode(d), dim)),
+        }
+    }
+
+    pub fn decrypt(&self, sk: &LweSecretKey) -> u8 {
+        let [low, high] = self.digits.clone().map(|d| decode(d.decrypt(sk)));
+        low + DIGIT_BASE * high
+    }
+
+    /// `self + rhs` modulo 16, with 3 bootstraps.
+    pub fn wrapping_add(&self, rhs: &Self, key: &EvaluationKey) -> Self {
+        let (low, high_sum) = self.add_low(rhs, key);
+        let high = key.bootstrap(&high_sum, |m| m % DIGIT_BASE);
+        FheUint4 {
+            digits: [low, high],
+        }
+    }
+
+    /// `self + rhs`, clamped at `MAX`, with 5 bootstraps.
+    pub fn saturating_add(&self, rhs: &Self, key: &EvaluationKey) -> Self {
+        let (low, high_sum) = self.add_low(rhs, key);
+        let overflow = key.bootstrap(&high_sum, |m| (m >= DIGIT_BASE) as u8);
+        let high = key.bootstrap(&high_sum, |m| m.min(DIGIT_BASE - 1));
+        FheUint4 {
+            digits: [clamp_low(low, overflow, DIGIT_BASE - 1, key), high],
+        }
+    }
+
+    /// `self - rhs` modulo 16, with 3 bootstraps.
+    pub fn wrapping_sub(&self, rhs: &Self, key: &EvaluationKey) -> Self {
+        let (low, high_diff) = self.sub_low(rhs, key);
+        let high = key.bootstrap(&high_diff, |m| m % DIGIT_BASE);
+        FheUint4 {
+            digits: [low, high],
+        }
+    }
+
+    /// `self - rhs`, clamped at `0`, with 5 bootstraps.
+    pub fn saturating_sub(&self, rhs: &Self, key: &EvaluationKey) -> Self {
+        let (low, high_diff) = self.sub_low(rhs, key);
+        let underflow = key.bootstrap(&high_diff, |m| (m < DIGIT_BASE) as u8);
+        let high = key.bootstrap(&high_diff, |m| m.saturating_sub(DIGIT_BASE));
+        FheUint4 {
+            digits: [clamp_low(low, underflow, 0, key), high],
+        }
+    }
+
+    /// `self + rhs` modulo 16, with the bootstraps of `wrapping_add`.
+    /// Panics if `rhs` exceeds `MAX`.
+    pub fn wrapping_add_scalar(&self, rhs: u8, key: &EvaluationKey) -> Self {
+        self.wrapping_add(&Self::trivial(rhs, self.dim()), key)
+    }
+
+    /// `self - rhs` modulo 16, with the bootstraps of `wrapping_sub`.
+    /// Panics if `rhs` exceeds `MAX`.
+    pub fn wrapping_sub_scalar(&self, rhs: u8, key: &EvaluationKey) -> Self {
+        self.wrapping_sub(&Self::trivial(rhs, self.dim()), key)
+    }
+
+    /// `self + rhs`, clamped at `MAX`, with the bootstraps of `saturating_add`.
+    /// Panics if `rhs` exceeds `MAX`.
+    pub fn saturating_add_scalar(&self, rhs: u8, key: &EvaluationKey) -> Self {
+        self.saturating_add(&Self::trivial(rhs, self.dim()), key)
+    }
+
+    /// `self - rhs`, clamped at `0`, with the bootstraps of `saturating_sub`.
+    /// Panics if `rhs` exceeds `MAX`.
+    pub fn saturating_sub_scalar(&self, rhs: u8, key: &EvaluationKey) -> Self {
+        self.saturating_sub(&Self::trivial(rhs, self.dim()), key)
+    }
+
+    fn dim(&self) -> usize {
+        self.digits[0].mask.len()
+    }
+
+    /// Low digit of `self + rhs`, and the sum of the high digits and of the carry, below `P/2`.
+    fn add_low(&self, rhs: &Self, key: &EvaluationKey) -> (LweCiphertext, LweCiphertext) {
+        let low_sum = self.digits[0].clone().add(rhs.digits[0].clone());
+        let carry = key.bootstrap(&low_sum, |m| m / DIGIT_BASE);
+        let low = key.bootstrap(&low_sum, |m| m % DIGIT_BASE);
+        let high_sum = self.digits[1].clone().add(rhs.digits[1].clone()).add(carry);
+        (low, high_sum)
+    }
+
+    /// Low digit of `self - rhs`, and the difference of the high digits and of the borrow offset
+    /// by the base: it is below the base if and only if `self < rhs`.
+    fn sub_low(&self, rhs: &Self, key: &EvaluationKey) -> (LweCiphertext, LweCiphertext) {
+        let low_diff = self.digits[0]
+            .clone()
+            .sub(&rhs.digits[0])
+            .add_plaintext(encode(DIGIT_BASE));
+        let borrow = key.bootstrap(&low_diff, |m| (m < DIGIT_BASE) as u8);
+        let low = key.bootstrap(&low_diff, |m| m % DIGIT_BASE);
+        let high_diff = self.digits[1]
+            .clone()
+            .sub(&rhs.digits[1])
+            .sub(&borrow)
+            .add_plaintext(encode(DIGIT_BASE));
+        (low, high_diff)
+    }
+}
+
+/// Replaces the digit `low` by `bound` where the encrypted `flag` is `1`, with one bootstrap.
+fn clamp_low(
+    low: LweCiphertext,
+    mut flag: LweCiphertext,
+    bound: u8,
+    key: &EvaluationKey,
+) -> LweCiphertext {
+    flag.multiply_constant_assign(DIGIT_BASE as u64);
+    key.bootstrap(&low.add(flag), |m| if m >= DIGIT_BASE { bound } else { m })
+}
+
+/// `c_0 + c_1 x + ... + c_d x^d` modulo 16, where `coeffs` are `c_0, ..., c_d`, with 7
+/// bootstraps. Without coefficients, the result is a trivial zero.
+pub fn eval_poly(coeffs: &[u64], x: &FheUint4, key: &EvaluationKey) -> FheUint4 {
+    if coeffs.is_empty() {
+        return FheUint4::trivial(0, x.dim());
+    }
+    let p = |m: u8| eval_clear(coeffs, m);
+
+    let [low, high] = &x.digits;
+    let top_bit = key.bootstrap(high, |m| m / 2);
+    // The nibble modulo 8, below `P/2`.
+    let y = low
+        .clone()
+        .add(key.bootstrap(high, |m| DIGIT_BASE * (m % 2)));
+
+    let low_digit = key.bootstrap(&y, |m| p(m) % DIGIT_BASE);
+    let high_digit = key.bootstrap(&y, |m| p(m) / DIGIT_BASE);
+    // `1` where `p(y + 8)` and `p(y)` differ, by 8.
+    let mut flip = key.bootstrap(&y, |m| (p(m + 8) != p(m)) as u8);
+    flip.multiply_constant_assign(2);
+    let flip = key.bootstrap(&flip.add(top_bit), |m| 2 * (m == 3) as u8);
+    FheUint4 {
+        digits: [
+            low_digit,
+            key.bootstrap(&high_digit.add(flip), |m| m % DIGIT_BASE),
+        ],
+    }
+}
+
+/// `p(m)` modulo 16, by Horner's rule.
+fn eval_clear(coeffs: &[u64], m: u8) -> u8 {
+    let p = coeffs
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &c| acc.wrapping_mul(m as u64).wrapping_add(c));
+    (p % 16) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ggsw::{BootstrappingKey, EvaluationKey, GgswCiphertext, TestKeys};
+    use crate::glwe::keygen_for;
+    use crate::lwe::{compute_ksk, lwe_keygen_for, KeyDistribution};
+    use crate::params::presets::TEST;
+    use crate::uint4::{eval_clear, eval_poly, FheUint4, MAX};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_all_pairs() {
+        // Trivial ciphertexts through a bootstrapping key of trivial GGSW ciphertexts take the
+        // fast paths of the external product and of the keyswitch, which makes the exhaustive
+        // check of the tables cheap. The key bits do not matter since the masks are zero.
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let bsk = BootstrappingKey {
+            ggsws: vec![GgswCiphertext::trivial_encrypt(0, &TEST); TEST.lwe_dim.0],
+            distribution: KeyDistribution::Binary,
+        };
+        let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+        let key = EvaluationKey {
+            bsk: &bsk,
+            ksk: &ksk,
+            params: &TEST,
+        };
+
+        for a in 0..=MAX {
+            for b in 0..=MAX {
+                let x = FheUint4::trivial(a, TEST.lwe_dim.0);
+                let y = FheUint4::trivial(b, TEST.lwe_dim.0);
+                let decrypt = |z: FheUint4| z.decrypt(&lwe_sk);
+                assert_eq!(decrypt(x.wrapping_add(&y, &key)), (a + b) % 16, "{a} + {b}");
+                assert_eq!(
+                    decrypt(x.saturating_add(&y, &key)),
+                    (a + b).min(MAX),
+                    "{a} + {b}"
+                );
+                assert_eq!(
+                    decrypt(x.wrapping_sub(&y, &key)),
+                    a.wrapping_sub(b) % 16,
+                    "{a} - {b}"
+                );
+                assert_eq!(
+                    decrypt(x.saturating_sub(&y, &key)),
+                    a.saturating_sub(b),
+                    "{a} - {b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_saturating() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        let fifteen = FheUint4::encrypt(15, &keys.lwe_sk);
+        let zero = FheUint4::encrypt(0, &keys.lwe_sk);
+        let five = FheUint4::encrypt(5, &keys.lwe_sk);
+        assert_eq!(
+            fifteen.saturating_add(&fifteen, &key).decrypt(&keys.lwe_sk),
+            15
+        );
+        assert_eq!(zero.saturating_sub(&five, &key).decrypt(&keys.lwe_sk), 0);
+        assert_eq!(
+            fifteen.saturating_sub(&five, &key).decrypt(&keys.lwe_sk),
+            10
+        );
+        assert_eq!(five.saturating_sub_scalar(7, &key).decrypt(&keys.lwe_sk), 0);
+
+        // 5 + 6 + 3 + 4 saturates at the last addition.
+        let chained = [6, 3, 4].iter().fold(five, |acc, &m| {
+            acc.saturating_add(&FheUint4::encrypt(m, &keys.lwe_sk), &key)
+        });
+        assert_eq!(chained.decrypt(&keys.lwe_sk), 15);
+        let chained = zero
+            .saturating_add_scalar(6, &key)
+            .saturating_add_scalar(3, &key)
+            .saturating_add_scalar(2, &key);
+        assert_eq!(chained.decrypt(&keys.lwe_sk), 11);
+    }
+
+    #[test]
+    fn test_eval_poly_all_nibbles() {
+        // Trivial ciphertexts and key, as in `test_all_pairs`.
+        let lwe_sk = lwe_keygen_for(&TEST, &mut thread_rng());
+        let glwe_sk = keygen_for(&TEST, &mut thread_rng());
+        let bsk = BootstrappingKey {
+            ggsws: vec![GgswCiphertext::trivial_encrypt(0, &TEST); TEST.lwe_dim.0],
+            distribution: KeyDistribution::Binary,
+        };
+        let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+        let key = EvaluationKey {
+            bsk: &bsk,
+            ksk: &ksk,
+            params: &TEST,
+        };
+
+        let polys: [&[u64]; 5] = [
+            &[],
+            &[7],
+            &[3, 5],
+            &[1, 2, 0, 1],
+            &[9, 4, 13, 1, 6, 0, 2, 11, 5, 8, 3, 15, 7, 1, 10, 12],
+        ];
+        for coeffs in polys {
+            for m in 0..=MAX {
+                let x = FheUint4::trivial(m, TEST.lwe_dim.0);
+                assert_eq!(
+                    eval_poly(coeffs, &x, &key).decrypt(&lwe_sk),
+                    eval_clear(coeffs, m),
+                    "{coeffs:?} at {m}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_poly() {
+        let keys = TestKeys::new(&TEST);
+        let key = keys.evaluation_key();
+
+        // x^3 + 2x + 1
+        for m in 0..=MAX {
+            let x = FheUint4::encrypt(m, &keys.lwe_sk);
+            let expected = (m as u32 * m as u32 * m as u32 + 2 * m as u32 + 1) % 16;
+            assert_eq!(
+                eval_poly(&[1, 2, 0, 1], &x, &key).decrypt(&keys.lwe_sk) as u32,
+                expected,
+                "{m}"
+            );
+        }
+    }
+}
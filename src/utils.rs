@@ -1,9 +1,123 @@
+use crate::error::TfheError;
+use crate::params::{DecompositionBaseLog, DecompositionLevelCount};
+use crate::poly::ResiduePoly;
+use crate::P;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rand_distr::Exp1;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+
+/// Length in bytes of the nonces of deterministic encryptions.
+pub const NONCE_LEN: usize = 24;
+
+/// Length in bytes of the seeds of the masks of seeded ciphertexts and keys.
+pub const SEED_LEN: usize = 32;
+
+/// Encodes `msg` in the MSBs. Bits of `msg` above the plaintext modulus `P` are dropped, see
+/// `try_encode`.
 pub fn encode(msg: u8) -> u64 {
     (msg as u64) << 60
 }
 
+/// Encodes `msg` in the MSBs. Errors if `msg` does not fit below the plaintext modulus `P`.
+pub fn try_encode(msg: u8) -> Result<u64, TfheError> {
+    if msg as usize >= P {
+        return Err(TfheError::MessageOutOfRange {
+            msg: msg as u64,
+            max: P as u64,
+        });
+    }
+    Ok(encode(msg))
+}
+
 pub fn decode(mu: u64) -> u8 {
-    ((((mu >> 59) + 1) >> 1) % 16) as u8
+    decode_bits(mu, P.ilog2())
+}
+
+/// Encodes a message of `bits` bits in the MSBs. Bits of `msg` above `2^bits` are dropped.
+/// Panics if `bits` is not between 1 and 8.
+pub fn encode_bits(msg: u8, bits: u32) -> u64 {
+    assert!(
+        (1..=8).contains(&bits),
+        "messages of {bits} bits do not fit in a u8"
+    );
+    (msg as u64) << (64 - bits)
+}
+
+/// Decodes a message of `bits` bits encoded in the MSBs, rounding to the nearest.
+/// Panics if `bits` is not between 1 and 8.
+pub fn decode_bits(mu: u64, bits: u32) -> u8 {
+    assert!(
+        (1..=8).contains(&bits),
+        "messages of {bits} bits do not fit in a u8"
+    );
+    let shift = 64 - bits;
+    ((((mu >> (shift - 1)) + 1) >> 1) % (1 << bits)) as u8
+}
+
+/// Encodes `msgs` in the first coefficients of a message polynomial of degree `poly_size`, the
+/// others being zero. Additions of the ciphertexts then add the messages slot by slot.
+/// Panics if there are more messages than coefficients.
+pub fn encode_poly(msgs: &[u8], poly_size: usize) -> ResiduePoly {
+    assert!(
+        msgs.len() <= poly_size,
+        "{} messages for {poly_size} coefficients",
+        msgs.len()
+    );
+
+    let mut poly = ResiduePoly::zero(poly_size);
+    for (coef, &msg) in poly.coefs.iter_mut().zip(msgs) {
+        *coef = encode(msg);
+    }
+    poly
+}
+
+/// Decodes every coefficient of a message polynomial, as returned by
+/// `GlweCiphertext::decrypt_poly`.
+pub fn decode_poly(coefs: &[u64]) -> Vec<u8> {
+    coefs.iter().map(|&mu| decode(mu)).collect()
+}
+
+/// Decodes the phase of a ciphertext switched to the modulus `2^log2_modulus`, see
+/// `LweCiphertext::decrypt_modswitched_to`. Panics if the modulus cannot hold a message.
+pub fn decode_modswitched(phase: u64, log2_modulus: u32) -> u8 {
+    assert!(
+        (4..64).contains(&log2_modulus),
+        "the modulus 2^{log2_modulus} cannot hold a message"
+    );
+    decode(phase << (64 - log2_modulus))
+}
+
+/// Plaintext modulus of the checked encoding, see `encode_checked`.
+pub const CHECKED_P: u8 = 8;
+
+/// Number of redundant bits below the message bits in the checked encoding.
+pub const CHECK_BITS: u32 = 5;
+
+/// Encodes `msg` in the 3 MSBs, followed by `CHECK_BITS` bits that decrypt to zero as long as the
+/// noise is below `2^(61 - CHECK_BITS)`, see `decode_checked`. The encoding is additive modulo
+/// `CHECKED_P`, like `encode` modulo `P`. Panics if `msg` is not below `CHECKED_P`.
+pub fn encode_checked(msg: u8) -> u64 {
+    assert!(
+        msg < CHECKED_P,
+        "message {msg} is out of the checked plaintext space [0, {CHECKED_P})"
+    );
+    (msg as u64) << 61
+}
+
+/// Decodes the output of `encode_checked`, erroring with `TfheError::NoiseOverflow` if the check
+/// bits are not zero after rounding: the noise overflowed them and may have corrupted the message.
+/// A uniformly random phase, as after a noise blow-up, passes the check with probability
+/// `2^(1 - CHECK_BITS)`.
+pub fn decode_checked(mu: u64) -> Result<u8, TfheError> {
+    let msg = (((mu >> 60) + 1) >> 1) % CHECKED_P as u64;
+    let noise = (mu.wrapping_sub(msg << 61) as i64).unsigned_abs();
+    let bound = 1 << (61 - CHECK_BITS);
+    if noise >= bound {
+        return Err(TfheError::NoiseOverflow { noise, bound });
+    }
+    Ok(msg as u8)
 }
 
 pub fn decode_bootstrapped(mu: u64) -> u8 {
@@ -14,9 +128,278 @@ pub fn decode_bootstrapped(mu: u64) -> u8 {
     }
 }
 
+/// Draws a noise from the discrete Gaussian of parameter `std_dev` centered on zero, which gives
+/// every integer `z` a probability proportional to `exp(-z^2 / (2 std_dev^2))`.
+///
+/// Rejection sampling from a discrete Laplace distribution of scale `floor(std_dev) + 1`, after
+/// Canonne, Kamath and Steinke, "The Discrete Gaussian for Differential Privacy" (2020), in
+/// floating point rather than exact arithmetic. Between 44% and 76% of the proposals are accepted,
+/// growing with `std_dev`.
+/// Panics if `std_dev` is negative or not finite.
+pub fn sample_gaussian<R: Rng + ?Sized>(std_dev: f64, rng: &mut R) -> i64 {
+    assert!(
+        std_dev.is_finite() && std_dev >= 0.0,
+        "invalid standard deviation {std_dev}"
+    );
+    if std_dev == 0.0 {
+        return 0;
+    }
+
+    let scale = std_dev.floor() + 1.0;
+    let variance = std_dev * std_dev;
+    loop {
+        // The floor of an exponential of mean `scale` is geometric, of ratio `exp(-1 / scale)`.
+        let magnitude = (scale * rng.sample::<f64, _>(Exp1)).floor();
+        let negative = rng.gen::<bool>();
+        // Zero would otherwise be drawn twice as often as with its two signs.
+        if negative && magnitude == 0.0 {
+            continue;
+        }
+        let gap = magnitude - variance / scale;
+        if rng.gen::<f64>() < (-gap * gap / (2.0 * variance)).exp() {
+            let z = magnitude as i64;
+            return if negative { -z } else { z };
+        }
+    }
+}
+
 pub fn round_value(val: u64) -> u64 {
     let mut rounded_val = val >> 47;
     rounded_val += rounded_val & 1;
     rounded_val >>= 1;
     rounded_val
 }
+
+/// SHAKE256 digest of `domain` and `coefs`, identifying a secret key without revealing it.
+pub fn fingerprint(domain: &[u8], coefs: &[u64]) -> [u8; 32] {
+    let mut hasher = Shake256::default();
+    hasher.update(domain);
+    for coef in coefs {
+        hasher.update(&coef.to_le_bytes());
+    }
+
+    let mut digest = [0u8; 32];
+    hasher.finalize_xof().read(&mut digest);
+    digest
+}
+
+/// First 4 bytes of the SHAKE256 digest of `coefs`, in hex: tells values apart in logs without
+/// printing them.
+pub fn short_digest(coefs: &[u64]) -> String {
+    fingerprint(b"ttfhe/debug", coefs)[..4]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Overwrites `coefs` with zeros, with volatile writes that the compiler cannot elide even when
+/// `coefs` is dropped right after. Copies made by earlier reallocations are not wiped.
+pub(crate) fn wipe<T: Copy + Default>(coefs: &mut [T]) {
+    for coef in coefs.iter_mut() {
+        // SAFETY: `coef` is a valid, aligned and exclusive reference.
+        unsafe { std::ptr::write_volatile(coef, T::default()) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Maps `f` over `items`, in parallel with the `parallel` feature.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn par_map<T, U>(items: &[T], f: impl Fn(&T) -> U) -> Vec<U> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn par_map<T: Sync, U: Send>(items: &[T], f: impl Fn(&T) -> U + Sync + Send) -> Vec<U> {
+    use rayon::prelude::*;
+    items.par_iter().map(f).collect()
+}
+
+/// Stream of the masks of a seeded ciphertext or key: ChaCha20 seeded with `seed`.
+pub fn mask_rng(seed: &[u8; SEED_LEN]) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed(*seed)
+}
+
+/// Randomness of a deterministic encryption: a ChaCha20 stream seeded from the SHAKE256 XOF of
+/// `domain`, the coefficients `key` of the secret key and `nonce`. Keyed by the secret
+/// coefficients rather than by a public digest of them, the stream cannot be recomputed without
+/// the key, which would reveal the noise and thus the key from the ciphertexts.
+pub fn nonce_rng<'a>(
+    domain: &[u8],
+    key: impl IntoIterator<Item = &'a u64>,
+    nonce: &[u8; NONCE_LEN],
+) -> ChaCha20Rng {
+    let mut hasher = Shake256::default();
+    hasher.update(domain);
+    for coef in key {
+        hasher.update(&coef.to_le_bytes());
+    }
+    hasher.update(nonce);
+
+    let mut seed = [0u8; 32];
+    hasher.finalize_xof().read(&mut seed);
+    ChaCha20Rng::from_seed(seed)
+}
+
+/// Approximate signed decomposition with lg(B) = `base_log` of `val`, first rounded to the
+/// closest multiple of `q / B^levels`: the recomposition is within `q / (2 B^levels)` of `val`.
+/// Digits are given from the least significant level, in `[-B/2, B/2)` as u64.
+pub fn decompose_rounded(
+    val: u64,
+    base_log: DecompositionBaseLog,
+    levels: DecompositionLevelCount,
+) -> Vec<u64> {
+    let (base_log, levels) = (base_log.0, levels.0);
+    let mut rounded_val = val >> (63 - base_log * levels);
+    rounded_val += rounded_val & 1;
+    rounded_val >>= 1;
+
+    let digit_mask = (1u64 << base_log) - 1;
+    let mut carry = 0u64;
+    (0..levels)
+        .map(|i| {
+            let res = ((rounded_val >> (base_log * i)) & digit_mask) + carry;
+            carry = (res >= 1 << (base_log - 1)) as u64;
+            res.wrapping_sub(carry << base_log)
+        })
+        .collect()
+}
+
+/// Inverse of the approximate decompositions: recomposes `digits`, given from the least
+/// significant level and in two's complement, with lg(B) = `base_log` into the MSBs of a value in
+/// Z_{2^64}.
+pub fn recompose(digits: &[u64], base_log: DecompositionBaseLog) -> u64 {
+    let base_log = base_log.0;
+    let shift = 64 - base_log * digits.len();
+    digits.iter().enumerate().fold(0u64, |acc, (i, d)| {
+        acc.wrapping_add(d << (shift + base_log * i))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TfheError;
+    use crate::ggsw::decomposition_8_2;
+    use crate::lwe::decomposition_4_4;
+    use crate::params::{DecompositionBaseLog as BaseLog, DecompositionLevelCount as Levels};
+    use crate::utils::{decode, decode_bits, decompose_rounded, encode, encode_bits, recompose};
+    use crate::utils::{decode_checked, encode_checked, CHECK_BITS};
+    use crate::utils::{sample_gaussian, try_encode};
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_decode_checked_bounds() {
+        let bound = 1u64 << (61 - CHECK_BITS);
+        for msg in 0..8 {
+            let mu = encode_checked(msg);
+            assert_eq!(decode_checked(mu), Ok(msg));
+            assert_eq!(decode_checked(mu.wrapping_add(bound - 1)), Ok(msg));
+            assert_eq!(decode_checked(mu.wrapping_sub(bound - 1)), Ok(msg));
+            assert_eq!(
+                decode_checked(mu.wrapping_add(bound)),
+                Err(TfheError::NoiseOverflow {
+                    noise: bound,
+                    bound
+                })
+            );
+            assert!(decode_checked(mu.wrapping_sub(bound)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_try_encode() {
+        for msg in 0..16 {
+            assert_eq!(try_encode(msg), Ok(encode(msg)));
+            assert_eq!(decode(encode(msg)), msg);
+        }
+        assert_eq!(
+            try_encode(16),
+            Err(TfheError::MessageOutOfRange { msg: 16, max: 16 })
+        );
+        assert_eq!(
+            try_encode(16).unwrap_err().to_string(),
+            "message 16 is out of the plaintext space [0, 16)"
+        );
+        assert!(try_encode(u8::MAX).is_err());
+        // The infallible encoding drops the high bits.
+        assert_eq!(encode(17), encode(1));
+
+        for bits in 1..=8 {
+            assert_eq!(
+                encode_bits(u8::MAX, bits),
+                encode_bits(u8::MAX >> (8 - bits), bits)
+            );
+            for msg in 0..1u8 << (bits - 1) {
+                assert_eq!(decode_bits(encode_bits(msg, bits), bits), msg);
+            }
+        }
+        assert_eq!(encode_bits(3, 4), encode(3));
+    }
+
+    #[test]
+    fn test_decompose() {
+        for _ in 0..10000 {
+            let val = thread_rng().gen::<u64>();
+
+            assert_eq!(
+                decompose_rounded(val, BaseLog(4), Levels(4)),
+                decomposition_4_4(val)
+            );
+            let (low, high) = decomposition_8_2(val);
+            assert_eq!(
+                decompose_rounded(val, BaseLog(8), Levels(2)),
+                [low as u64, high as u64]
+            );
+
+            for (base_log, levels) in [(3, 5), (6, 3), (10, 2), (1, 20)] {
+                let digits = decompose_rounded(val, BaseLog(base_log), Levels(levels));
+                let half_base = 1i64 << (base_log - 1);
+                assert!(digits
+                    .iter()
+                    .all(|&d| (-half_base..half_base).contains(&(d as i64))));
+
+                let error = recompose(&digits, BaseLog(base_log)).wrapping_sub(val) as i64;
+                assert!(error.unsigned_abs() <= 1 << (63 - base_log * levels));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_gaussian() {
+        let samples = 200_000;
+        assert_eq!(sample_gaussian(0.0, &mut thread_rng()), 0);
+
+        // Small parameters tell the discrete Gaussian apart from the rounded continuous one, which
+        // draws 0 with probability 0.383 instead of 0.399 for `std_dev = 1`.
+        for std_dev in [1.0f64, 3.2] {
+            let rho = |z: i64| (-((z * z) as f64) / (2.0 * std_dev * std_dev)).exp();
+            let total: f64 = (-100..=100).map(rho).sum();
+            let mut counts = [0usize; 3];
+            for _ in 0..samples {
+                let z = sample_gaussian(std_dev, &mut thread_rng());
+                if z.abs() <= 1 {
+                    counts[(z + 1) as usize] += 1;
+                }
+            }
+            for (z, count) in (-1..=1).zip(counts) {
+                let measured = count as f64 / samples as f64;
+                let expected = rho(z) / total;
+                assert!(
+                    (measured - expected).abs() < 0.005,
+                    "std_dev {std_dev}, P({z}): expected {expected}, measured {measured}"
+                );
+            }
+        }
+
+        let std_dev = (1u64 << 40) as f64;
+        let draws: Vec<f64> = (0..samples)
+            .map(|_| sample_gaussian(std_dev, &mut thread_rng()) as f64)
+            .collect();
+        let mean = draws.iter().sum::<f64>() / samples as f64;
+        let variance = draws.iter().map(|x| x * x).sum::<f64>() / samples as f64;
+        assert!(mean.abs() < 0.02 * std_dev, "mean {mean:e}");
+        assert!(
+            (variance / (std_dev * std_dev) - 1.0).abs() < 0.02,
+            "variance {variance:e}"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! The counters are process-wide, so these tests live in their own test binary and are serialized.
+#![cfg(feature = "metrics")]
+
+use std::sync::Mutex;
+use std::time::Duration;
+use ttfhe::ggsw::{cmux, GgswCiphertext};
+use ttfhe::glwe::{keygen, GlweCiphertext};
+use ttfhe::lwe::{compute_ksk, lwe_keygen};
+use ttfhe::metrics::{self, Operation};
+use ttfhe::utils::encode;
+use ttfhe::{k, ELL};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_scripted_counts() {
+    let _guard = LOCK.lock().unwrap();
+
+    let lwe_sk = lwe_keygen();
+    let glwe_sk = keygen();
+    let ksk = compute_ksk(&glwe_sk.recode(), &lwe_sk);
+    let ggsw = GgswCiphertext::encrypt(1, &glwe_sk);
+    let ct1 = GlweCiphertext::encrypt(encode(2), &glwe_sk);
+    let ct2 = GlweCiphertext::encrypt(encode(3), &glwe_sk);
+
+    metrics::reset();
+    let before = metrics::snapshot();
+    assert!(Operation::ALL.iter().all(|&op| before.count(op) == 0));
+
+    let product = ggsw.external_product(&ct1);
+    let selected = cmux(&ggsw, &ct1, &product);
+    let extracted = selected.sample_extract();
+    let keyswitched = extracted.keyswitch(&ksk);
+    extracted.keyswitch(&ksk);
+    keyswitched.modswitch();
+    ct1.body.mul(&ct2.body);
+
+    let spent = metrics::snapshot().since(&before);
+    let products_per_external = ((k + 1) * ELL * (k + 1)) as u64;
+    assert_eq!(spent.count(Operation::ExternalProduct), 2);
+    assert_eq!(spent.count(Operation::Keyswitch), 2);
+    assert_eq!(spent.count(Operation::Modswitch), 1);
+    assert_eq!(spent.count(Operation::BlindRotation), 0);
+    assert_eq!(
+        spent.count(Operation::PolyMul),
+        2 * products_per_external + 1
+    );
+    assert!(spent.duration(Operation::ExternalProduct) > Duration::ZERO);
+
+    metrics::reset();
+    assert_eq!(metrics::snapshot(), Default::default());
+}
+
+#[test]
+fn test_trivial_fast_paths() {
+    let _guard = LOCK.lock().unwrap();
+
+    let glwe_sk = keygen();
+    let ksk = compute_ksk(&glwe_sk.recode(), &lwe_keygen());
+    let ct1 = GlweCiphertext::encrypt(encode(2), &glwe_sk);
+    let ct2 = GlweCiphertext::encrypt(encode(3), &glwe_sk);
+    let selector = GgswCiphertext::trivial_encrypt(1, &glwe_sk.params);
+    let trivial = GlweCiphertext::trivial_encrypt_for(encode(5), &glwe_sk.params);
+
+    let before = metrics::snapshot();
+    let selected = cmux(&selector, &ct1, &ct2);
+    GgswCiphertext::trivial_encrypt(3, &glwe_sk.params).external_product(&ct1);
+    let keyswitched = trivial.sample_extract().keyswitch(&ksk);
+    let spent = metrics::snapshot().since(&before);
+
+    assert_eq!(spent.count(Operation::ExternalProduct), 0);
+    assert_eq!(spent.count(Operation::Keyswitch), 0);
+    assert_eq!(spent.count(Operation::PolyMul), 0);
+    assert_eq!(selected.decrypt(&glwe_sk), ct2.decrypt(&glwe_sk));
+    assert!(keyswitched.is_trivial());
+}
+
+#[test]
+fn test_concurrent_counts() {
+    let _guard = LOCK.lock().unwrap();
+
+    let lwe_sk = lwe_keygen();
+    let ct = GlweCiphertext::encrypt(encode(5), &keygen())
+        .sample_extract()
+        .keyswitch(&compute_ksk(&keygen().recode(), &lwe_sk));
+
+    let before = metrics::snapshot();
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..250 {
+                    ct.modswitch();
+                }
+            });
+        }
+    });
+    assert_eq!(
+        metrics::snapshot()
+            .since(&before)
+            .count(Operation::Modswitch),
+        1000
+    );
+}
+
+#[test]
+fn test_reset_between_snapshots() {
+    let _guard = LOCK.lock().unwrap();
+
+    let lwe_sk = lwe_keygen();
+    let ct = GlweCiphertext::encrypt(encode(5), &keygen())
+        .sample_extract()
+        .keyswitch(&compute_ksk(&keygen().recode(), &lwe_sk));
+
+    metrics::reset();
+    for _ in 0..3 {
+        ct.modswitch();
+    }
+    let before = metrics::snapshot();
+    metrics::reset();
+    ct.modswitch();
+
+    let spent = metrics::snapshot().since(&before);
+    assert_eq!(spent.count(Operation::Modswitch), 0);
+    assert_eq!(spent.count(Operation::Keyswitch), 0);
+}
@@ -0,0 +1,40 @@
+//! Full client/server flow of the high-level API, with the keys and the ciphertexts crossing the
+//! boundary as bytes.
+
+use rand::{thread_rng, Rng};
+use ttfhe::params::presets::TOY;
+use ttfhe::serialization::{Serializable, SerializableKey};
+use ttfhe::shortint::{ClientKey, ServerKey};
+use ttfhe::uint4::FheUint4;
+
+#[test]
+fn test_client_server_flow() {
+    // Client side.
+    let (client_key, server_key) = ClientKey::generate(&TOY);
+    let server_key_bytes = server_key.to_bytes();
+    let client_key = ClientKey::from_bytes(&client_key.to_bytes()).unwrap();
+
+    for _ in 0..4 {
+        let [a, b, c]: [u8; 3] = thread_rng().gen::<[u8; 3]>().map(|m| m % 16);
+        let inputs: Vec<Vec<u8>> = [a, b, c]
+            .iter()
+            .map(|&m| client_key.encrypt(m).to_bytes())
+            .collect();
+
+        // Server side, from bytes only.
+        let server_key = ServerKey::from_bytes(&server_key_bytes).unwrap();
+        let params = server_key.params();
+        let [ct_a, ct_b, ct_c]: [FheUint4; 3] =
+            std::array::from_fn(|i| FheUint4::from_bytes_for(&inputs[i], params).unwrap());
+        let res = server_key.sub(&server_key.add(&ct_a, &ct_b), &ct_c);
+        let output = res.to_bytes();
+
+        // Back on the client side.
+        let res = FheUint4::from_bytes_for(&output, client_key.params()).unwrap();
+        assert_eq!(
+            client_key.decrypt(&res),
+            a.wrapping_add(b).wrapping_sub(c) % 16,
+            "({a} + {b}) - {c}"
+        );
+    }
+}
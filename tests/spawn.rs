@@ -0,0 +1,66 @@
+//! Concurrent bootstraps against a shared server key, driven by a minimal executor.
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use ttfhe::boolean::gen_keys;
+use ttfhe::lwe::LweCiphertext;
+use ttfhe::params::presets::TOY;
+use ttfhe::spawn::spawn;
+use ttfhe::utils::{decode, encode};
+
+/// Unparks the thread of the executor.
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls all of `futures` on the current thread until they are all ready.
+fn join_all<F: Future + Unpin>(mut futures: Vec<F>) -> Vec<F::Output> {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    while outputs.iter().any(Option::is_none) {
+        for (future, output) in futures.iter_mut().zip(&mut outputs) {
+            if output.is_none() {
+                if let Poll::Ready(res) = Pin::new(future).poll(&mut cx) {
+                    *output = Some(res);
+                }
+            }
+        }
+        if outputs.iter().any(Option::is_none) {
+            thread::park();
+        }
+    }
+    outputs.into_iter().map(Option::unwrap).collect()
+}
+
+#[test]
+fn test_concurrent_bootstraps() {
+    let (client_key, server_key) = gen_keys(&TOY);
+    let server_key = Arc::new(server_key);
+
+    let futures: Vec<_> = (0..100)
+        .map(|i| {
+            let ct = LweCiphertext::encrypt(encode(i % 8), &client_key.sk);
+            server_key.spawn_bootstrap(ct, |m| 15 - m)
+        })
+        .collect();
+    let outputs = join_all(futures);
+
+    for (i, ct) in outputs.into_iter().enumerate() {
+        assert_eq!(decode(ct.decrypt(&client_key.sk)), 15 - (i as u8 % 8));
+    }
+}
+
+#[test]
+#[should_panic(expected = "spawned computation panicked")]
+fn test_spawn_panics() {
+    join_all(vec![spawn::<()>(|| panic!("bootstrap failed"))]);
+}